@@ -0,0 +1,1438 @@
+//! The [`Benchmark`] trait and the [`Registry`] of benchmarks a run can
+//! select from by name.
+//!
+//! A benchmark builds its request params, says what the server must have
+//! finished before timing starts, picks a runner style, and optionally
+//! validates and summarizes responses and checks `expect` fields. Most LSP
+//! requests only need a params builder: see [`Request`]. Scenarios with their
+//! own protocol (typing, cancellation, load, ...) override [`Benchmark::run`].
+
+use crate::capabilities;
+use crate::config::{MethodConfig, SyncMode};
+use crate::expect::VerifyTally;
+use crate::helpers::{uri, ServerProbe};
+use crate::position::{sync_label, PositionEncoding};
+use crate::results::{BenchResult, BenchRow};
+use crate::runners::{
+    bench_call_hierarchy, bench_cancel, bench_code_action, bench_diagnostics,
+    bench_edit_diagnostics, bench_load, bench_lsp_create_sequence, bench_lsp_delete_sequence,
+    bench_lsp_delta, bench_lsp_didopen, bench_lsp_method, bench_lsp_method_cold,
+    bench_lsp_rename_sequence, bench_lsp_snapshots, bench_spawn, bench_typing, ResolvedDidOpen,
+    ResolvedSnapshot,
+};
+use crate::sandbox::{CloneMode, Sandbox, SandboxMode};
+use crate::ServerConfig;
+use console::style;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// What a server must have finished before the first timed request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// Diagnostics for the opened file (the single-file build).
+    Diagnostics,
+    /// The background project index: the `$/progress` end that follows the
+    /// file build. Needed by cross-file requests such as references.
+    ProjectIndex,
+}
+
+/// How a benchmark drives the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runner {
+    /// Fresh server per iteration, timed from spawn through `initialize`.
+    Spawn,
+    /// One server; the request is repeated `warmup + iterations` times.
+    Warm,
+    /// Fresh server per iteration, timed from `didOpen` through the response.
+    Cold,
+    /// One request after each `didChange` snapshot.
+    Snapshots,
+    /// One request after each `didOpen` step.
+    DidOpen,
+    /// Rename, create or delete lifecycle steps, each mutating the project.
+    Sequence,
+    /// The benchmark overrides [`Benchmark::run`].
+    Custom,
+}
+
+/// Everything resolved from the config that a benchmark needs to run.
+#[derive(Clone, Copy)]
+pub struct BenchContext<'a> {
+    /// Project root URI.
+    pub root: &'a str,
+    /// Project directory servers are spawned in.
+    pub cwd: &'a Path,
+    /// Target file (`file` in the config), already joined onto `cwd`.
+    pub file: &'a Path,
+    pub line: u32,
+    pub col: u32,
+    pub methods: &'a HashMap<String, MethodConfig>,
+    /// Legacy top-level `trigger_character`.
+    pub trigger_character: Option<&'a str>,
+    pub timeout: Duration,
+    pub index_timeout: Duration,
+    pub warmup: usize,
+    pub iterations: usize,
+    pub response_limit: usize,
+    pub init_settings: Option<&'a Value>,
+    pub verbose: bool,
+    /// Capability probes by server label.
+    pub probes: &'a HashMap<String, ServerProbe>,
+    /// Registry used to build params for requests sent by composite
+    /// benchmarks (typing, cancellation, load).
+    pub registry: &'a Registry,
+    /// Which benchmarks run in a clone of the project.
+    pub sandbox: SandboxMode,
+    pub sandbox_clone: CloneMode,
+}
+
+impl<'a> BenchContext<'a> {
+    /// Per-method config for `method`, if any.
+    pub fn method(&self, method: &str) -> Option<&'a MethodConfig> {
+        self.methods.get(method)
+    }
+
+    /// Line/col for `method`, falling back to the global defaults.
+    pub fn pos(&self, method: &str) -> (u32, u32) {
+        self.method(method)
+            .map(|m| (m.line.unwrap_or(self.line), m.col.unwrap_or(self.col)))
+            .unwrap_or((self.line, self.col))
+    }
+
+    /// Start line/col for range-based requests, falling back to (0, 0).
+    pub fn start_pos(&self, method: &str) -> (u32, u32) {
+        self.method(method)
+            .map(|m| (m.start_line.unwrap_or(0), m.start_col.unwrap_or(0)))
+            .unwrap_or((0, 0))
+    }
+
+    /// The per-method `file` override, or the target file.
+    pub fn file_for(&self, method: &str) -> PathBuf {
+        self.method(method)
+            .and_then(|m| m.file.as_deref())
+            .map(|f| self.cwd.join(f))
+            .unwrap_or_else(|| self.file.to_path_buf())
+    }
+
+    pub fn sync(&self, method: &str) -> SyncMode {
+        self.method(method).map(|m| m.sync).unwrap_or_default()
+    }
+
+    /// `didChange` snapshots for `method`, resolved against the project.
+    pub fn snapshots(&self, method: &str) -> Vec<ResolvedSnapshot> {
+        let (l, c) = self.pos(method);
+        self.method(method)
+            .map(|m| {
+                m.did_change
+                    .iter()
+                    .map(|s| ResolvedSnapshot {
+                        path: self.cwd.join(&s.file),
+                        line: s.line.unwrap_or(l),
+                        col: s.col.unwrap_or(c),
+                        expect: s.expect.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `didOpen` steps for `method`, resolved against the project.
+    pub fn did_open_steps(&self, method: &str) -> Vec<ResolvedDidOpen> {
+        self.method(method)
+            .map(|m| {
+                m.did_open
+                    .iter()
+                    .map(|s| ResolvedDidOpen {
+                        path: self.cwd.join(&s.file),
+                        line: s.line,
+                        col: s.col,
+                        expect: s.expect.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Position encoding a server negotiated in its probe.
+    pub fn encoding_for(&self, label: &str) -> PositionEncoding {
+        self.probes
+            .get(label)
+            .map(|p| PositionEncoding::from_capabilities(&p.capabilities))
+            .unwrap_or_default()
+    }
+
+    /// Params for a request sent by a composite benchmark: the registered
+    /// benchmark's own builder, or a plain position request.
+    pub fn request_params(&self, method: &str, file_uri: &str) -> Value {
+        match self.registry.get(method) {
+            Some(b) => b.params(self, file_uri),
+            None => position_params(self, method, file_uri),
+        }
+    }
+
+    /// The runner a request benchmark uses given its method config:
+    /// lifecycle steps, then `cold`, then `didOpen`, then `didChange`.
+    pub fn runner_for(&self, method: &str) -> Runner {
+        match self.method(method) {
+            Some(m)
+                if !m.rename_steps.is_empty()
+                    || !m.create_steps.is_empty()
+                    || !m.delete_steps.is_empty() =>
+            {
+                Runner::Sequence
+            }
+            Some(m) if m.cold => Runner::Cold,
+            Some(m) if !m.did_open.is_empty() => Runner::DidOpen,
+            Some(m) if !m.did_change.is_empty() => Runner::Snapshots,
+            _ => Runner::Warm,
+        }
+    }
+}
+
+/// A benchmark selectable by name from the `benchmarks` list.
+pub trait Benchmark {
+    /// Name in the config's `benchmarks` list and in `results.json`. Also the
+    /// key for capability checks and `methods` overrides.
+    fn name(&self) -> &str;
+
+    /// LSP method the request runners send. Defaults to [`name`](Self::name).
+    fn method(&self) -> &str {
+        self.name()
+    }
+
+    /// Params for one request against `file_uri`.
+    fn params(&self, ctx: &BenchContext, file_uri: &str) -> Value {
+        position_params(ctx, self.name(), file_uri)
+    }
+
+    /// What the request runners wait for before timing.
+    fn readiness(&self) -> Readiness {
+        Readiness::Diagnostics
+    }
+
+    /// How the benchmark drives the server.
+    fn runner(&self, ctx: &BenchContext) -> Runner {
+        ctx.runner_for(self.name())
+    }
+
+    /// Whether the benchmark renames, creates, deletes or edits project
+    /// files, so `sandbox: auto` runs it in a clone of the project.
+    fn mutates(&self, ctx: &BenchContext) -> bool {
+        self.runner(ctx) == Runner::Sequence
+    }
+
+    /// Print what the benchmark is about to do, or return why it is skipped.
+    fn prepare(&self, ctx: &BenchContext) -> Result<(), String> {
+        announce_method(ctx, self.name());
+        Ok(())
+    }
+
+    /// Benchmark one server.
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        run_runner(self, ctx, srv, on_progress)
+    }
+
+    /// Whether a recorded response is acceptable. Any rejected response
+    /// marks the server's result `invalid`. JSON-RPC errors are already
+    /// rejected by the runners.
+    fn is_valid(&self, _response: &Value) -> bool {
+        true
+    }
+
+    /// The value recorded in `results.json` for a response.
+    fn summarize(&self, response: Value) -> Value {
+        response
+    }
+
+    /// Check `expect` fields against successful rows for `--verify`.
+    fn verify(&self, ctx: &BenchContext, rows: &[BenchRow], tally: &mut VerifyTally) {
+        verify_method(self, ctx, rows, tally)
+    }
+
+    /// The request recorded as `input` in `results.json`.
+    fn input(&self, ctx: &BenchContext) -> Option<Value> {
+        match self.runner(ctx) {
+            Runner::Spawn | Runner::Custom => None,
+            _ => {
+                let params = self.params(ctx, &uri(ctx.file));
+                let rpc =
+                    json!({"jsonrpc": "2.0", "id": 1, "method": self.method(), "params": params});
+                Some(Value::String(serde_json::to_string(&rpc).unwrap()))
+            }
+        }
+    }
+}
+
+/// Whether `bench` runs in a sandbox under the context's `sandbox` mode.
+pub fn sandboxed(bench: &dyn Benchmark, ctx: &BenchContext) -> bool {
+    match ctx.sandbox {
+        SandboxMode::Always => true,
+        SandboxMode::Never => false,
+        SandboxMode::Auto => bench.mutates(ctx),
+    }
+}
+
+/// Run `bench` against one server, in a fresh sandbox when
+/// [`sandboxed`], then apply its validator and summarizer.
+pub fn execute(
+    bench: &dyn Benchmark,
+    ctx: &BenchContext,
+    srv: &ServerConfig,
+    on_progress: &dyn Fn(&str),
+) -> BenchResult {
+    let result = if sandboxed(bench, ctx) {
+        on_progress("cloning project");
+        let sandbox = match Sandbox::create(ctx.cwd, ctx.sandbox_clone) {
+            Ok(s) => s,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("sandbox: {}", e),
+                    usage: None,
+                }
+            }
+        };
+        let cwd = sandbox.path().to_path_buf();
+        let file = cwd.join(ctx.file.strip_prefix(ctx.cwd).unwrap_or(ctx.file));
+        let root = uri(&cwd);
+        let sandbox_ctx = BenchContext {
+            root: &root,
+            cwd: &cwd,
+            file: &file,
+            ..*ctx
+        };
+        let result = bench.run(&sandbox_ctx, srv, on_progress);
+        // Report locations in the real project, not the discarded clone.
+        unsandbox(result, &root, ctx.root)
+    } else {
+        bench.run(ctx, srv, on_progress)
+    };
+    match result {
+        BenchResult::Ok {
+            iterations,
+            usage,
+            details,
+        } => {
+            if let Some((_, bad)) = iterations.iter().find(|(_, r)| !bench.is_valid(r)) {
+                return BenchResult::Invalid {
+                    first_response: json!({ "result": bad }),
+                    usage,
+                };
+            }
+            BenchResult::Ok {
+                iterations: iterations
+                    .into_iter()
+                    .map(|(ms, r)| (ms, bench.summarize(r)))
+                    .collect(),
+                usage,
+                details,
+            }
+        }
+        other => other,
+    }
+}
+
+/// Replace the sandbox root URI `from` with `to` throughout a result.
+fn unsandbox(result: BenchResult, from: &str, to: &str) -> BenchResult {
+    let rewrite = |v: Value| -> Value {
+        let text = serde_json::to_string(&v).unwrap();
+        if !text.contains(from) {
+            return v;
+        }
+        serde_json::from_str(&text.replace(from, to)).unwrap_or(v)
+    };
+    match result {
+        BenchResult::Ok {
+            iterations,
+            usage,
+            details,
+        } => BenchResult::Ok {
+            iterations: iterations
+                .into_iter()
+                .map(|(ms, r)| (ms, rewrite(r)))
+                .collect(),
+            usage,
+            details: details.map(rewrite),
+        },
+        BenchResult::Invalid {
+            first_response,
+            usage,
+        } => BenchResult::Invalid {
+            first_response: rewrite(first_response),
+            usage,
+        },
+        other => other,
+    }
+}
+
+/// The runner behind [`Benchmark::run`]'s default: dispatch on
+/// [`Benchmark::runner`].
+pub fn run_runner<B: Benchmark + ?Sized>(
+    bench: &B,
+    ctx: &BenchContext,
+    srv: &ServerConfig,
+    on_progress: &dyn Fn(&str),
+) -> BenchResult {
+    let name = bench.name();
+    let method = bench.method();
+    let params = |_: &str, file_uri: &str| bench.params(ctx, file_uri);
+    let method_cfg = ctx.method(name);
+    let progress_token = method_cfg.and_then(|m| m.wait_for_progress_token.as_deref());
+    match bench.runner(ctx) {
+        Runner::Spawn => bench_spawn(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            ctx.index_timeout,
+            ctx.warmup,
+            ctx.iterations,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        ),
+        Runner::Warm => bench_lsp_method(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            method,
+            &params,
+            bench.readiness(),
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+            method_cfg.is_some_and(|m| m.wait_for_progress),
+        ),
+        Runner::Cold => bench_lsp_method_cold(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            method,
+            &params,
+            bench.readiness(),
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+            progress_token,
+        ),
+        Runner::Snapshots => bench_lsp_snapshots(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            method,
+            &params,
+            &ctx.snapshots(name),
+            ctx.sync(name),
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        ),
+        Runner::DidOpen => {
+            let (line, col) = ctx.pos(name);
+            bench_lsp_didopen(
+                srv,
+                ctx.root,
+                ctx.cwd,
+                ctx.file,
+                method,
+                &params,
+                &ctx.did_open_steps(name),
+                line,
+                col,
+                ctx.index_timeout,
+                ctx.timeout,
+                ctx.response_limit,
+                on_progress,
+                ctx.init_settings,
+                ctx.verbose,
+                progress_token,
+            )
+        }
+        Runner::Sequence => {
+            let m = method_cfg.expect("sequence runner without method config");
+            if !m.rename_steps.is_empty() {
+                bench_lsp_rename_sequence(
+                    srv,
+                    ctx.root,
+                    ctx.cwd,
+                    ctx.file,
+                    &m.rename_steps,
+                    ctx.index_timeout,
+                    ctx.timeout,
+                    ctx.response_limit,
+                    on_progress,
+                    ctx.init_settings,
+                    ctx.verbose,
+                )
+            } else if !m.create_steps.is_empty() {
+                bench_lsp_create_sequence(
+                    srv,
+                    ctx.root,
+                    ctx.cwd,
+                    ctx.file,
+                    &m.create_steps,
+                    ctx.index_timeout,
+                    ctx.timeout,
+                    ctx.response_limit,
+                    on_progress,
+                    ctx.init_settings,
+                    ctx.verbose,
+                )
+            } else {
+                bench_lsp_delete_sequence(
+                    srv,
+                    ctx.root,
+                    ctx.cwd,
+                    ctx.file,
+                    &m.delete_steps,
+                    ctx.index_timeout,
+                    ctx.timeout,
+                    ctx.response_limit,
+                    on_progress,
+                    ctx.init_settings,
+                    ctx.verbose,
+                )
+            }
+        }
+        Runner::Custom => BenchResult::Fail {
+            error: format!("{} has no runner", name),
+            usage: None,
+        },
+    }
+}
+
+/// Print the edits, opens, lifecycle steps and cold-start mode configured
+/// for a request benchmark.
+pub fn announce_method(ctx: &BenchContext, method: &str) {
+    let m = match ctx.method(method) {
+        Some(m) => m,
+        None => return,
+    };
+    if !m.did_change.is_empty() {
+        eprintln!(
+            "  {} {} snapshot(s) via didChange{}",
+            style("edit").cyan(),
+            m.did_change.len(),
+            sync_label(m.sync)
+        );
+    }
+    if !m.did_open.is_empty() {
+        eprintln!(
+            "  {} {} file(s) via didOpen",
+            style("open").cyan(),
+            m.did_open.len()
+        );
+    }
+    if !m.rename_steps.is_empty() {
+        eprintln!(
+            "  {} {} rename step(s) (full lifecycle)",
+            style("rename").magenta(),
+            m.rename_steps.len()
+        );
+    }
+    if !m.create_steps.is_empty() {
+        eprintln!(
+            "  {} {} create step(s) (full lifecycle)",
+            style("create").cyan(),
+            m.create_steps.len()
+        );
+    }
+    if !m.delete_steps.is_empty() {
+        eprintln!(
+            "  {} {} delete step(s) (full lifecycle)",
+            style("delete").yellow(),
+            m.delete_steps.len()
+        );
+    }
+    if m.cold {
+        eprintln!(
+            "  {} fresh server per iteration (cold start)",
+            style("cold").red()
+        );
+    }
+}
+
+/// The check behind [`Benchmark::verify`]'s default. `didOpen` runs check
+/// the baseline and then each step, `didChange` runs each snapshot, and
+/// other request runners the first iteration, against the most specific
+/// `expect`. Spawn and custom runners check nothing.
+pub fn verify_method<B: Benchmark + ?Sized>(
+    bench: &B,
+    ctx: &BenchContext,
+    rows: &[BenchRow],
+    tally: &mut VerifyTally,
+) {
+    let runner = bench.runner(ctx);
+    if matches!(runner, Runner::Spawn | Runner::Custom) {
+        return;
+    }
+    let name = bench.name();
+    let method_expect = ctx.method(name).and_then(|m| m.expect.as_ref());
+    let did_open_steps = ctx.did_open_steps(name);
+    let snapshots = ctx.snapshots(name);
+    // Skip failed/invalid servers
+    for row in rows.iter().filter(|r| r.kind == 0) {
+        let encoding = ctx.encoding_for(&row.label);
+        match runner {
+            Runner::DidOpen => {
+                // Iteration 0 is the baseline, then one per didOpen step
+                for (i, (_ms, resp)) in row.iterations.iter().enumerate() {
+                    if i == 0 {
+                        let what = format!("[baseline] {}", row.label);
+                        tally.check(resp, method_expect, encoding, &what);
+                    } else if let Some(step) = did_open_steps.get(i - 1) {
+                        let step_name = step.path.file_name().unwrap_or_default().to_string_lossy();
+                        let expect = step.expect.as_ref().or(method_expect);
+                        tally.check(resp, expect, encoding, &format!("[{}] {}", i, step_name));
+                    }
+                }
+            }
+            Runner::Snapshots => {
+                // 1:1 mapping between iterations and snapshots
+                for (i, ((_ms, resp), snap)) in row.iterations.iter().zip(&snapshots).enumerate() {
+                    let snap_name = snap.path.file_name().unwrap_or_default().to_string_lossy();
+                    let expect = snap.expect.as_ref().or(method_expect);
+                    tally.check(
+                        resp,
+                        expect,
+                        encoding,
+                        &format!("[{}] {}", i + 1, snap_name),
+                    );
+                }
+            }
+            _ => match row.iterations.first() {
+                // Just check the first iteration (all should be the same)
+                Some((_ms, resp)) => tally.check(resp, method_expect, encoding, &row.label),
+                None if method_expect.is_none() => tally.skipped += 1,
+                None => {}
+            },
+        }
+    }
+}
+
+/// Check `expect` against each server's last response, for benchmarks whose
+/// final iteration is the one that matters.
+fn verify_last(name: &str, ctx: &BenchContext, rows: &[BenchRow], tally: &mut VerifyTally) {
+    let expect = match ctx.method(name).and_then(|m| m.expect.as_ref()) {
+        Some(exp) => exp,
+        None => return,
+    };
+    for row in rows.iter().filter(|r| r.kind == 0) {
+        if let Some((_ms, resp)) = row.iterations.last() {
+            let what = format!("[{}] {}", row.label, name);
+            tally.check(resp, Some(expect), ctx.encoding_for(&row.label), &what);
+        }
+    }
+}
+
+// ── Params builders ─────────────────────────────────────────────────────────
+
+/// Params builder for a [`Request`]: `(ctx, method, file_uri)`.
+pub type ParamsFn = fn(&BenchContext, &str, &str) -> Value;
+
+pub fn position_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let (l, c) = ctx.pos(method);
+    json!({
+        "textDocument": { "uri": file_uri },
+        "position": { "line": l, "character": c },
+    })
+}
+
+pub fn doc_params(_ctx: &BenchContext, _method: &str, file_uri: &str) -> Value {
+    json!({ "textDocument": { "uri": file_uri } })
+}
+
+fn ref_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let (l, c) = ctx.pos(method);
+    json!({
+        "textDocument": { "uri": file_uri },
+        "position": { "line": l, "character": c },
+        "context": { "includeDeclaration": true },
+    })
+}
+
+fn symbol_params(_ctx: &BenchContext, _method: &str, _file_uri: &str) -> Value {
+    json!({ "query": "" })
+}
+
+fn rename_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let (l, c) = ctx.pos(method);
+    let new_name = ctx
+        .method(method)
+        .and_then(|m| m.new_name.as_deref())
+        .unwrap_or("__lsp_bench_rename__");
+    json!({
+        "textDocument": { "uri": file_uri },
+        "position": { "line": l, "character": c },
+        "newName": new_name,
+    })
+}
+
+fn completion_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let mut params = position_params(ctx, method, file_uri);
+    // Trigger from methods map, then legacy top-level trigger_character
+    let tc = ctx
+        .method(method)
+        .and_then(|m| m.trigger.as_deref())
+        .or(ctx.trigger_character);
+    if let Some(tc) = tc {
+        params["context"] = json!({
+            "triggerKind": 2,
+            "triggerCharacter": tc,
+        });
+    }
+    params
+}
+
+fn formatting_params(_ctx: &BenchContext, _method: &str, file_uri: &str) -> Value {
+    json!({
+        "textDocument": { "uri": file_uri },
+        "options": { "tabSize": 4, "insertSpaces": true },
+    })
+}
+
+fn selection_range_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let (l, c) = ctx.pos(method);
+    json!({
+        "textDocument": { "uri": file_uri },
+        "positions": [{ "line": l, "character": c }],
+    })
+}
+
+fn inlay_hint_params(_ctx: &BenchContext, _method: &str, file_uri: &str) -> Value {
+    json!({
+        "textDocument": { "uri": file_uri },
+        "range": {
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 9999, "character": 0 },
+        },
+    })
+}
+
+fn semantic_tokens_range_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let (sl, sc) = ctx.start_pos(method);
+    let (el, ec) = ctx.pos(method);
+    json!({
+        "textDocument": { "uri": file_uri },
+        "range": {
+            "start": { "line": sl, "character": sc },
+            "end": { "line": el, "character": ec },
+        },
+    })
+}
+
+fn will_rename_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let method_cfg = ctx.method(method);
+    let new_name = method_cfg
+        .and_then(|m| m.new_name.as_deref())
+        .unwrap_or("__lsp_bench_renamed__.sol");
+    // Use per-method file override if set, otherwise use the top-level file.
+    let old_uri = match method_cfg.and_then(|m| m.file.as_deref()) {
+        Some(rel_path) => uri(&ctx.cwd.join(rel_path)),
+        None => file_uri.to_string(),
+    };
+    // Derive newUri by replacing the filename in oldUri
+    let new_uri = if let Some(pos) = old_uri.rfind('/') {
+        format!("{}/{}", &old_uri[..pos], new_name)
+    } else {
+        new_name.to_string()
+    };
+    json!({
+        "files": [{
+            "oldUri": old_uri,
+            "newUri": new_uri,
+        }]
+    })
+}
+
+fn will_create_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    let new_name = ctx
+        .method(method)
+        .and_then(|m| m.new_name.as_deref())
+        .unwrap_or("__lsp_bench_created__.sol");
+    // Build a URI for the new file in the same directory as the target file.
+    let new_uri = if let Some(pos) = file_uri.rfind('/') {
+        format!("{}/{}", &file_uri[..pos], new_name)
+    } else {
+        new_name.to_string()
+    };
+    json!({
+        "files": [{
+            "uri": new_uri,
+        }]
+    })
+}
+
+fn will_delete_params(ctx: &BenchContext, method: &str, file_uri: &str) -> Value {
+    // Use per-method file override if set, otherwise use the top-level file.
+    let target_uri = match ctx.method(method).and_then(|m| m.file.as_deref()) {
+        Some(rel_path) => uri(&ctx.cwd.join(rel_path)),
+        None => file_uri.to_string(),
+    };
+    json!({
+        "files": [{
+            "uri": target_uri,
+        }]
+    })
+}
+
+fn execute_command_params(ctx: &BenchContext, method: &str, _file_uri: &str) -> Value {
+    let method_cfg = ctx.method(method);
+    let command = method_cfg
+        .and_then(|m| m.command.as_deref())
+        .unwrap_or("solidity.reindex")
+        .to_string();
+    let arguments = method_cfg.map(|m| m.arguments.clone()).unwrap_or_default();
+    json!({
+        "command": command,
+        "arguments": arguments,
+    })
+}
+
+// ── Built-in benchmarks ─────────────────────────────────────────────────────
+
+/// A single LSP request, benchmarked with whichever request runner its
+/// method config selects.
+pub struct Request {
+    pub method: &'static str,
+    pub params: ParamsFn,
+    pub readiness: Readiness,
+    /// Runs in a sandbox under `sandbox: auto` even without lifecycle steps.
+    pub mutating: bool,
+}
+
+impl Request {
+    /// A request that only needs the file build before timing.
+    pub fn new(method: &'static str, params: ParamsFn) -> Self {
+        Self {
+            method,
+            params,
+            readiness: Readiness::Diagnostics,
+            mutating: false,
+        }
+    }
+
+    /// A request that depends on the background project index.
+    pub fn indexed(method: &'static str, params: ParamsFn) -> Self {
+        Self {
+            method,
+            params,
+            readiness: Readiness::ProjectIndex,
+            mutating: false,
+        }
+    }
+
+    /// Mark the request as one whose server side may edit project files
+    /// (e.g. through `workspace/applyEdit`).
+    pub fn mutating(self) -> Self {
+        Self {
+            mutating: true,
+            ..self
+        }
+    }
+}
+
+impl Benchmark for Request {
+    fn name(&self) -> &str {
+        self.method
+    }
+
+    fn params(&self, ctx: &BenchContext, file_uri: &str) -> Value {
+        (self.params)(ctx, self.method, file_uri)
+    }
+
+    fn readiness(&self) -> Readiness {
+        self.readiness
+    }
+
+    fn mutates(&self, ctx: &BenchContext) -> bool {
+        self.mutating || self.runner(ctx) == Runner::Sequence
+    }
+}
+
+/// `initialize`: spawn-to-initialized time.
+struct Initialize;
+
+impl Benchmark for Initialize {
+    fn name(&self) -> &str {
+        "initialize"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Spawn
+    }
+
+    fn prepare(&self, _ctx: &BenchContext) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// `textDocument/diagnostic`: didOpen to the first push of diagnostics.
+struct Diagnostics;
+
+impl Benchmark for Diagnostics {
+    fn name(&self) -> &str {
+        "textDocument/diagnostic"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, _ctx: &BenchContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        bench_diagnostics(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            ctx.index_timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+}
+
+/// `textDocument/didChange`: each snapshot's edit to its diagnostics.
+struct EditDiagnostics;
+
+impl Benchmark for EditDiagnostics {
+    fn name(&self) -> &str {
+        "textDocument/didChange"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, ctx: &BenchContext) -> Result<(), String> {
+        let count = ctx.method(self.name()).map_or(0, |m| m.did_change.len());
+        if count == 0 {
+            return Err(
+                "no `didChange` snapshots set under methods.textDocument/didChange".to_string(),
+            );
+        }
+        eprintln!(
+            "  {} {} snapshot(s) via didChange{}",
+            style("edit").cyan(),
+            count,
+            sync_label(ctx.sync(self.name()))
+        );
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        bench_edit_diagnostics(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            &ctx.snapshots(self.name()),
+            ctx.sync(self.name()),
+            ctx.index_timeout,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+
+    fn verify(&self, ctx: &BenchContext, rows: &[BenchRow], tally: &mut VerifyTally) {
+        let method_expect = ctx.method(self.name()).and_then(|m| m.expect.as_ref());
+        let snapshots = ctx.snapshots(self.name());
+        for row in rows.iter().filter(|r| r.kind == 0) {
+            let encoding = ctx.encoding_for(&row.label);
+            for (i, ((_ms, resp), snap)) in row.iterations.iter().zip(&snapshots).enumerate() {
+                let snap_name = snap.path.file_name().unwrap_or_default().to_string_lossy();
+                let expect = snap.expect.as_ref().or(method_expect);
+                let what = format!("[{}] {} {}", i + 1, row.label, snap_name);
+                tally.check(resp, expect, encoding, &what);
+            }
+        }
+    }
+}
+
+/// `textDocument/semanticTokens/full/delta`: needs `resultId` chaining.
+struct SemanticTokensDelta;
+
+impl Benchmark for SemanticTokensDelta {
+    fn name(&self) -> &str {
+        "textDocument/semanticTokens/full/delta"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        bench_lsp_delta(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            &ctx.snapshots(self.name()),
+            ctx.sync(self.name()),
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+}
+
+/// `typing`: keystroke-by-keystroke edits, each followed by requests.
+struct Typing;
+
+impl Typing {
+    fn requests(ctx: &BenchContext) -> Vec<String> {
+        match ctx.method("typing") {
+            Some(m) if !m.requests.is_empty() => m.requests.clone(),
+            _ => vec!["textDocument/completion".to_string()],
+        }
+    }
+}
+
+impl Benchmark for Typing {
+    fn name(&self) -> &str {
+        "typing"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, ctx: &BenchContext) -> Result<(), String> {
+        match ctx.method("typing").and_then(|m| m.text.as_deref()) {
+            Some(text) if !text.is_empty() => {
+                let (line, col) = ctx.pos("typing");
+                eprintln!(
+                    "  {} {:?} at {}:{}, then {}",
+                    style("type").cyan(),
+                    text,
+                    line,
+                    col,
+                    Self::requests(ctx).join(", ")
+                );
+                Ok(())
+            }
+            _ => Err("no `text` set under methods.typing".to_string()),
+        }
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        let text = ctx
+            .method("typing")
+            .and_then(|m| m.text.as_deref())
+            .unwrap_or_default();
+        let (line, col) = ctx.pos("typing");
+        bench_typing(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            text,
+            &Self::requests(ctx),
+            &|m, u| ctx.request_params(m, u),
+            line,
+            col,
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+}
+
+/// `$/cancelRequest`: cancel an in-flight request and time the reply.
+struct Cancel;
+
+impl Cancel {
+    fn request<'a>(ctx: &BenchContext<'a>) -> &'a str {
+        ctx.method("$/cancelRequest")
+            .and_then(|m| m.request.as_deref())
+            .unwrap_or("textDocument/references")
+    }
+
+    fn cancel_after(ctx: &BenchContext) -> Duration {
+        Duration::from_millis(
+            ctx.method("$/cancelRequest")
+                .and_then(|m| m.cancel_after_ms)
+                .unwrap_or(10),
+        )
+    }
+}
+
+impl Benchmark for Cancel {
+    fn name(&self) -> &str {
+        "$/cancelRequest"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, ctx: &BenchContext) -> Result<(), String> {
+        eprintln!(
+            "  {} {} after {}ms",
+            style("cancel").cyan(),
+            Self::request(ctx),
+            Self::cancel_after(ctx).as_millis()
+        );
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        let request = Self::request(ctx);
+        // Servers must advertise the provider of the cancelled request.
+        if let Some(probe) = ctx.probes.get(&srv.label) {
+            if !capabilities::supports(&probe.capabilities, request) {
+                let provider = capabilities::provider_for(request).unwrap_or(request);
+                return BenchResult::Unsupported {
+                    provider: provider.to_string(),
+                };
+            }
+        }
+        bench_cancel(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            request,
+            &|m, u| ctx.request_params(m, u),
+            Self::cancel_after(ctx),
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+}
+
+/// `load`: several requests kept in flight at once.
+struct Load;
+
+impl Load {
+    fn requests(ctx: &BenchContext) -> Vec<String> {
+        match ctx.method("load") {
+            Some(m) if !m.requests.is_empty() => m.requests.clone(),
+            _ => [
+                "textDocument/hover",
+                "textDocument/documentHighlight",
+                "textDocument/codeLens",
+                "textDocument/inlayHint",
+            ]
+            .iter()
+            .map(|m| m.to_string())
+            .collect(),
+        }
+    }
+
+    fn concurrency(ctx: &BenchContext) -> usize {
+        ctx.method("load")
+            .and_then(|m| m.concurrency)
+            .unwrap_or(4)
+            .max(1)
+    }
+}
+
+impl Benchmark for Load {
+    fn name(&self) -> &str {
+        "load"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, ctx: &BenchContext) -> Result<(), String> {
+        eprintln!(
+            "  {} {} in flight: {}",
+            style("load").cyan(),
+            Self::concurrency(ctx),
+            Self::requests(ctx).join(", ")
+        );
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        bench_load(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            ctx.file,
+            &Self::requests(ctx),
+            &|m, u| ctx.request_params(m, u),
+            Self::concurrency(ctx),
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+}
+
+/// `textDocument/codeAction`: requested with the diagnostics at the target
+/// position.
+struct CodeAction;
+
+impl Benchmark for CodeAction {
+    fn name(&self) -> &str {
+        "textDocument/codeAction"
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    /// Servers may push `workspace/applyEdit` while computing actions.
+    fn mutates(&self, _ctx: &BenchContext) -> bool {
+        true
+    }
+
+    fn prepare(&self, _ctx: &BenchContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        let (line, col) = ctx.pos(self.name());
+        bench_code_action(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            &ctx.file_for(self.name()),
+            line,
+            col,
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+        )
+    }
+
+    fn verify(&self, ctx: &BenchContext, rows: &[BenchRow], tally: &mut VerifyTally) {
+        verify_last(self.name(), ctx, rows, tally);
+    }
+}
+
+/// `callHierarchy/incomingCalls` and `outgoingCalls`: prepare the hierarchy
+/// item, then time the follow-up request.
+struct CallHierarchy(&'static str);
+
+impl Benchmark for CallHierarchy {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn runner(&self, _ctx: &BenchContext) -> Runner {
+        Runner::Custom
+    }
+
+    fn prepare(&self, _ctx: &BenchContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        ctx: &BenchContext,
+        srv: &ServerConfig,
+        on_progress: &dyn Fn(&str),
+    ) -> BenchResult {
+        let (line, col) = ctx.pos(self.0);
+        bench_call_hierarchy(
+            srv,
+            ctx.root,
+            ctx.cwd,
+            &ctx.file_for(self.0),
+            self.0,
+            line,
+            col,
+            ctx.index_timeout,
+            ctx.timeout,
+            ctx.warmup,
+            ctx.iterations,
+            ctx.response_limit,
+            on_progress,
+            ctx.init_settings,
+            ctx.verbose,
+            ctx.method(self.0)
+                .and_then(|m| m.wait_for_progress_token.as_deref()),
+        )
+    }
+
+    fn verify(&self, ctx: &BenchContext, rows: &[BenchRow], tally: &mut VerifyTally) {
+        verify_last(self.0, ctx, rows, tally);
+    }
+}
+
+// ── Registry ────────────────────────────────────────────────────────────────
+
+/// Benchmarks available to a run, in the order they run.
+#[derive(Default)]
+pub struct Registry {
+    benchmarks: Vec<Box<dyn Benchmark>>,
+}
+
+impl Registry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every benchmark `lsp-bench` ships with.
+    pub fn builtin() -> Self {
+        let mut r = Self::new();
+        r.register(Initialize);
+        r.register(Diagnostics);
+        r.register(EditDiagnostics);
+        r.register(SemanticTokensDelta);
+        r.register(Typing);
+        r.register(Cancel);
+        r.register(Load);
+        let requests = [
+            Request::indexed("textDocument/definition", position_params),
+            Request::indexed("textDocument/declaration", position_params),
+            Request::new("textDocument/typeDefinition", position_params),
+            Request::indexed("textDocument/implementation", position_params),
+            Request::indexed("textDocument/hover", position_params),
+            Request::indexed("textDocument/references", ref_params),
+            Request::indexed("textDocument/completion", completion_params),
+            Request::indexed("textDocument/signatureHelp", position_params),
+            Request::indexed("textDocument/rename", rename_params).mutating(),
+            Request::indexed("textDocument/prepareRename", position_params),
+            Request::new("textDocument/documentSymbol", doc_params),
+            Request::indexed("textDocument/documentHighlight", position_params),
+            Request::indexed("textDocument/documentLink", doc_params),
+            Request::new("textDocument/formatting", formatting_params),
+            Request::new("textDocument/foldingRange", doc_params),
+            Request::new("textDocument/selectionRange", selection_range_params),
+            Request::new("textDocument/codeLens", doc_params),
+            Request::indexed("textDocument/inlayHint", inlay_hint_params),
+            Request::new("textDocument/semanticTokens/full", doc_params),
+            Request::new(
+                "textDocument/semanticTokens/range",
+                semantic_tokens_range_params,
+            ),
+            Request::new("textDocument/documentColor", doc_params),
+            Request::indexed("workspace/symbol", symbol_params),
+            Request::indexed("workspace/willRenameFiles", will_rename_params).mutating(),
+            Request::new("workspace/willCreateFiles", will_create_params).mutating(),
+            Request::indexed("workspace/willDeleteFiles", will_delete_params).mutating(),
+            // The command may push workspace/applyEdit, which lands on disk.
+            Request::new("workspace/executeCommand", execute_command_params).mutating(),
+            Request::indexed("textDocument/prepareCallHierarchy", position_params),
+        ];
+        for req in requests {
+            r.register(req);
+        }
+        r.register(CodeAction);
+        r.register(CallHierarchy("callHierarchy/incomingCalls"));
+        r.register(CallHierarchy("callHierarchy/outgoingCalls"));
+        r
+    }
+
+    /// Add a benchmark, replacing any registered under the same name.
+    pub fn register(&mut self, bench: impl Benchmark + 'static) {
+        let bench: Box<dyn Benchmark> = Box::new(bench);
+        match self
+            .benchmarks
+            .iter()
+            .position(|b| b.name() == bench.name())
+        {
+            Some(i) => self.benchmarks[i] = bench,
+            None => self.benchmarks.push(bench),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Benchmark> {
+        self.iter().find(|b| b.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Benchmark> {
+        self.benchmarks.iter().map(|b| b.as_ref())
+    }
+
+    /// Registered names, in run order.
+    pub fn names(&self) -> Vec<&str> {
+        self.iter().map(|b| b.name()).collect()
+    }
+}
@@ -0,0 +1,74 @@
+//! Which LSP methods a server advertises.
+//!
+//! [`provider_for`] maps each benchmarked method to the `ServerCapabilities`
+//! path of its provider, and [`supports`] checks that path in a server's
+//! `InitializeResult`. Benchmarks use it to report `unsupported` instead of
+//! timing a request the server never offered, and the report's capabilities
+//! table and the mock server read the same map.
+
+use serde_json::Value;
+
+/// `ServerCapabilities` path (dot-separated) a server must advertise in its
+/// `InitializeResult` before a benchmark can run against it.
+///
+/// Returns `None` for benchmarks that don't depend on a provider, such as
+/// `initialize` itself or push-based `textDocument/diagnostic`.
+pub fn provider_for(method: &str) -> Option<&'static str> {
+    let path = match method {
+        "textDocument/definition" => "definitionProvider",
+        "textDocument/declaration" => "declarationProvider",
+        "textDocument/typeDefinition" => "typeDefinitionProvider",
+        "textDocument/implementation" => "implementationProvider",
+        "textDocument/hover" => "hoverProvider",
+        "textDocument/references" => "referencesProvider",
+        "textDocument/completion" => "completionProvider",
+        "textDocument/signatureHelp" => "signatureHelpProvider",
+        "textDocument/rename" => "renameProvider",
+        "textDocument/prepareRename" => "renameProvider.prepareProvider",
+        "textDocument/documentSymbol" => "documentSymbolProvider",
+        "textDocument/documentHighlight" => "documentHighlightProvider",
+        "textDocument/documentLink" => "documentLinkProvider",
+        "textDocument/formatting" => "documentFormattingProvider",
+        "textDocument/foldingRange" => "foldingRangeProvider",
+        "textDocument/selectionRange" => "selectionRangeProvider",
+        "textDocument/codeLens" => "codeLensProvider",
+        "textDocument/inlayHint" => "inlayHintProvider",
+        "textDocument/semanticTokens/full" => "semanticTokensProvider.full",
+        "textDocument/semanticTokens/range" => "semanticTokensProvider.range",
+        "textDocument/semanticTokens/full/delta" => "semanticTokensProvider.full.delta",
+        "textDocument/documentColor" => "colorProvider",
+        "textDocument/codeAction" => "codeActionProvider",
+        "textDocument/prepareCallHierarchy"
+        | "callHierarchy/incomingCalls"
+        | "callHierarchy/outgoingCalls" => "callHierarchyProvider",
+        "workspace/symbol" => "workspaceSymbolProvider",
+        "workspace/executeCommand" => "executeCommandProvider",
+        "workspace/willRenameFiles" => "workspace.fileOperations.willRename",
+        "workspace/willCreateFiles" => "workspace.fileOperations.willCreate",
+        "workspace/willDeleteFiles" => "workspace.fileOperations.willDelete",
+        _ => return None,
+    };
+    Some(path)
+}
+
+/// Check whether `caps` (the `capabilities` object of an `InitializeResult`)
+/// advertises the provider needed for `method`.
+///
+/// A provider counts as advertised when every segment of its path is present
+/// and the final value is neither `null` nor `false` — so `true`, an options
+/// object and a registration options object all qualify. Benchmarks without a
+/// provider are always supported.
+pub fn supports(caps: &Value, method: &str) -> bool {
+    let path = match provider_for(method) {
+        Some(p) => p,
+        None => return true,
+    };
+    let mut cur = caps;
+    for key in path.split('.') {
+        match cur.get(key) {
+            Some(v) => cur = v,
+            None => return false,
+        }
+    }
+    !matches!(cur, Value::Null | Value::Bool(false))
+}
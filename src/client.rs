@@ -0,0 +1,1089 @@
+//! JSON-RPC client that spawns an LSP server and drives it over stdio.
+
+use crate::config::{ServerConfig, SyncMode};
+use crate::helpers::{is_diagnostics_for, uri};
+use crate::journal;
+use crate::position::{incremental_change, PositionEncoding};
+use crate::runners::apply_workspace_changes_to_disk;
+use console::style;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessStatus, System};
+
+// ── Wire trace ──────────────────────────────────────────────────────────────
+
+/// Append-only JSONL log of every JSON-RPC message exchanged with a server.
+/// Each line is `{"t_ms", "dir", "bytes", "message"}`: `t_ms` is monotonic
+/// time since the server was spawned, `dir` is `send`, `recv` or `spawn`, and
+/// `bytes` is the message body's Content-Length.
+#[derive(Clone)]
+pub struct Trace {
+    pub start: Instant,
+    pub out: Arc<Mutex<std::fs::File>>,
+}
+
+impl Trace {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let out = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Self {
+            start: Instant::now(),
+            out: Arc::new(Mutex::new(out)),
+        })
+    }
+
+    pub fn record(&self, dir: &str, bytes: usize, message: &Value) {
+        let t_ms = self.start.elapsed().as_secs_f64() * 1000.0;
+        let mut line = serde_json::to_string(&json!({
+            "t_ms": (t_ms * 1000.0).round() / 1000.0,
+            "dir": dir,
+            "bytes": bytes,
+            "message": message,
+        }))
+        .unwrap();
+        line.push('\n');
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// File-name-safe form of a server label or benchmark name
+/// (`textDocument/definition` -> `textDocument_definition`).
+pub fn trace_file_part(name: &str) -> String {
+    let part: String = name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '@' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    part.trim_matches('_').to_string()
+}
+
+// ── Live servers ────────────────────────────────────────────────────────────
+
+pub type SharedStdin = Arc<Mutex<Option<std::process::ChildStdin>>>;
+
+/// A server spawned by this process that hasn't been stopped yet.
+struct LiveServer {
+    pid: u32,
+    /// Label of the server config it was spawned from, if any.
+    label: Option<String>,
+    stdin: SharedStdin,
+}
+
+static LIVE: Mutex<Vec<LiveServer>> = Mutex::new(Vec::new());
+
+fn live() -> std::sync::MutexGuard<'static, Vec<LiveServer>> {
+    LIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Process ids of the live servers spawned for the config labelled `label`.
+pub fn live_server_pids(label: &str) -> Vec<u32> {
+    live()
+        .iter()
+        .filter(|s| s.label.as_deref() == Some(label))
+        .map(|s| s.pid)
+        .collect()
+}
+
+/// Shut down every live server from outside its runner: send `shutdown` and
+/// `exit`, close stdin, and kill whatever is still running after `grace`.
+/// Used by the interrupt handler; runners keep their clients but every
+/// later write fails with "stdin closed".
+pub fn shutdown_live_servers(grace: Duration) {
+    let servers = std::mem::take(&mut *live());
+    for LiveServer { stdin, .. } in &servers {
+        // A runner mid-write holds the lock; that server is killed below.
+        let Ok(mut stdin) = stdin.try_lock() else {
+            continue;
+        };
+        if let Some(mut w) = stdin.take() {
+            for msg in [
+                json!({"jsonrpc":"2.0","id":"lsp-bench-shutdown","method":"shutdown","params":null}),
+                json!({"jsonrpc":"2.0","method":"exit","params":null}),
+            ] {
+                let body = msg.to_string();
+                let _ = write!(w, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+            }
+            let _ = w.flush();
+        }
+    }
+
+    let pids: Vec<Pid> = servers.iter().map(|s| Pid::from_u32(s.pid)).collect();
+    let mut sys = System::new();
+    let deadline = Instant::now() + grace;
+    loop {
+        sys.refresh_pids_specifics(&pids, ProcessRefreshKind::new());
+        let running: Vec<&sysinfo::Process> = pids
+            .iter()
+            .filter_map(|pid| sys.process(*pid))
+            .filter(|p| p.status() != ProcessStatus::Zombie)
+            .collect();
+        if running.is_empty() {
+            return;
+        }
+        if Instant::now() >= deadline {
+            for p in running {
+                p.kill();
+            }
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+pub struct LspClient {
+    pub child: std::process::Child,
+    pub rx: mpsc::Receiver<Value>,
+    /// The server's stdin, shared with the live-server list so an interrupt
+    /// can shut the server down from another thread.
+    pub writer: SharedStdin,
+    pub id: i64,
+    pub logs: Arc<Mutex<Vec<String>>>,
+    pub verbose: bool,
+    /// Answers for `workspace/configuration`, from the server's config.
+    pub workspace_config: Value,
+    /// `workspace/configuration` requests received and what we answered.
+    pub config_exchanges: Vec<Value>,
+    /// Active `client/registerCapability` registrations.
+    pub registrations: Vec<Value>,
+    /// Original content of files changed by `workspace/applyEdit`.
+    pub edit_restore: HashMap<PathBuf, Vec<u8>>,
+    /// Content we last wrote for each file changed by `workspace/applyEdit`.
+    pub edit_written: HashMap<PathBuf, Vec<u8>>,
+    pub edit_versions: HashMap<String, i32>,
+    /// Version of each open document, as last sent in didOpen/didChange.
+    pub doc_versions: HashMap<String, i32>,
+    /// Position encoding negotiated in `initialize`.
+    pub encoding: PositionEncoding,
+    /// `TextDocumentSyncKind` the server advertised (0 none, 1 full, 2 incremental).
+    pub sync_kind: u64,
+    /// Wire trace, when enabled for this run.
+    pub trace: Option<Trace>,
+    pub startup: StartupTimes,
+}
+
+/// When each step of starting the server happened, for the startup phase
+/// breakdown of the `initialize` benchmark.
+#[derive(Debug, Clone)]
+pub struct StartupTimes {
+    pub spawned: Instant,
+    /// First byte the server wrote to stdout, set by the reader thread.
+    pub first_byte: Arc<OnceLock<Instant>>,
+    pub initialize_sent: Option<Instant>,
+    pub initialize_answered: Option<Instant>,
+    pub initialized_sent: Option<Instant>,
+}
+
+pub struct DiagnosticsInfo {
+    pub message: Value,
+}
+
+fn reader_thread(
+    stdout: std::process::ChildStdout,
+    tx: mpsc::Sender<Value>,
+    logs: Arc<Mutex<Vec<String>>>,
+    verbose: bool,
+    trace: Option<Trace>,
+    first_byte: Arc<OnceLock<Instant>>,
+) {
+    let mut reader = BufReader::new(stdout);
+    if reader.fill_buf().is_ok_and(|buf| !buf.is_empty()) {
+        let _ = first_byte.set(Instant::now());
+    }
+    let start = Instant::now();
+    let mut last_log = start;
+    loop {
+        let mut content_length: usize = 0;
+        let mut in_header = false;
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => return,
+                Ok(_) => {}
+                Err(_) => return,
+            }
+            let t = line.trim();
+            if t.is_empty() {
+                if in_header {
+                    break;
+                }
+                continue;
+            }
+            if let Some(v) = t.strip_prefix("Content-Length:") {
+                if let Ok(n) = v.trim().parse::<usize>() {
+                    content_length = n;
+                    in_header = true;
+                    continue;
+                }
+            }
+            if t.starts_with("Content-Type:") {
+                in_header = true;
+                continue;
+            }
+        }
+        if content_length == 0 {
+            continue;
+        }
+        let mut body = vec![0u8; content_length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        if let Ok(msg) = serde_json::from_slice::<Value>(&body) {
+            if let Some(ref trace) = trace {
+                trace.record("recv", content_length, &msg);
+            }
+            // Capture window/logMessage notifications
+            if msg.get("method").and_then(|m| m.as_str()) == Some("window/logMessage") {
+                if let Some(text) = msg
+                    .get("params")
+                    .and_then(|p| p.get("message"))
+                    .and_then(|m| m.as_str())
+                {
+                    if verbose {
+                        let now = Instant::now();
+                        let elapsed = now.duration_since(start);
+                        let delta = now.duration_since(last_log);
+                        last_log = now;
+                        eprintln!(
+                            "  {} {} {}",
+                            style(format!(
+                                "[+{:.1}s Δ{:.0}ms]",
+                                elapsed.as_secs_f64(),
+                                delta.as_millis()
+                            ))
+                            .dim(),
+                            style("log").dim(),
+                            style(text).dim(),
+                        );
+                    }
+                    if let Ok(mut l) = logs.lock() {
+                        l.push(text.to_string());
+                    }
+                }
+            }
+            // Stream $/progress notifications in verbose mode
+            if verbose && msg.get("method").and_then(|m| m.as_str()) == Some("$/progress") {
+                if let Some(value) = msg.get("params").and_then(|p| p.get("value")) {
+                    let kind = value.get("kind").and_then(|k| k.as_str()).unwrap_or("");
+                    let message = value.get("message").and_then(|m| m.as_str()).unwrap_or("");
+                    let title = value.get("title").and_then(|t| t.as_str()).unwrap_or("");
+                    let now = Instant::now();
+                    let elapsed = now.duration_since(start);
+                    let delta = now.duration_since(last_log);
+                    last_log = now;
+                    let detail = match kind {
+                        "begin" => format!("{}: {}", title, message),
+                        "report" => message.to_string(),
+                        "end" => format!("done: {}", message),
+                        _ => format!("{}: {}", kind, message),
+                    };
+                    eprintln!(
+                        "  {} {} {}",
+                        style(format!(
+                            "[+{:.1}s Δ{:.0}ms]",
+                            elapsed.as_secs_f64(),
+                            delta.as_millis()
+                        ))
+                        .dim(),
+                        style("progress").cyan().dim(),
+                        style(detail).dim(),
+                    );
+                }
+            }
+            if tx.send(msg).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl LspClient {
+    pub fn spawn(
+        cmd: &str,
+        args: &[String],
+        cwd: &Path,
+        verbose: bool,
+        trace: Option<Trace>,
+    ) -> Result<Self, String> {
+        let abs_cmd = if cmd.starts_with("..") || cmd.starts_with("./") {
+            std::fs::canonicalize(cmd)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| cmd.to_string())
+        } else {
+            cmd.to_string()
+        };
+        let stderr_cfg = if verbose {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        };
+        let spawned = Instant::now();
+        let mut child = Command::new(&abs_cmd)
+            .args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr_cfg)
+            .spawn()
+            .map_err(|e| format!("{}: {}", cmd, e))?;
+        let writer: SharedStdin = Arc::new(Mutex::new(child.stdin.take()));
+        live().push(LiveServer {
+            pid: child.id(),
+            label: None,
+            stdin: writer.clone(),
+        });
+        let stdout = child.stdout.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let logs_clone = logs.clone();
+        let trace_clone = trace.clone();
+        let first_byte = Arc::new(OnceLock::new());
+        let first_byte_clone = first_byte.clone();
+        std::thread::spawn(move || {
+            reader_thread(
+                stdout,
+                tx,
+                logs_clone,
+                verbose,
+                trace_clone,
+                first_byte_clone,
+            )
+        });
+        Ok(Self {
+            child,
+            rx,
+            writer,
+            id: 1,
+            logs,
+            verbose,
+            workspace_config: Value::Null,
+            config_exchanges: Vec::new(),
+            registrations: Vec::new(),
+            edit_restore: HashMap::new(),
+            edit_written: HashMap::new(),
+            edit_versions: HashMap::new(),
+            doc_versions: HashMap::new(),
+            encoding: PositionEncoding::Utf16,
+            sync_kind: 0,
+            trace,
+            startup: StartupTimes {
+                spawned,
+                first_byte,
+                initialize_sent: None,
+                initialize_answered: None,
+                initialized_sent: None,
+            },
+        })
+    }
+
+    /// Spawn a configured server, carrying over its `workspace_configuration`
+    /// and wire trace file.
+    pub fn spawn_server(srv: &ServerConfig, cwd: &Path, verbose: bool) -> Result<Self, String> {
+        let trace = match srv.trace {
+            Some(ref path) => {
+                let trace = Trace::open(path)?;
+                trace.record("spawn", 0, &json!({ "cmd": srv.cmd, "args": srv.args }));
+                Some(trace)
+            }
+            None => None,
+        };
+        let mut c = Self::spawn(&srv.cmd, &srv.args, cwd, verbose, trace)?;
+        let pid = c.child.id();
+        if let Some(s) = live().iter_mut().find(|s| s.pid == pid) {
+            s.label = Some(srv.label.clone());
+        }
+        if let Some(ref wc) = srv.workspace_configuration {
+            c.workspace_config = wc.clone();
+        }
+        Ok(c)
+    }
+
+    /// Frame and write one JSON-RPC message to the server's stdin.
+    pub fn write_message(&mut self, msg: &Value) -> Result<(), String> {
+        let body = serde_json::to_string(msg).unwrap();
+        if let Some(ref trace) = self.trace {
+            trace.record("send", body.len(), msg);
+        }
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let w = writer.as_mut().ok_or("stdin closed")?;
+        write!(w, "Content-Length: {}\r\n\r\n{}", body.len(), body).map_err(|e| e.to_string())?;
+        w.flush().map_err(|e| e.to_string())
+    }
+
+    pub fn send(&mut self, method: &str, params: Value) -> Result<i64, String> {
+        let id = self.id;
+        let msg = json!({"jsonrpc":"2.0","id":id,"method":method,"params":params});
+        self.id += 1;
+        self.write_message(&msg)?;
+        Ok(id)
+    }
+
+    pub fn notif(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.write_message(&json!({"jsonrpc":"2.0","method":method,"params":params}))
+    }
+
+    /// Send a JSON-RPC response to a server-initiated request.
+    pub fn respond(&mut self, id: Value, result: Value) -> Result<(), String> {
+        self.write_message(&json!({"jsonrpc":"2.0","id":id,"result":result}))
+    }
+
+    pub fn recv(&mut self, timeout: Duration) -> Result<Value, String> {
+        self.rx.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => "timeout".to_string(),
+            mpsc::RecvTimeoutError::Disconnected => "EOF".to_string(),
+        })
+    }
+
+    /// Answer a server-to-client request. Returns `false` if `msg` is not a
+    /// request (a response or notification), so the caller should handle it.
+    ///
+    /// Unanswered requests stall some servers' transports, so everything gets
+    /// a reply: `workspace/configuration` from the server's
+    /// `workspace_configuration`, `workspace/applyEdit` by editing the files
+    /// on disk, `client/(un)registerCapability` by tracking registrations,
+    /// and anything else (e.g. `window/workDoneProgress/create`) with `null`.
+    pub fn handle_server_request(&mut self, msg: &Value) -> bool {
+        let (id, method) = match (msg.get("id"), msg.get("method").and_then(|m| m.as_str())) {
+            (Some(id), Some(method)) => (id.clone(), method.to_string()),
+            _ => return false,
+        };
+        let result = match method.as_str() {
+            "workspace/configuration" => {
+                let params = msg.get("params").cloned().unwrap_or(Value::Null);
+                let result = self.configuration_result(&params);
+                if self.verbose {
+                    eprintln!(
+                        "  {} {} -> {}",
+                        style("config").cyan().dim(),
+                        style(serde_json::to_string(&params).unwrap_or_default()).dim(),
+                        style(serde_json::to_string(&result).unwrap_or_default()).dim(),
+                    );
+                }
+                self.config_exchanges
+                    .push(json!({ "params": params, "result": result }));
+                result
+            }
+            "workspace/applyEdit" => {
+                let edit = msg
+                    .get("params")
+                    .and_then(|p| p.get("edit"))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                let result = self.apply_server_edit(&edit);
+                if self.verbose {
+                    eprintln!(
+                        "  {} {}",
+                        style("applyEdit").cyan().dim(),
+                        style(serde_json::to_string(&result).unwrap_or_default()).dim(),
+                    );
+                }
+                result
+            }
+            "client/registerCapability" => {
+                if let Some(regs) = msg
+                    .get("params")
+                    .and_then(|p| p.get("registrations"))
+                    .and_then(|r| r.as_array())
+                {
+                    self.registrations.extend(regs.iter().cloned());
+                }
+                json!(null)
+            }
+            "client/unregisterCapability" => {
+                // The spec spells the field `unregisterations`.
+                if let Some(unregs) = msg
+                    .get("params")
+                    .and_then(|p| p.get("unregisterations"))
+                    .and_then(|r| r.as_array())
+                {
+                    for u in unregs {
+                        let id = u.get("id");
+                        self.registrations.retain(|r| r.get("id") != id);
+                    }
+                }
+                json!(null)
+            }
+            _ => json!(null),
+        };
+        let _ = self.respond(id, result);
+        true
+    }
+
+    /// Build the `workspace/configuration` response: one value per requested
+    /// item, looked up by `section` in the configured map. An exact key match
+    /// wins; otherwise dotted sections walk nested maps. Items without a
+    /// section get the whole map. Unknown sections answer `null`.
+    pub fn configuration_result(&self, params: &Value) -> Value {
+        let items = params
+            .get("items")
+            .and_then(|i| i.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let answers: Vec<Value> = items
+            .iter()
+            .map(|item| match item.get("section").and_then(|s| s.as_str()) {
+                None | Some("") => self.workspace_config.clone(),
+                Some(section) => {
+                    if let Some(v) = self.workspace_config.get(section) {
+                        return v.clone();
+                    }
+                    let mut cur = &self.workspace_config;
+                    for key in section.split('.') {
+                        match cur.get(key) {
+                            Some(v) => cur = v,
+                            None => return Value::Null,
+                        }
+                    }
+                    cur.clone()
+                }
+            })
+            .collect();
+        Value::Array(answers)
+    }
+
+    /// Apply a server-pushed `WorkspaceEdit` to disk and notify the server
+    /// with `didChange`. Returns the `ApplyWorkspaceEditResult`.
+    ///
+    /// Resource operations (create/rename/delete) aren't advertised, so an
+    /// edit containing one is rejected as a whole before anything is written,
+    /// as is one targeting a file that doesn't exist or a document whose
+    /// `version` isn't the one we last sent. A failed write is reported as
+    /// `applied: false` too, so the server never assumes an edit landed.
+    pub fn apply_server_edit(&mut self, edit: &Value) -> Value {
+        let reject = |reason: String| json!({ "applied": false, "failureReason": reason });
+        let mut change_sets: Vec<serde_json::Map<String, Value>> = Vec::new();
+        if let Some(doc_changes) = edit.get("documentChanges").and_then(|d| d.as_array()) {
+            for dc in doc_changes {
+                if let Some(kind) = dc.get("kind").and_then(|k| k.as_str()) {
+                    return reject(format!("resource operation '{}' is not supported", kind));
+                }
+                let doc = dc.get("textDocument");
+                let doc_uri = doc.and_then(|t| t.get("uri")).and_then(|u| u.as_str());
+                let (Some(doc_uri), Some(edits)) = (doc_uri, dc.get("edits")) else {
+                    return reject(
+                        "documentChanges entry without textDocument.uri or edits".into(),
+                    );
+                };
+                // A null version means the server doesn't know one; a
+                // document we never opened has no version to check against.
+                let version = doc.and_then(|t| t.get("version")).and_then(|v| v.as_i64());
+                if let (Some(version), Some(&open)) = (version, self.doc_versions.get(doc_uri)) {
+                    if version != open as i64 {
+                        return reject(format!(
+                            "{} is at version {}, not {}",
+                            doc_uri, open, version
+                        ));
+                    }
+                }
+                let mut set = serde_json::Map::new();
+                set.insert(doc_uri.to_string(), edits.clone());
+                change_sets.push(set);
+            }
+        } else if let Some(changes) = edit.get("changes").and_then(|c| c.as_object()) {
+            change_sets.push(changes.clone());
+        }
+        for file_uri in change_sets.iter().flat_map(|set| set.keys()) {
+            match file_uri.strip_prefix("file://") {
+                Some(path) if Path::new(path).is_file() => {}
+                Some(path) => return reject(format!("{} does not exist", path)),
+                None => return reject(format!("{} is not a file URI", file_uri)),
+            }
+        }
+
+        let mut restore = std::mem::take(&mut self.edit_restore);
+        let mut versions = std::mem::take(&mut self.edit_versions);
+        let mut result = Ok(());
+        for set in &change_sets {
+            result = apply_workspace_changes_to_disk(self, set, &mut restore, &mut versions);
+            for file_uri in set.keys() {
+                let path = PathBuf::from(file_uri.strip_prefix("file://").unwrap_or(file_uri));
+                if let Ok(written) = std::fs::read(&path) {
+                    self.edit_written.insert(path, written);
+                }
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+        self.edit_restore = restore;
+        self.edit_versions = versions;
+        match result {
+            Ok(()) => json!({ "applied": true }),
+            Err(e) => reject(e),
+        }
+    }
+
+    /// Undo `workspace/applyEdit` changes. A file is only put back if it still
+    /// holds what we wrote, so a runner that already restored its own
+    /// snapshot of the same file isn't clobbered. Files the edit created are
+    /// removed.
+    pub fn restore_applied_edits(&mut self) {
+        let restore = std::mem::take(&mut self.edit_restore);
+        for (path, written) in std::mem::take(&mut self.edit_written) {
+            if std::fs::read(&path).ok().as_ref() != Some(&written) {
+                continue;
+            }
+            match restore.get(&path) {
+                Some(original) => {
+                    let _ = journal::write(&path, original);
+                }
+                None => {
+                    let _ = journal::remove(&path);
+                }
+            }
+        }
+    }
+
+    /// Keep answering server requests until `window` passes without any
+    /// traffic. Servers often ask for configuration right after `initialized`.
+    pub fn drain(&mut self, window: Duration) {
+        while let Ok(msg) = self.recv(window) {
+            self.handle_server_request(&msg);
+        }
+    }
+
+    pub fn read_response(&mut self, expected_id: i64, timeout: Duration) -> Result<Value, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err("timeout".into());
+            }
+            let msg = self.recv(remaining)?;
+            // Answer server-to-client requests (e.g. workspace/configuration)
+            // that arrive while we're waiting for a specific response.
+            if self.handle_server_request(&msg) {
+                continue;
+            }
+            // Match by id — skip notifications
+            if msg.get("id").and_then(|v| v.as_i64()) == Some(expected_id) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Wait for a `$/progress` end notification (e.g. background project
+    /// indexing). Drains all notifications until it sees one with
+    /// `value.kind == "end"`, or until timeout. Auto-responds to
+    /// `window/workDoneProgress/create` requests from the server.
+    ///
+    /// If `token_filter` is `Some`, only matches progress-end notifications
+    /// whose `params.token` equals the given string.
+    pub fn wait_for_progress_end(&mut self, timeout: Duration, token_filter: Option<&str>) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            let msg = match self.recv(remaining) {
+                Ok(m) => m,
+                Err(_) => return,
+            };
+            if self.handle_server_request(&msg) {
+                continue;
+            }
+            if msg.get("method").and_then(|m| m.as_str()) == Some("$/progress") {
+                let params = msg.get("params");
+                let kind = params
+                    .and_then(|p| p.get("value"))
+                    .and_then(|v| v.get("kind"))
+                    .and_then(|k| k.as_str());
+                if kind == Some("end") {
+                    // If a token filter is set, only match that specific token.
+                    if let Some(expected) = token_filter {
+                        let token = params.and_then(|p| p.get("token")).and_then(|t| t.as_str());
+                        if token == Some(expected) {
+                            return;
+                        }
+                        // Wrong token — keep waiting.
+                        continue;
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// After `initialized` and a didOpen of `file_uri`: read until the server
+    /// has published diagnostics for the file and ended every `$/progress`
+    /// it began, then for `quiet` more in case another progress begins, or
+    /// until `timeout` runs out. Returns when the first diagnostics arrived
+    /// and when the last progress ended.
+    pub fn wait_for_startup(
+        &mut self,
+        file_uri: &str,
+        quiet: Duration,
+        timeout: Duration,
+    ) -> (Option<Instant>, Option<Instant>) {
+        let deadline = Instant::now() + timeout;
+        let mut first_diagnostics = None;
+        let mut last_progress_end = None;
+        let mut active: HashSet<String> = HashSet::new();
+        let mut quiet_until = Instant::now() + quiet;
+        loop {
+            let settled = first_diagnostics.is_some() && active.is_empty();
+            let until = if settled {
+                quiet_until.min(deadline)
+            } else {
+                deadline
+            };
+            let remaining = until.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return (first_diagnostics, last_progress_end);
+            }
+            let msg = match self.recv(remaining) {
+                Ok(m) => m,
+                Err(_) => return (first_diagnostics, last_progress_end),
+            };
+            if self.handle_server_request(&msg) {
+                continue;
+            }
+            let now = Instant::now();
+            if is_diagnostics_for(&msg, file_uri) {
+                first_diagnostics.get_or_insert(now);
+                quiet_until = now + quiet;
+                continue;
+            }
+            if msg.get("method").and_then(|m| m.as_str()) != Some("$/progress") {
+                continue;
+            }
+            let params = msg.get("params");
+            // Tokens are strings or integers.
+            let token = params
+                .and_then(|p| p.get("token"))
+                .map(|t| t.to_string())
+                .unwrap_or_default();
+            match params
+                .and_then(|p| p.get("value"))
+                .and_then(|v| v.get("kind"))
+                .and_then(|k| k.as_str())
+            {
+                Some("begin") => {
+                    active.insert(token);
+                }
+                Some("end") => {
+                    active.remove(&token);
+                    last_progress_end = Some(now);
+                }
+                _ => continue,
+            }
+            quiet_until = now + quiet;
+        }
+    }
+
+    /// Keep reading until `quiet` passes without a `publishDiagnostics` for
+    /// `file_uri`, or `timeout` runs out. Returns the last one and when it
+    /// arrived.
+    pub fn wait_for_diagnostics_settle(
+        &mut self,
+        file_uri: &str,
+        quiet: Duration,
+        timeout: Duration,
+    ) -> Option<(Instant, Value)> {
+        let deadline = Instant::now() + timeout;
+        let mut quiet_until = Instant::now() + quiet;
+        let mut last = None;
+        loop {
+            let remaining = quiet_until
+                .min(deadline)
+                .saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return last;
+            }
+            let msg = match self.recv(remaining) {
+                Ok(m) => m,
+                Err(_) => return last,
+            };
+            if self.handle_server_request(&msg) {
+                continue;
+            }
+            if is_diagnostics_for(&msg, file_uri) {
+                let now = Instant::now();
+                last = Some((now, msg));
+                quiet_until = now + quiet;
+            }
+        }
+    }
+
+    pub fn wait_for_valid_diagnostics(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<DiagnosticsInfo, String> {
+        self.wait_for_diagnostics_with_min(timeout, 0)
+    }
+
+    /// Like `wait_for_valid_diagnostics` but keeps looping until at least
+    /// `min_count` diagnostics are received.  Used by `bench_code_action` so
+    /// that an empty clearing-publish doesn't cause us to send codeAction with
+    /// an empty diagnostics array and get null back.
+    pub fn wait_for_diagnostics_with_min(
+        &mut self,
+        timeout: Duration,
+        min_count: usize,
+    ) -> Result<DiagnosticsInfo, String> {
+        let start = Instant::now();
+        let deadline = start + timeout;
+        let mut last_count = 0usize;
+        let mut last_msg = json!(null);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return if last_count >= min_count.max(1) {
+                    Ok(DiagnosticsInfo { message: last_msg })
+                } else {
+                    Err("timeout".into())
+                };
+            }
+            let msg = self.recv(remaining)?;
+            // Answer server requests (e.g. window/workDoneProgress/create)
+            // that may arrive while we're waiting for diagnostics.
+            if self.handle_server_request(&msg) {
+                continue;
+            }
+            let count = msg
+                .get("params")
+                .and_then(|p| p.get("diagnostics"))
+                .and_then(|d| d.as_array())
+                .map(|a| a.len())
+                .unwrap_or(0);
+            last_count = count;
+            last_msg = msg;
+            if count >= min_count {
+                return Ok(DiagnosticsInfo { message: last_msg });
+            }
+            // count < min_count — keep waiting for the real publish
+        }
+    }
+
+    /// Perform the `initialize`/`initialized` handshake and return the
+    /// server's `InitializeResult` (`capabilities`, `serverInfo`, ...).
+    pub fn initialize(
+        &mut self,
+        root: &str,
+        init_settings: Option<&Value>,
+    ) -> Result<Value, String> {
+        let mut params = json!({
+            "processId": std::process::id(),
+            "rootUri": root,
+            "capabilities": {
+                "general": {
+                    "positionEncodings": ["utf-16", "utf-8", "utf-32"]
+                },
+                "textDocument": {
+                    "publishDiagnostics": {},
+                    "definition": { "dynamicRegistration": false, "linkSupport": true },
+                    "declaration": { "dynamicRegistration": false, "linkSupport": true },
+                    "hover": { "dynamicRegistration": false, "contentFormat": ["plaintext", "markdown"] },
+                    "completion": {
+                        "dynamicRegistration": false,
+                        "completionItem": { "snippetSupport": false }
+                    },
+                    "documentSymbol": { "dynamicRegistration": false },
+                    "documentLink": { "dynamicRegistration": false },
+                    "references": { "dynamicRegistration": false },
+                    "rename": { "dynamicRegistration": false, "prepareSupport": true },
+                    "signatureHelp": { "dynamicRegistration": false },
+                    "codeAction": { "dynamicRegistration": false },
+                    "documentHighlight": { "dynamicRegistration": false },
+                    "selectionRange": { "dynamicRegistration": false },
+                    "callHierarchy": { "dynamicRegistration": false },
+                },
+                "window": {
+                    "workDoneProgress": true
+                },
+                "workspace": {
+                    "applyEdit": true,
+                    "workspaceEdit": { "documentChanges": true },
+                    "configuration": true,
+                    "symbol": { "dynamicRegistration": false },
+                    "fileOperations": {
+                        "willRename": true,
+                        "willCreate": true,
+                        "willDelete": true
+                    }
+                }
+            },
+        });
+        if let Some(settings) = init_settings {
+            params["initializationOptions"] = settings.clone();
+        }
+        self.startup.initialize_sent = Some(Instant::now());
+        let id = self.send("initialize", params)?;
+        let resp = self.read_response(id, Duration::from_secs(10))?;
+        self.startup.initialize_answered = Some(Instant::now());
+        let result = resp.get("result").cloned().unwrap_or(Value::Null);
+        if let Some(caps) = result.get("capabilities") {
+            self.encoding = PositionEncoding::from_capabilities(caps);
+            // textDocumentSync is either a kind or TextDocumentSyncOptions
+            let sync = caps.get("textDocumentSync");
+            self.sync_kind = sync
+                .and_then(|s| s.as_u64())
+                .or_else(|| sync.and_then(|s| s.get("change")).and_then(|c| c.as_u64()))
+                .unwrap_or(0);
+        }
+        self.notif("initialized", json!({}))?;
+        self.startup.initialized_sent = Some(Instant::now());
+        Ok(result)
+    }
+
+    pub fn open_file(&mut self, path: &Path) -> Result<(), String> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file_uri = uri(path);
+        self.notif(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": file_uri,
+                    "languageId": "solidity",
+                    "version": 1,
+                    "text": content,
+                }
+            }),
+        )?;
+        self.doc_versions.insert(file_uri, 1);
+        Ok(())
+    }
+
+    /// Send a full-document textDocument/didChange notification.
+    pub fn did_change(&mut self, file_uri: &str, version: i32, text: &str) -> Result<(), String> {
+        self.notif(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": file_uri, "version": version },
+                "contentChanges": [{ "text": text }],
+            }),
+        )?;
+        self.doc_versions.insert(file_uri.to_string(), version);
+        Ok(())
+    }
+
+    /// Ask the server to cancel request `id` via `$/cancelRequest`.
+    pub fn cancel(&mut self, id: i64) -> Result<(), String> {
+        self.notif("$/cancelRequest", json!({ "id": id }))
+    }
+
+    /// Send a textDocument/didChange with a single range edit covering only
+    /// what differs between `old` and `new`.
+    pub fn did_change_incremental(
+        &mut self,
+        file_uri: &str,
+        version: i32,
+        old: &str,
+        new: &str,
+    ) -> Result<(), String> {
+        let change = incremental_change(old, new, self.encoding);
+        self.notif(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": file_uri, "version": version },
+                "contentChanges": [change],
+            }),
+        )?;
+        self.doc_versions.insert(file_uri.to_string(), version);
+        Ok(())
+    }
+
+    /// Send the change from `old` to `new` in the requested `sync` mode.
+    /// Incremental changes are only sent to servers that advertise
+    /// `TextDocumentSyncKind.Incremental`; others get the full document.
+    pub fn sync_change(
+        &mut self,
+        file_uri: &str,
+        version: i32,
+        old: &str,
+        new: &str,
+        sync: SyncMode,
+    ) -> Result<(), String> {
+        if sync == SyncMode::Incremental && self.sync_kind == 2 {
+            self.did_change_incremental(file_uri, version, old, new)
+        } else {
+            self.did_change(file_uri, version, new)
+        }
+    }
+
+    /// Graceful LSP shutdown: send `shutdown` request, wait for response,
+    /// send `exit` notification, then wait for the process to exit.
+    /// Falls back to SIGKILL if the server doesn't exit within 5 seconds.
+    /// This allows profilers (DHAT, etc.) to write output on clean exit.
+    pub fn kill(mut self) {
+        self.shutdown_gracefully();
+    }
+
+    /// Kill the process without the shutdown handshake, for a server that
+    /// has already crashed or stopped answering.
+    pub fn abort(mut self) {
+        self.forget();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        self.restore_applied_edits();
+    }
+
+    pub fn shutdown_gracefully(&mut self) {
+        self.stop_process();
+        self.restore_applied_edits();
+    }
+
+    /// Drop this server from the live-server list.
+    fn forget(&self) {
+        let pid = self.child.id();
+        live().retain(|s| s.pid != pid);
+    }
+
+    pub fn stop_process(&mut self) {
+        self.forget();
+        // 1. Send shutdown request
+        if let Ok(id) = self.send("shutdown", json!(null)) {
+            // Wait up to 5s for shutdown response
+            let _ = self.read_response(id, Duration::from_secs(5));
+        }
+        // 2. Send exit notification
+        let _ = self.notif("exit", json!(null));
+        // 3. Close stdin — tower-lsp's serve loop detects EOF and returns,
+        //    allowing destructors (e.g. DHAT profiler) to run.
+        drop(self.writer.lock().unwrap_or_else(|e| e.into_inner()).take());
+        // 4. Wait for process to exit (up to 5s), then force kill
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(_)) => return, // exited cleanly
+                Ok(None) => {
+                    if Instant::now() >= deadline {
+                        let _ = self.child.kill();
+                        let _ = self.child.wait();
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(_) => {
+                    let _ = self.child.kill();
+                    let _ = self.child.wait();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        // Try graceful shutdown first (allows profilers to write output),
+        // fall back to kill if it fails.
+        self.shutdown_gracefully();
+    }
+}
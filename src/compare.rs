@@ -0,0 +1,231 @@
+//! `lsp-bench compare`: regression detection between two `results.json`
+//! files.
+//!
+//! Benchmarks are matched by name and servers by label. A pair counts as
+//! regressed or improved when the Mann-Whitney U test on its iteration
+//! latencies is significant *and* the chosen statistic moved by at least the
+//! configured percentage; anything else is unchanged. A server that passed
+//! in the base run and fails in, or is missing from, the head run is a
+//! regression too.
+
+use crate::report::{format_latency, Metric};
+use crate::results::{Results, ServerEntry, Status};
+use crate::stats::{mann_whitney_u, min_p_value, Stats};
+use console::{style, StyledObject};
+
+/// When a change counts.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Minimum slowdown, in percent, for a regression.
+    pub regression_pct: f64,
+    /// Minimum speedup, in percent, for an improvement.
+    pub improvement_pct: f64,
+    /// Significance level for the Mann-Whitney U test.
+    pub alpha: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Regressed,
+    Improved,
+    Unchanged,
+    /// Passed in the base run, failed in the head run.
+    Broke,
+    /// Failed in the base run, passed in the head run.
+    Fixed,
+    /// Passed in the base run, absent from the head run: the benchmark or
+    /// the server was dropped.
+    Missing,
+}
+
+/// One benchmark × server pair of the base run.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub benchmark: String,
+    pub server: String,
+    /// The chosen statistic in each run, when it passed.
+    pub base_ms: Option<f64>,
+    pub head_ms: Option<f64>,
+    /// Iterations of each run that passed; 0 for a failed or missing side.
+    pub samples: (usize, usize),
+    /// `(head - base) / base`, in percent.
+    pub change_pct: Option<f64>,
+    /// Mann-Whitney U p-value; `None` with fewer than two iterations a side.
+    pub p_value: Option<f64>,
+    pub outcome: Outcome,
+}
+
+impl Comparison {
+    pub fn is_regression(&self) -> bool {
+        matches!(
+            self.outcome,
+            Outcome::Regressed | Outcome::Broke | Outcome::Missing
+        )
+    }
+
+    pub fn is_improvement(&self) -> bool {
+        matches!(self.outcome, Outcome::Improved | Outcome::Fixed)
+    }
+
+    /// Whether both runs have enough iterations for the test to reach
+    /// `alpha` at all; see [`min_p_value`].
+    pub fn can_reach(&self, alpha: f64) -> bool {
+        min_p_value(self.samples.0, self.samples.1) < alpha
+    }
+}
+
+/// Compare every benchmark × server pair of `base` against `head` on
+/// `metric`. Pairs that failed in both runs, failed in the base run and are
+/// missing from the head run, or appear only in the head run are left out.
+pub fn compare(base: &Results, head: &Results, metric: Metric, t: &Thresholds) -> Vec<Comparison> {
+    let mut out = Vec::new();
+    for bench in &base.benchmarks {
+        let head_bench = head.benchmarks.iter().find(|b| b.name == bench.name);
+        for base_entry in &bench.servers {
+            let head_entry =
+                head_bench.and_then(|b| b.servers.iter().find(|e| e.server == base_entry.server));
+            let base_ms = latencies(base_entry);
+            let head_ms = head_entry.and_then(latencies);
+            let stat = |ms: &Option<Vec<f64>>| ms.as_deref().map(|ms| metric.pick(&Stats::of(ms)));
+            let (b, h) = (stat(&base_ms), stat(&head_ms));
+            let (change_pct, p_value, outcome) = match (&base_ms, &head_ms) {
+                (None, None) => continue,
+                (Some(_), None) if head_entry.is_none() => (None, None, Outcome::Missing),
+                (Some(_), None) => (None, None, Outcome::Broke),
+                (None, Some(_)) => (None, None, Outcome::Fixed),
+                (Some(base_ms), Some(head_ms)) => {
+                    let (b, h) = (b.unwrap(), h.unwrap());
+                    let change = (b > 0.0).then(|| (h - b) / b * 100.0);
+                    let p = (base_ms.len() >= 2 && head_ms.len() >= 2)
+                        .then(|| mann_whitney_u(base_ms, head_ms).1);
+                    let significant = p.is_some_and(|p| p < t.alpha);
+                    let outcome = match change {
+                        Some(c) if significant && c >= t.regression_pct => Outcome::Regressed,
+                        Some(c) if significant && -c >= t.improvement_pct => Outcome::Improved,
+                        _ => Outcome::Unchanged,
+                    };
+                    (change, p, outcome)
+                }
+            };
+            let count = |ms: &Option<Vec<f64>>| ms.as_ref().map_or(0, Vec::len);
+            out.push(Comparison {
+                benchmark: bench.name.clone(),
+                server: base_entry.server.clone(),
+                base_ms: b,
+                head_ms: h,
+                samples: (count(&base_ms), count(&head_ms)),
+                change_pct,
+                p_value,
+                outcome,
+            });
+        }
+    }
+    out
+}
+
+/// Iteration latencies of a passing entry.
+fn latencies(entry: &ServerEntry) -> Option<Vec<f64>> {
+    (entry.status == Status::Ok && !entry.iterations.is_empty())
+        .then(|| entry.iterations.iter().map(|it| it.ms).collect())
+}
+
+// ── Output ──────────────────────────────────────────────────────────────────
+
+/// Print the regressed, improved and unchanged tables and a summary line.
+pub fn print_comparison(rows: &[Comparison], metric: Metric) {
+    let groups: [(StyledObject<&str>, fn(&Comparison) -> bool); 3] = [
+        (style("Regressed").red().bold(), Comparison::is_regression),
+        (style("Improved").green().bold(), Comparison::is_improvement),
+        (style("Unchanged").dim().bold(), |r| {
+            r.outcome == Outcome::Unchanged
+        }),
+    ];
+    let mut counts = [0; 3];
+    for (i, (title, pred)) in groups.into_iter().enumerate() {
+        let group: Vec<&Comparison> = rows.iter().filter(|r| pred(r)).collect();
+        counts[i] = group.len();
+        if group.is_empty() {
+            continue;
+        }
+        println!("{} ({})", title, group.len());
+        print_table(&group, metric);
+        println!();
+    }
+    let missing = rows
+        .iter()
+        .filter(|r| r.outcome == Outcome::Missing)
+        .count();
+    print!(
+        "{} regressed, {} improved, {} unchanged",
+        counts[0], counts[1], counts[2]
+    );
+    if missing > 0 {
+        print!(" ({} missing from head)", missing);
+    }
+    println!();
+}
+
+fn print_table(rows: &[&Comparison], metric: Metric) {
+    let header = [
+        "benchmark".to_string(),
+        "server".to_string(),
+        format!("base {}", metric.label()),
+        format!("head {}", metric.label()),
+        "change".to_string(),
+        "p".to_string(),
+    ];
+    let cells: Vec<[String; 6]> = rows
+        .iter()
+        .map(|r| {
+            let ms = |v: Option<f64>| v.map(format_latency).unwrap_or_else(|| "fail".into());
+            let head = match r.outcome {
+                Outcome::Missing => "-".to_string(),
+                _ => ms(r.head_ms),
+            };
+            let change = match r.outcome {
+                Outcome::Broke => "broke".to_string(),
+                Outcome::Missing => "missing".to_string(),
+                Outcome::Fixed => "fixed".to_string(),
+                _ => r
+                    .change_pct
+                    .map(|c| format!("{:+.1}%", c))
+                    .unwrap_or_else(|| "-".into()),
+            };
+            [
+                r.benchmark.clone(),
+                r.server.clone(),
+                ms(r.base_ms),
+                head,
+                change,
+                r.p_value
+                    .map(|p| format!("{:.3}", p))
+                    .unwrap_or_else(|| "-".into()),
+            ]
+        })
+        .collect();
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &cells {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |row: &[String; 6]| {
+        let parts: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, w))| {
+                if i < 2 {
+                    format!("{:<w$}", cell, w = w)
+                } else {
+                    format!("{:>w$}", cell, w = w)
+                }
+            })
+            .collect();
+        format!("  {}", parts.join("  ").trim_end())
+    };
+    println!("{}", style(line(&header)).dim());
+    for row in &cells {
+        println!("{}", line(row));
+    }
+}
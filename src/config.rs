@@ -0,0 +1,834 @@
+//! Benchmark configuration (`benchmark.yaml`) and the server registry
+//! (`servers.yaml`).
+
+use crate::sandbox::{CloneMode, SandboxMode};
+use console::style;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// ── Server Registry ─────────────────────────────────────────────────────────
+
+/// A version entry in the server registry. Overrides the parent server's fields.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ServerVersion {
+    #[serde(default)]
+    pub cmd: Option<String>,
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub workspace_configuration: Option<Value>,
+}
+
+/// A server definition in the registry, with optional named versions.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerRegistryEntry {
+    pub cmd: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub link: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub workspace_configuration: Option<Value>,
+    #[serde(default)]
+    pub versions: HashMap<String, ServerVersion>,
+}
+
+/// Server registry: name → definition. Loaded from servers.yaml.
+pub type ServerRegistry = HashMap<String, ServerRegistryEntry>;
+
+/// Load a server registry file. Returns empty map if file doesn't exist.
+pub fn load_server_registry(path: &Path) -> ServerRegistry {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_yaml::from_str(&content).unwrap_or_else(|e| {
+            eprintln!(
+                "  {} parsing {}: {}",
+                style("warn").yellow(),
+                path.display(),
+                e
+            );
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Resolve a server reference like "mmsaki" or "mmsaki@v0.1.20" into a ServerConfig.
+/// Falls back to treating the name as a cmd if not found in the registry.
+fn resolve_server(name: &str, registry: &ServerRegistry) -> ServerConfig {
+    let (base_name, version) = match name.split_once('@') {
+        Some((b, v)) => (b, Some(v)),
+        None => (name, None),
+    };
+
+    if let Some(entry) = registry.get(base_name) {
+        let label = match version {
+            Some(v) => format!("{} {}", base_name, v),
+            None => base_name.to_string(),
+        };
+
+        // Start with base entry values
+        let mut cmd = entry.cmd.clone();
+        let mut args = entry.args.clone();
+        let mut link = entry.link.clone();
+        let mut description = entry.description.clone();
+        let mut commit = entry.commit.clone();
+        let mut repo = entry.repo.clone();
+        let mut workspace_configuration = entry.workspace_configuration.clone();
+
+        // If a version is specified, override with version-specific values
+        if let Some(v) = version {
+            if let Some(ver) = entry.versions.get(v) {
+                if let Some(ref c) = ver.cmd {
+                    cmd = c.clone();
+                }
+                if let Some(ref a) = ver.args {
+                    args = a.clone();
+                }
+                if let Some(ref l) = ver.link {
+                    link = l.clone();
+                }
+                if let Some(ref d) = ver.description {
+                    description = d.clone();
+                }
+                if let Some(ref c) = ver.commit {
+                    commit = Some(c.clone());
+                }
+                if let Some(ref r) = ver.repo {
+                    repo = Some(r.clone());
+                }
+                if let Some(ref wc) = ver.workspace_configuration {
+                    workspace_configuration = Some(wc.clone());
+                }
+            } else {
+                eprintln!(
+                    "  {} version '{}' not found for server '{}', using base",
+                    style("warn").yellow(),
+                    v,
+                    base_name
+                );
+            }
+        }
+
+        ServerConfig {
+            label,
+            cmd,
+            args,
+            link,
+            description,
+            commit,
+            repo,
+            workspace_configuration,
+            trace: None,
+        }
+    } else {
+        // Not in registry — treat the name as both label and cmd
+        ServerConfig {
+            label: name.to_string(),
+            cmd: name.to_string(),
+            args: Vec::new(),
+            link: String::new(),
+            description: String::new(),
+            commit: None,
+            repo: None,
+            workspace_configuration: None,
+            trace: None,
+        }
+    }
+}
+
+/// Find a servers.yaml file by checking: explicit path, next to config, parent dirs.
+pub fn discover_servers_file(config_path: &str, explicit: Option<&str>) -> Option<PathBuf> {
+    // 1. Explicit path from config or CLI
+    if let Some(p) = explicit {
+        let path = Path::new(config_path)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(p);
+        if path.exists() {
+            return Some(path);
+        }
+        // Also try as absolute/relative to cwd
+        let abs = Path::new(p);
+        if abs.exists() {
+            return Some(abs.to_path_buf());
+        }
+    }
+
+    // 2. Look next to the config file
+    if let Some(dir) = Path::new(config_path).parent() {
+        let candidate = dir.join("servers.yaml");
+        if candidate.exists() {
+            return Some(candidate);
+        }
+    }
+
+    // 3. Walk parent directories
+    let mut dir = Path::new(config_path)
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.to_path_buf());
+    while let Some(d) = dir {
+        let candidate = d.join("servers.yaml");
+        if candidate.exists() {
+            return Some(candidate);
+        }
+        dir = d.parent().map(|p| p.to_path_buf());
+    }
+
+    None
+}
+
+// ── Config ──────────────────────────────────────────────────────────────────
+
+/// Expected result for a goto-definition (or similar) response.
+///
+/// ```yaml
+/// expect:
+///   file: SafeCast.sol
+///   line: 39
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct CompletionItemExpect {
+    /// Expected label for a completion item.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Substring that must appear in completion item detail.
+    #[serde(default, rename = "detailContains")]
+    pub detail_contains: Option<String>,
+    /// Prefix that completion item sortText must start with.
+    #[serde(default, rename = "sortTextPrefix")]
+    pub sort_text_prefix: Option<String>,
+    /// Whether completion item should include additionalTextEdits.
+    #[serde(default, rename = "hasAdditionalTextEdits")]
+    pub has_additional_text_edits: Option<bool>,
+    /// Substring that must appear in at least one additionalTextEdit.newText.
+    #[serde(default, rename = "additionalTextEditsContain")]
+    pub additional_text_edits_contain: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ExpectConfig {
+    /// Expected filename suffix (e.g. "SafeCast.sol"). Matches if the response
+    /// URI ends with this string.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Expected 0-based line number in the response.
+    #[serde(default)]
+    pub line: Option<u32>,
+    /// Expected 0-based column (in characters) of the response range start.
+    /// Converted from the server's position encoding before comparing.
+    #[serde(default)]
+    pub col: Option<u32>,
+    /// Expected number of items in an array response (e.g. references count).
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// Minimum number of items in an array response. Passes if actual >= min_count.
+    #[serde(default, rename = "minCount")]
+    pub min_count: Option<usize>,
+    /// Completion-item predicates that must match at least one completion item.
+    #[serde(default, rename = "containsItems")]
+    pub contains_items: Vec<CompletionItemExpect>,
+    /// Completion-item predicates that must not match any completion item.
+    #[serde(default, rename = "absentItems")]
+    pub absent_items: Vec<CompletionItemExpect>,
+    /// For workspace/executeCommand: expect result.success == true/false.
+    #[serde(default)]
+    pub success: Option<bool>,
+    /// For textDocument/codeAction: expect at least one action whose title
+    /// contains this substring.
+    #[serde(default, rename = "titleContains")]
+    pub title_contains: Option<String>,
+}
+
+/// A file snapshot sent via didChange, with its own cursor position.
+/// `line`/`col` fall back to the method's position when omitted.
+///
+/// ```yaml
+/// didChange:
+///   - file: src/libraries/Pool.v2.sol
+///     line: 107
+///     col: 15
+///     expect:
+///       file: SafeCast.sol
+///       line: 39
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileSnapshot {
+    /// Path to the snapshot file (relative to project).
+    pub file: String,
+    /// 0-based line for the benchmark request after this snapshot.
+    #[serde(default)]
+    pub line: Option<u32>,
+    /// 0-based column for the benchmark request after this snapshot.
+    #[serde(default)]
+    pub col: Option<u32>,
+    /// Expected response (for --verify mode).
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+}
+
+/// A file to open via didOpen, then re-request on the original file.
+///
+/// ```yaml
+/// didOpen:
+///   - file: src/PoolManager.sol
+///     expect:
+///       minCount: 50
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DidOpenStep {
+    /// Path to the file to open (relative to project).
+    pub file: String,
+    /// Optional line override for the re-request on the original file.
+    /// If omitted, uses the method's line.
+    #[serde(default)]
+    pub line: Option<u32>,
+    /// Optional col override for the re-request on the original file.
+    /// If omitted, uses the method's col.
+    #[serde(default)]
+    pub col: Option<u32>,
+    /// Expected response after opening this file (for --verify mode).
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+}
+
+/// A rename step in a multi-rename sequence for workspace/willRenameFiles.
+///
+/// Each step renames a file and validates the result. The bench harness
+/// executes the full LSP lifecycle for each step:
+///   willRenameFiles → apply edits → rename on disk → didRenameFiles → wait for re-index
+///
+/// ```yaml
+/// renameSteps:
+///   - file: A.sol            # file to rename (relative to project root)
+///     newName: AA.sol         # new filename
+///     expect:
+///       count: 1              # expect 1 file with edits
+///   - file: AA.sol            # rename back
+///     newName: A.sol
+///     expect:
+///       count: 1
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RenameStep {
+    /// File to rename (relative to project root).
+    pub file: String,
+    /// New filename (just the filename, not a path).
+    #[serde(rename = "newName")]
+    pub new_name: String,
+    /// Expected response (for --verify mode).
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+}
+
+/// A create step in a lifecycle sequence for workspace/willCreateFiles.
+///
+/// ```yaml
+/// createSteps:
+///   - file: test/NewFile.sol
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateStep {
+    /// File to create (relative to project root).
+    pub file: String,
+    /// Expected response (for --verify mode).
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+}
+
+/// A delete step in a lifecycle sequence for workspace/willDeleteFiles.
+///
+/// ```yaml
+/// deleteSteps:
+///   - file: src/Foo.sol
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeleteStep {
+    /// File to delete (relative to project root).
+    pub file: String,
+    /// Expected response (for --verify mode).
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+}
+
+/// Per-method configuration overrides.
+///
+/// ```yaml
+/// methods:
+///   textDocument/completion:
+///     line: 105
+///     col: 28
+///     trigger: "."
+///   textDocument/definition:
+///     line: 50
+///     col: 10
+///     didChange:
+///       - file: src/libraries/Pool.v2.sol
+///         line: 107
+///         col: 15
+///       - file: src/libraries/Pool.v3.sol
+///         line: 112
+///         col: 15
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct MethodConfig {
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[serde(default)]
+    pub col: Option<u32>,
+    /// Start line for range-based requests (e.g. semanticTokens/range).
+    #[serde(default, rename = "startLine")]
+    pub start_line: Option<u32>,
+    /// Start column for range-based requests (e.g. semanticTokens/range).
+    #[serde(default, rename = "startCol")]
+    pub start_col: Option<u32>,
+    /// Trigger character (e.g. ".") — only used for textDocument/completion.
+    #[serde(default)]
+    pub trigger: Option<String>,
+    /// New name for textDocument/rename (defaults to "__lsp_bench_rename__").
+    #[serde(default, rename = "newName")]
+    pub new_name: Option<String>,
+    /// Override the target file for this method. For workspace/willRenameFiles,
+    /// this sets the oldUri instead of using the top-level `file`. Path is
+    /// relative to the project root (e.g. "src/libraries/Pool.sol").
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Command name for workspace/executeCommand (e.g. "solidity.reindex").
+    /// Required when benchmarking workspace/executeCommand.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Arguments for workspace/executeCommand. Defaults to an empty array.
+    #[serde(default)]
+    pub arguments: Vec<Value>,
+    /// After receiving the response, wait for a $/progress end notification
+    /// before recording the iteration time. Useful for workspace/executeCommand
+    /// when you want to measure end-to-end time including background work
+    /// (e.g. solidity.reindex completing the full project rebuild).
+    #[serde(default, rename = "waitForProgress")]
+    pub wait_for_progress: bool,
+    /// Wait for a $/progress end with a specific token before sending the
+    /// request (cold-start only). When set, ignores progress-end notifications
+    /// with different tokens. Use "solidity/projectIndexFull" to wait for the
+    /// full two-phase index to complete.
+    #[serde(default, rename = "waitForProgressToken")]
+    pub wait_for_progress_token: Option<String>,
+
+    /// Expected response for the base request (no didChange). Used by --verify.
+    #[serde(default)]
+    pub expect: Option<ExpectConfig>,
+    /// File snapshots sent sequentially via didChange. Each snapshot is one
+    /// iteration: send content, run one request at that snapshot's line/col.
+    #[serde(default, rename = "didChange")]
+    pub did_change: Vec<FileSnapshot>,
+    /// How `didChange` snapshots are sent: `full` replaces the whole document,
+    /// `incremental` sends the minimal range edit from the previous snapshot
+    /// (falls back to full if the server doesn't advertise incremental sync).
+    #[serde(default)]
+    pub sync: SyncMode,
+    /// Text typed one character at a time by the `typing` benchmark, starting
+    /// at this method's `line`/`col` (e.g. `"pool.swap("`).
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Requests the `typing` benchmark fires after every keystroke, or the
+    /// mix the `load` benchmark cycles through.
+    #[serde(default)]
+    pub requests: Vec<String>,
+    /// Number of requests the `load` benchmark keeps in flight (default 4).
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Request the `$/cancelRequest` benchmark sends and then cancels.
+    /// Defaults to `textDocument/references`.
+    #[serde(default)]
+    pub request: Option<String>,
+    /// Milliseconds between sending the request and cancelling it (default 10).
+    #[serde(default, rename = "cancelAfterMs")]
+    pub cancel_after_ms: Option<u64>,
+    /// Files to open sequentially via didOpen. After each open (and waiting for
+    /// diagnostics), the benchmark request is re-sent on the original file.
+    /// Used to test cross-file features like forward references: open file A,
+    /// get references, open file B (which imports A), get references again —
+    /// the count should grow as the server discovers more cross-file references.
+    #[serde(default, rename = "didOpen")]
+    pub did_open: Vec<DidOpenStep>,
+    /// Cold-start mode: spawn a fresh server per iteration and measure the full
+    /// end-to-end time from didOpen through diagnostics through the method response.
+    /// This captures what the user actually feels — compilation + request latency.
+    #[serde(default)]
+    pub cold: bool,
+    /// Sequential rename steps for workspace/willRenameFiles. Each step is a
+    /// full rename lifecycle: willRenameFiles → apply edits on disk → didRenameFiles
+    /// → wait for re-index. This tests the real-world multi-rename scenario where
+    /// each rename mutates state and the next rename must work on the new state.
+    #[serde(default, rename = "renameSteps")]
+    pub rename_steps: Vec<RenameStep>,
+    /// Sequential create steps for workspace/willCreateFiles. Each step is a
+    /// full create lifecycle: willCreateFiles → apply edits on disk (if any)
+    /// → create file on disk → didCreateFiles.
+    #[serde(default, rename = "createSteps")]
+    pub create_steps: Vec<CreateStep>,
+    /// Sequential delete steps for workspace/willDeleteFiles. Each step is a
+    /// full delete lifecycle: willDeleteFiles → apply edits on disk (if any)
+    /// → delete file on disk → didDeleteFiles.
+    #[serde(default, rename = "deleteSteps")]
+    pub delete_steps: Vec<DeleteStep>,
+}
+
+/// Content change style for `textDocument/didChange` snapshots.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    #[default]
+    Full,
+    Incremental,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default = "default_project")]
+    pub project: String,
+    #[serde(default)]
+    pub file: String,
+    #[serde(default = "default_line")]
+    pub line: u32,
+    #[serde(default = "default_col")]
+    pub col: u32,
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+    #[serde(default = "default_warmup")]
+    pub warmup: usize,
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default = "default_index_timeout")]
+    pub index_timeout: u64,
+    #[serde(default = "default_output")]
+    pub output: String,
+    #[serde(default)]
+    pub benchmarks: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Output path for the generated report. Omit to skip report generation.
+    #[serde(default)]
+    pub report: Option<String>,
+    /// Report style (deprecated — only "competition" format is supported).
+    #[serde(default = "default_report_style")]
+    pub report_style: String,
+    #[serde(
+        default = "default_response_limit",
+        deserialize_with = "deserialize_response_limit",
+        rename = "response"
+    )]
+    pub response_limit: usize,
+    /// Deprecated: use methods.textDocument/completion.trigger instead.
+    #[serde(default)]
+    pub trigger_character: Option<String>,
+    /// Per-method position and trigger overrides.
+    #[serde(default)]
+    pub methods: HashMap<String, MethodConfig>,
+    /// Servers to benchmark. Accepts inline definitions (objects with label/cmd)
+    /// or string references resolved against the server registry ("mmsaki",
+    /// "solc", "mmsaki@v0.1.20"). Defaults to ["mmsaki"] if omitted.
+    #[serde(
+        default = "default_servers",
+        deserialize_with = "deserialize_servers_opt"
+    )]
+    pub servers: Vec<ServerConfig>,
+    /// Path to a servers.yaml registry file. Auto-discovered next to the config
+    /// file if not specified.
+    #[serde(default, rename = "servers_file")]
+    pub servers_file: Option<String>,
+    /// Sub-configs to run sequentially. The parent's settings are merged as
+    /// defaults into each sub-config (sub-config values win).
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Settings sent as `initializationOptions` in the LSP `initialize` request.
+    /// Mirrors the editor's `settings` block (e.g. lint.exclude, inlayHints).
+    #[serde(default, rename = "initializeSettings")]
+    pub initialize_settings: Option<Value>,
+    /// Write a JSONL wire trace of every message per server × benchmark to
+    /// `<output>/traces/`. Same as `--trace`.
+    #[serde(default)]
+    pub trace: bool,
+    /// Run benchmarks in a throwaway clone of the project, one per server
+    /// run: `auto` (mutating benchmarks only), `always` or `never`.
+    #[serde(default)]
+    pub sandbox: SandboxMode,
+    /// How sandboxes are cloned: `copy` or `hardlink`.
+    #[serde(default)]
+    pub sandbox_clone: CloneMode,
+    /// Milliseconds between background samples of each server's RSS, CPU,
+    /// threads and open files. 0 turns the sampler off.
+    #[serde(default = "default_sample_interval_ms")]
+    pub sample_interval_ms: u64,
+    /// Warn when a server's coefficient of variation on a benchmark exceeds
+    /// this. 0 turns the warning off.
+    #[serde(default = "default_cv_threshold")]
+    pub cv_threshold: f64,
+    /// Keep a copy of every completed run in `<output>/history/`, for
+    /// `gen-report --trend`.
+    #[serde(default = "default_history")]
+    pub history: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerConfig {
+    pub label: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub link: String,
+    pub cmd: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Git ref (branch, tag, or SHA) to checkout and build from.
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Path to the git repo to build from. Required when `commit` is set.
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Settings returned to server-initiated `workspace/configuration`
+    /// requests, keyed by section (e.g. `solidity`). Dotted sections like
+    /// `solidity.linter` are resolved by walking nested maps.
+    #[serde(default)]
+    pub workspace_configuration: Option<Value>,
+    /// Wire trace file for the current benchmark run. Set by `run_bench`
+    /// when tracing is enabled; not read from config.
+    #[serde(skip)]
+    pub trace: Option<PathBuf>,
+}
+
+fn default_project() -> String {
+    ".".to_string()
+}
+fn default_line() -> u32 {
+    102
+}
+fn default_col() -> u32 {
+    15
+}
+fn default_iterations() -> usize {
+    10
+}
+fn default_warmup() -> usize {
+    2
+}
+fn default_timeout() -> u64 {
+    10
+}
+fn default_index_timeout() -> u64 {
+    15
+}
+fn default_sample_interval_ms() -> u64 {
+    250
+}
+fn default_cv_threshold() -> f64 {
+    0.2
+}
+fn default_history() -> bool {
+    true
+}
+fn default_output() -> String {
+    "benchmarks".to_string()
+}
+fn default_report_style() -> String {
+    "delta".to_string()
+}
+fn default_response_limit() -> usize {
+    80
+}
+fn default_servers() -> Vec<ServerConfig> {
+    vec![ServerConfig {
+        label: "mmsaki".to_string(),
+        cmd: "solidity-language-server".to_string(),
+        args: Vec::new(),
+        link: "https://github.com/mmsaki/solidity-language-server".to_string(),
+        description: String::new(),
+        commit: None,
+        repo: None,
+        workspace_configuration: None,
+        trace: None,
+    }]
+}
+
+/// Deserialize `servers` field: accepts an array of objects (inline ServerConfig)
+/// or strings (registry references like "mmsaki", "solc", "mmsaki@v0.1.20").
+/// String entries are stored with label=string, cmd="" as placeholders —
+/// they get resolved against the server registry after loading.
+/// Returns default servers if the field is missing or null.
+fn deserialize_servers_opt<'de, D>(deserializer: D) -> Result<Vec<ServerConfig>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let val: serde_yaml::Value = serde::Deserialize::deserialize(deserializer)?;
+    if val.is_null() {
+        return Ok(default_servers());
+    }
+    let items = match val.as_sequence() {
+        Some(s) => s,
+        None => {
+            return Err(serde::de::Error::custom(
+                "servers must be an array of strings or objects",
+            ))
+        }
+    };
+    let mut servers = Vec::new();
+    for item in items {
+        match item {
+            serde_yaml::Value::String(name) => {
+                // String reference — placeholder, resolved later against registry
+                servers.push(ServerConfig {
+                    label: name.clone(),
+                    cmd: String::new(), // empty = needs registry resolution
+                    args: Vec::new(),
+                    link: String::new(),
+                    description: String::new(),
+                    commit: None,
+                    repo: None,
+                    workspace_configuration: None,
+                    trace: None,
+                });
+            }
+            serde_yaml::Value::Mapping(_) => {
+                // Inline object — deserialize as ServerConfig directly
+                let cfg: ServerConfig =
+                    serde_yaml::from_value(item.clone()).map_err(serde::de::Error::custom)?;
+                servers.push(cfg);
+            }
+            _ => {
+                return Err(serde::de::Error::custom(
+                    "servers entries must be strings or objects",
+                ));
+            }
+        }
+    }
+    Ok(servers)
+}
+
+/// Resolve string-reference servers against the registry.
+/// Servers with an empty `cmd` are looked up in the registry.
+/// Servers with a non-empty `cmd` (inline definitions) are left as-is.
+pub fn resolve_servers(servers: &mut [ServerConfig], registry: &ServerRegistry) {
+    for srv in servers.iter_mut() {
+        if srv.cmd.is_empty() {
+            *srv = resolve_server(&srv.label, registry);
+        }
+    }
+}
+
+/// Deserialize `response` field: accepts "full" or a number.
+/// - "full" → 0 (no limit)
+/// - number → truncate to that many chars
+/// - omitted/null → 80 (default)
+fn deserialize_response_limit<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let val: serde_yaml::Value = serde::Deserialize::deserialize(deserializer)?;
+    match &val {
+        serde_yaml::Value::String(s) if s == "full" => Ok(0),
+        serde_yaml::Value::String(_) => Err(serde::de::Error::custom(
+            "response must be \"full\" or a number",
+        )),
+        serde_yaml::Value::Number(n) => {
+            if let Some(v) = n.as_u64() {
+                Ok(v as usize)
+            } else {
+                Err(serde::de::Error::custom(
+                    "response must be \"full\" or a positive number",
+                ))
+            }
+        }
+        serde_yaml::Value::Null => Ok(80),
+        _ => Err(serde::de::Error::custom(
+            "response must be \"full\" or a number",
+        )),
+    }
+}
+
+pub fn load_config(path: &str) -> Config {
+    let content = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Error reading config {}: {}", path, e);
+        std::process::exit(1);
+    });
+    serde_yaml::from_str(&content).unwrap_or_else(|e| {
+        eprintln!("Error parsing config {}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+/// Check if a config has `include` entries (either via raw YAML or parsed Config).
+/// Returns Some((resolved paths, parent defaults YAML)) if found, None otherwise.
+/// Parent defaults are all keys in the parent config except `include`.
+pub fn check_include(path: &str) -> Option<(Vec<String>, serde_yaml::Value)> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let raw: serde_yaml::Value = serde_yaml::from_str(&content).ok()?;
+    let items = raw.get("include")?.as_sequence()?;
+    if items.is_empty() {
+        return None;
+    }
+    let parent_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let paths: Vec<String> = items
+        .iter()
+        .filter_map(|v| {
+            v.as_str()
+                .map(|s| parent_dir.join(s).to_string_lossy().to_string())
+        })
+        .collect();
+    // Build defaults: everything in the parent except `include`
+    let mut defaults = raw.clone();
+    if let serde_yaml::Value::Mapping(ref mut m) = defaults {
+        m.remove(serde_yaml::Value::String("include".to_string()));
+    }
+    Some((paths, defaults))
+}
+
+/// Merge parent defaults with a sub-config. Sub-config keys win.
+/// Only top-level keys are merged (no deep merge).
+pub fn merge_configs(defaults: &serde_yaml::Value, child_path: &str) -> Option<serde_yaml::Value> {
+    let content = std::fs::read_to_string(child_path).ok()?;
+    let child: serde_yaml::Value = serde_yaml::from_str(&content).ok()?;
+    let mut merged = defaults.clone();
+    if let (serde_yaml::Value::Mapping(ref mut base), serde_yaml::Value::Mapping(ref overrides)) =
+        (&mut merged, &child)
+    {
+        for (k, v) in overrides {
+            base.insert(k.clone(), v.clone());
+        }
+    }
+    Some(merged)
+}
+
+pub fn timestamp() -> String {
+    let output = Command::new("date")
+        .args(["-u", "+%Y-%m-%dT%H:%M:%SZ"])
+        .output()
+        .ok();
+    output
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn date_stamp() -> String {
+    let output = Command::new("date").args(["+%Y-%m-%d"]).output().ok();
+    output
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,340 @@
+//! `expect` checks run by `--verify`.
+
+use crate::config::{CompletionItemExpect, ExpectConfig};
+use crate::position::{units_to_char_col, PositionEncoding};
+use console::style;
+use serde_json::Value;
+
+/// Check whether an LSP response matches the expected result.
+/// Returns Ok(()) on match, Err(message) on mismatch.
+pub fn check_expectation(
+    resp: &Value,
+    expect: &ExpectConfig,
+    encoding: PositionEncoding,
+) -> Result<(), String> {
+    // Extract result from response envelope
+    let result = resp
+        .get("result")
+        .or_else(|| resp.get("params"))
+        .unwrap_or(resp);
+
+    // Handle array responses (e.g. textDocument/definition can return Location[])
+    let location = if let Some(arr) = result.as_array() {
+        if arr.is_empty() {
+            // e.g. no diagnostics left after an edit fixes the error
+            if expect.count == Some(0) {
+                return Ok(());
+            }
+            return Err("response is empty array".to_string());
+        }
+        &arr[0]
+    } else if result.is_object() {
+        result
+    } else if result.is_null() {
+        return Err("response is null".to_string());
+    } else {
+        result
+    };
+
+    // Check file (URI ends with expected suffix)
+    if let Some(ref expected_file) = expect.file {
+        let uri = location
+            .get("targetUri")
+            .or_else(|| location.get("uri"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        if !uri.ends_with(expected_file) {
+            return Err(format!(
+                "file: expected \"{}\" but got \"{}\"",
+                expected_file,
+                uri.rsplit('/').next().unwrap_or(uri)
+            ));
+        }
+    }
+
+    // Check line
+    if let Some(expected_line) = expect.line {
+        // Try targetRange first (definitionLink), then range (Location)
+        let range = location
+            .get("targetRange")
+            .or_else(|| location.get("range"));
+        let actual_line = range
+            .and_then(|r| r.get("start"))
+            .and_then(|s| s.get("line"))
+            .and_then(|l| l.as_u64())
+            .map(|l| l as u32)
+            // prepareRename commonly returns { start, end } directly
+            .or_else(|| {
+                location
+                    .get("start")
+                    .and_then(|s| s.get("line"))
+                    .and_then(|l| l.as_u64())
+                    .map(|l| l as u32)
+            });
+        match actual_line {
+            Some(line) if line == expected_line => {}
+            Some(line) => {
+                return Err(format!("line: expected {} but got {}", expected_line, line));
+            }
+            None => {
+                return Err(format!(
+                    "line: expected {} but response has no range",
+                    expected_line
+                ));
+            }
+        }
+    }
+
+    // Check column
+    if let Some(expected_col) = expect.col {
+        let start = location
+            .get("targetRange")
+            .or_else(|| location.get("range"))
+            .and_then(|r| r.get("start"))
+            .or_else(|| location.get("start"));
+        let line = start.and_then(|s| s.get("line")).and_then(|l| l.as_u64());
+        let character = start
+            .and_then(|s| s.get("character"))
+            .and_then(|c| c.as_u64());
+        match (line, character) {
+            (Some(line), Some(character)) => {
+                // Without a readable target file the raw column is compared.
+                let text = location
+                    .get("targetUri")
+                    .or_else(|| location.get("uri"))
+                    .and_then(|v| v.as_str())
+                    .and_then(|u| u.strip_prefix("file://"))
+                    .and_then(|p| std::fs::read_to_string(p).ok());
+                let actual_col = match text {
+                    Some(text) => units_to_char_col(&text, line as u32, character as u32, encoding),
+                    None => character as u32,
+                };
+                if actual_col != expected_col {
+                    return Err(format!(
+                        "col: expected {} but got {}",
+                        expected_col, actual_col
+                    ));
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "col: expected {} but response has no range",
+                    expected_col
+                ));
+            }
+        }
+    }
+
+    // Check exact count (array responses)
+    if let Some(expected_count) = expect.count {
+        let actual_count = result.as_array().map(|a| a.len()).unwrap_or(0);
+        if actual_count != expected_count {
+            return Err(format!(
+                "count: expected {} but got {}",
+                expected_count, actual_count
+            ));
+        }
+    }
+
+    // Check minimum count (array responses)
+    if let Some(min) = expect.min_count {
+        let actual_count = result.as_array().map(|a| a.len()).unwrap_or(0);
+        if actual_count < min {
+            return Err(format!(
+                "minCount: expected >= {} but got {}",
+                min, actual_count
+            ));
+        }
+    }
+
+    // Check success field (workspace/executeCommand)
+    if let Some(expected_success) = expect.success {
+        let actual_success = result
+            .get("success")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if actual_success != expected_success {
+            return Err(format!(
+                "success: expected {} but got {}",
+                expected_success, actual_success
+            ));
+        }
+    }
+
+    // Check completion item predicates
+    if !expect.contains_items.is_empty() || !expect.absent_items.is_empty() {
+        let completion_items: Vec<&Value> = result
+            .get("items")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().collect())
+            .or_else(|| result.as_array().map(|a| a.iter().collect()))
+            .unwrap_or_default();
+
+        if completion_items.is_empty() {
+            return Err("containsItems/absentItems: response has no completion items".to_string());
+        }
+
+        for item_expect in &expect.contains_items {
+            if !completion_items
+                .iter()
+                .any(|item| completion_item_matches(item, item_expect))
+            {
+                return Err(format!(
+                    "containsItems: no completion item matched {}",
+                    completion_item_expect_to_string(item_expect)
+                ));
+            }
+        }
+
+        for item_expect in &expect.absent_items {
+            if completion_items
+                .iter()
+                .any(|item| completion_item_matches(item, item_expect))
+            {
+                return Err(format!(
+                    "absentItems: found unexpected completion item matching {}",
+                    completion_item_expect_to_string(item_expect)
+                ));
+            }
+        }
+    }
+
+    // Check code action title (textDocument/codeAction)
+    if let Some(ref expected_title) = expect.title_contains {
+        let actions = result.as_array().cloned().unwrap_or_default();
+        let found = actions.iter().any(|action| {
+            // Each element is either a CodeAction or a Command.
+            action
+                .get("title")
+                .and_then(|t| t.as_str())
+                .map(|t| t.contains(expected_title.as_str()))
+                .unwrap_or(false)
+        });
+        if !found {
+            return Err(format!(
+                "titleContains: no action with title containing \"{}\"",
+                expected_title
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn completion_item_matches(item: &Value, expect: &CompletionItemExpect) -> bool {
+    if let Some(ref label) = expect.label {
+        if item.get("label").and_then(|v| v.as_str()) != Some(label.as_str()) {
+            return false;
+        }
+    }
+
+    if let Some(ref detail_contains) = expect.detail_contains {
+        let detail = item.get("detail").and_then(|v| v.as_str()).unwrap_or("");
+        if !detail.contains(detail_contains) {
+            return false;
+        }
+    }
+
+    if let Some(ref prefix) = expect.sort_text_prefix {
+        let sort_text = item.get("sortText").and_then(|v| v.as_str()).unwrap_or("");
+        if !sort_text.starts_with(prefix) {
+            return false;
+        }
+    }
+
+    if let Some(expected_has_edits) = expect.has_additional_text_edits {
+        let has_edits = item
+            .get("additionalTextEdits")
+            .or_else(|| item.get("additional_text_edits"))
+            .map(|v| !v.is_null())
+            .unwrap_or(false);
+        if has_edits != expected_has_edits {
+            return false;
+        }
+    }
+
+    if let Some(ref needle) = expect.additional_text_edits_contain {
+        let edits = item
+            .get("additionalTextEdits")
+            .or_else(|| item.get("additional_text_edits"))
+            .and_then(|v| v.as_array());
+        let found = edits.is_some_and(|arr| {
+            arr.iter().any(|e| {
+                e.get("newText")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|s| s.contains(needle))
+            })
+        });
+        if !found {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn completion_item_expect_to_string(expect: &CompletionItemExpect) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(v) = &expect.label {
+        parts.push(format!("label={v}"));
+    }
+    if let Some(v) = &expect.detail_contains {
+        parts.push(format!("detailContains={v}"));
+    }
+    if let Some(v) = &expect.sort_text_prefix {
+        parts.push(format!("sortTextPrefix={v}"));
+    }
+    if let Some(v) = expect.has_additional_text_edits {
+        parts.push(format!("hasAdditionalTextEdits={v}"));
+    }
+    if let Some(v) = &expect.additional_text_edits_contain {
+        parts.push(format!("additionalTextEditsContain={v}"));
+    }
+    if parts.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{{}}}", parts.join(", "))
+    }
+}
+
+/// Result of verifying expectations across a benchmark run.
+#[derive(Default)]
+pub struct VerifyTally {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize, // no expect field
+}
+
+impl VerifyTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check `resp` against `expect` (counting a missing expectation as
+    /// skipped) and print a pass/fail line labelled `what`.
+    pub fn check(
+        &mut self,
+        resp: &Value,
+        expect: Option<&ExpectConfig>,
+        encoding: PositionEncoding,
+        what: &str,
+    ) {
+        let expect = match expect {
+            Some(exp) => exp,
+            None => {
+                self.skipped += 1;
+                return;
+            }
+        };
+        match check_expectation(resp, expect, encoding) {
+            Ok(()) => {
+                self.passed += 1;
+                eprintln!("  {} {}", style("✓").green().bold(), what);
+            }
+            Err(msg) => {
+                self.failed += 1;
+                eprintln!("  {} {} — {}", style("✗").red().bold(), what, msg);
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Server discovery, version detection, capability probing and response
+//! helpers shared by the runners.
+
+use crate::capabilities;
+use crate::client::LspClient;
+use crate::config::ServerConfig;
+use console::style;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+pub fn uri(p: &Path) -> String {
+    // canonicalize resolves symlinks and produces an absolute path.
+    // If it fails (file doesn't exist yet, e.g. rename target), fall back
+    // to making the path absolute via current_dir + join.
+    let abs = std::fs::canonicalize(p).unwrap_or_else(|_| {
+        if p.is_absolute() {
+            p.to_path_buf()
+        } else {
+            std::env::current_dir().unwrap_or_default().join(p)
+        }
+    });
+    format!("file://{}", abs.display())
+}
+
+pub fn available(cmd: &str) -> bool {
+    // Absolute path — just check file exists and is executable
+    if cmd.starts_with('/') {
+        return Path::new(cmd).exists();
+    }
+    Command::new("which")
+        .arg(cmd)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn resolve_binary(cmd: &str) -> Option<String> {
+    let which_out = Command::new("which")
+        .arg(cmd)
+        .stdout(Stdio::piped())
+        .output()
+        .ok()?;
+    let bin_path = String::from_utf8_lossy(&which_out.stdout)
+        .trim()
+        .to_string();
+    if bin_path.is_empty() {
+        return None;
+    }
+    std::fs::canonicalize(&bin_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .ok()
+        .or(Some(bin_path))
+}
+
+/// Checkout a git ref in the given repo and `cargo build --release`.
+/// Returns the absolute path to the built binary.
+pub fn build_from_commit(repo_path: &str, commit: &str, bin_name: &str) -> Result<String, String> {
+    let repo = PathBuf::from(repo_path);
+    if !repo.exists() {
+        return Err(format!("repo directory not found: {}", repo_path));
+    }
+
+    // Save current HEAD so we can restore later
+    let head_out = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(&repo)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("git rev-parse failed: {}", e))?;
+    let original_ref = String::from_utf8_lossy(&head_out.stdout).trim().to_string();
+    // If detached, save the SHA instead
+    let original_ref = if original_ref == "HEAD" {
+        let sha_out = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&repo)
+            .stdout(Stdio::piped())
+            .output()
+            .map_err(|e| format!("git rev-parse HEAD failed: {}", e))?;
+        String::from_utf8_lossy(&sha_out.stdout).trim().to_string()
+    } else {
+        original_ref
+    };
+
+    eprintln!(
+        "  {} checkout {} in {}",
+        style("build").cyan(),
+        style(commit).bold(),
+        repo_path
+    );
+
+    // Checkout the requested ref
+    let checkout = Command::new("git")
+        .args(["checkout", commit])
+        .current_dir(&repo)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .status()
+        .map_err(|e| format!("git checkout failed: {}", e))?;
+    if !checkout.success() {
+        return Err(format!("git checkout {} failed", commit));
+    }
+
+    // Build
+    eprintln!("  {} cargo build --release", style("build").cyan());
+    let build = Command::new("cargo")
+        .args(["build", "--release"])
+        .current_dir(&repo)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .status()
+        .map_err(|e| format!("cargo build failed: {}", e))?;
+    if !build.success() {
+        // Restore original ref before returning error
+        let _ = Command::new("git")
+            .args(["checkout", &original_ref])
+            .current_dir(&repo)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        return Err(format!("cargo build --release failed for {}", commit));
+    }
+
+    // Restore original ref
+    let _ = Command::new("git")
+        .args(["checkout", &original_ref])
+        .current_dir(&repo)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    let binary = repo.join("target/release").join(bin_name);
+    if !binary.exists() {
+        return Err(format!("built binary not found: {}", binary.display()));
+    }
+    Ok(binary.to_string_lossy().to_string())
+}
+
+pub fn detect_version(cmd: &str) -> String {
+    if cmd == "solc" {
+        if let Ok(output) = Command::new("solc")
+            .arg("--version")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+        {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                if line.starts_with("Version:") {
+                    return line.trim_start_matches("Version:").trim().to_string();
+                }
+            }
+        }
+    }
+    if let Ok(output) = Command::new(cmd)
+        .arg("--version")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+    {
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let line = stdout.lines().next().unwrap_or("").trim().to_string();
+            if !line.is_empty() {
+                return line;
+            }
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let line = stderr.lines().next().unwrap_or("").trim().to_string();
+            if !line.is_empty() {
+                return line;
+            }
+        }
+    }
+    if let Some(real_path) = resolve_binary(cmd) {
+        let mut dir = Path::new(&real_path).to_path_buf();
+        for _ in 0..10 {
+            dir = match dir.parent() {
+                Some(p) => p.to_path_buf(),
+                None => break,
+            };
+            let pkg = dir.join("package.json");
+            if pkg.exists() {
+                if let Ok(content) = std::fs::read_to_string(&pkg) {
+                    if let Ok(v) = serde_json::from_str::<Value>(&content) {
+                        if let Some(ver) = v.get("version").and_then(|v| v.as_str()) {
+                            let name = v.get("name").and_then(|n| n.as_str()).unwrap_or(cmd);
+                            return format!("{} {}", name, ver);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if let Ok(output) = Command::new("npm")
+        .args(["info", cmd, "version"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+    {
+        if output.status.success() {
+            let ver = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !ver.is_empty() {
+                return format!("{} {}", cmd, ver);
+            }
+        }
+    }
+    "unknown".to_string()
+}
+
+/// What a server told us during the planning probe.
+pub struct ServerProbe {
+    /// `capabilities` from the server's `InitializeResult`.
+    pub capabilities: Value,
+    /// `workspace/configuration` requests answered during the probe.
+    pub config_exchanges: Vec<Value>,
+    /// `client/registerCapability` registrations still active after the probe.
+    pub registrations: Vec<Value>,
+}
+
+/// Spawn a server once, run the `initialize` handshake and record its
+/// `capabilities` plus any configuration it asks for right after
+/// `initialized`. Used to plan which benchmarks each server can answer.
+pub fn probe_server(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> Result<ServerProbe, String> {
+    let mut c = LspClient::spawn_server(srv, cwd, verbose)?;
+    let result = c.initialize(root, init_settings)?;
+    let mut capabilities = match result.get("capabilities") {
+        Some(caps) if caps.is_object() => caps.clone(),
+        _ => return Err("InitializeResult has no capabilities".into()),
+    };
+    c.drain(Duration::from_millis(500));
+    let config_exchanges = std::mem::take(&mut c.config_exchanges);
+    let registrations = std::mem::take(&mut c.registrations);
+    merge_registrations(&mut capabilities, &registrations);
+    c.kill();
+    Ok(ServerProbe {
+        capabilities,
+        config_exchanges,
+        registrations,
+    })
+}
+
+/// Fold dynamic registrations into a static capabilities object, so a
+/// provider registered via `client/registerCapability` counts as advertised.
+/// The registration's `registerOptions` become the provider's value.
+pub(crate) fn merge_registrations(caps: &mut Value, registrations: &[Value]) {
+    for reg in registrations {
+        let method = match reg.get("method").and_then(|m| m.as_str()) {
+            Some(m) => m,
+            None => continue,
+        };
+        // Semantic tokens register once for all three request variants.
+        let path = match method {
+            "textDocument/semanticTokens" => "semanticTokensProvider",
+            m => match capabilities::provider_for(m) {
+                Some(p) => p,
+                None => continue,
+            },
+        };
+        let options = match reg.get("registerOptions") {
+            Some(opts) if opts.is_object() => opts.clone(),
+            _ => json!(true),
+        };
+        let keys: Vec<&str> = path.split('.').collect();
+        let mut cur = &mut *caps;
+        for key in &keys[..keys.len() - 1] {
+            if !cur.get(*key).is_some_and(|v| v.is_object()) {
+                cur[*key] = json!({});
+            }
+            cur = &mut cur[*key];
+        }
+        cur[keys[keys.len() - 1]] = options;
+    }
+}
+
+pub fn is_valid_response_for_method(_method: &str, resp: &Value) -> bool {
+    // Only reject JSON-RPC errors. Any other response — null, empty array,
+    // empty completion list, object — is a legitimate server reply.
+    // Correctness is checked separately via explicit `expect` blocks with --verify.
+    resp.get("error").is_none() && resp.get("result").is_some()
+}
+
+/// Whether `msg` is a `textDocument/publishDiagnostics` for `file_uri`.
+pub fn is_diagnostics_for(msg: &Value, file_uri: &str) -> bool {
+    msg.get("method").and_then(|m| m.as_str()) == Some("textDocument/publishDiagnostics")
+        && msg
+            .get("params")
+            .and_then(|p| p.get("uri"))
+            .and_then(|u| u.as_str())
+            == Some(file_uri)
+}
+
+pub fn is_valid_response(resp: &Value) -> bool {
+    is_valid_response_for_method("", resp)
+}
+
+pub fn response_summary(resp: &Value, _max_chars: usize) -> Value {
+    if let Some(err) = resp.get("error") {
+        json!({
+            "error": err.get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown")
+        })
+    } else if let Some(r) = resp.get("result").or_else(|| resp.get("params")) {
+        r.clone()
+    } else {
+        Value::Null
+    }
+}
@@ -0,0 +1,165 @@
+//! Append-only store of past runs, for `gen-report --trend`.
+//!
+//! After every completed run, `results.json` is copied to
+//! `<output>/history/<timestamp>.json` and a line naming the file and each
+//! server's version and commit is appended to `history/index.jsonl`.
+//! Nothing in the directory is ever rewritten, so it can be kept across
+//! runs (or synced somewhere) without merging.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the history directory inside the output directory.
+pub const HISTORY_DIR: &str = "history";
+
+/// File name of the index inside the history directory.
+pub const INDEX_FILE: &str = "index.jsonl";
+
+/// One line of `index.jsonl`: a stored run.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IndexEntry {
+    /// `timestamp` of the run's `results.json`.
+    pub timestamp: String,
+    /// Results file, relative to the history directory.
+    pub file: String,
+    /// Version and commit of each server in the run, by label.
+    pub servers: BTreeMap<String, ServerKey>,
+}
+
+/// What a server's results were measured against.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerKey {
+    pub version: String,
+    /// Git ref the server was built from, for servers with `commit` set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl IndexEntry {
+    /// Whether `server` ran at `version_or_commit` in this run: its full
+    /// version string, the bare version number in it (`0.1.24` for
+    /// `solidity-language-server 0.1.24+commit.abc`), or a prefix of the
+    /// commit it was built from.
+    pub fn matches(&self, server: &str, version_or_commit: &str) -> bool {
+        if version_or_commit.is_empty() {
+            return false;
+        }
+        self.servers.get(server).is_some_and(|key| {
+            let token = key.version.split_whitespace().last().unwrap_or("");
+            key.version == version_or_commit
+                || token.split('+').next() == Some(version_or_commit)
+                || key
+                    .commit
+                    .as_deref()
+                    .is_some_and(|c| c.starts_with(version_or_commit))
+        })
+    }
+}
+
+/// Copy the run at `results_path` into the history directory `dir` and
+/// index it. Returns the stored file's path.
+pub fn record(dir: &Path, results_path: &str) -> Result<PathBuf, String> {
+    let text =
+        std::fs::read_to_string(results_path).map_err(|e| format!("{}: {}", results_path, e))?;
+    let data: Value =
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", results_path, e))?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    let timestamp = data
+        .get("timestamp")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    // Colons aren't allowed in Windows file names. Runs finishing in the same
+    // second get a numeric suffix instead of replacing each other.
+    let stem = timestamp.replace(':', "-");
+    let (file, mut out) = (1..)
+        .map(|i| match i {
+            1 => format!("{}.json", stem),
+            _ => format!("{}-{}.json", stem, i),
+        })
+        .find_map(|name| {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(dir.join(&name))
+            {
+                Ok(f) => Some(Ok((name, f))),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(format!("{}: {}", dir.join(&name).display(), e))),
+            }
+        })
+        .unwrap()?;
+    out.write_all(text.as_bytes())
+        .map_err(|e| format!("{}: {}", dir.join(&file).display(), e))?;
+
+    let servers = data
+        .get("servers")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|s| {
+            let name = s.get("name")?.as_str()?;
+            let key = ServerKey {
+                version: s
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                commit: s.get("commit").and_then(|v| v.as_str()).map(String::from),
+            };
+            Some((name.to_string(), key))
+        })
+        .collect();
+    let entry = IndexEntry {
+        timestamp,
+        file: file.clone(),
+        servers,
+    };
+    let index_path = dir.join(INDEX_FILE);
+    let mut line = serde_json::to_string(&entry).unwrap();
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&index_path)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .map_err(|e| format!("{}: {}", index_path.display(), e))?;
+    Ok(dir.join(file))
+}
+
+/// The index of the history directory `dir`, oldest run first.
+pub fn load_index(dir: &Path) -> Result<Vec<IndexEntry>, String> {
+    let path = dir.join(INDEX_FILE);
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+/// The stored runs in `dir` whose index entry passes `keep`, with that
+/// entry, oldest first. Only those runs' files are read.
+pub fn load_runs(
+    dir: &Path,
+    keep: impl Fn(&IndexEntry) -> bool,
+) -> Result<Vec<(IndexEntry, Value)>, String> {
+    load_index(dir)?
+        .into_iter()
+        .filter(|entry| keep(entry))
+        .map(|entry| {
+            let path = dir.join(&entry.file);
+            let text =
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let data =
+                serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok((entry, data))
+        })
+        .collect()
+}
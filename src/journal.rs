@@ -0,0 +1,279 @@
+//! Journal of project file changes, so an interrupted run can be undone.
+//!
+//! Every rename, create, edit and delete lsp-bench makes inside the project
+//! is appended to `<output>/journal.jsonl` (and synced) *before* it happens.
+//! On SIGINT/SIGTERM the journal is replayed in reverse and the servers are
+//! shut down; after a hard kill, `lsp-bench restore` replays it instead.
+//! Files in a sandbox aren't journaled: the sandbox is thrown away anyway.
+
+use crate::client::shutdown_live_servers;
+use crate::sandbox::{remove_live_sandboxes, replace_file};
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// File name of the journal inside the output directory.
+pub const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// One change, recorded with what it takes to undo it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    /// `path` is about to be written or deleted. `original` is its content
+    /// beforehand, or null if the file didn't exist.
+    Write {
+        path: PathBuf,
+        original: Option<Contents>,
+    },
+    /// `from` is about to be renamed to `to`.
+    Rename { from: PathBuf, to: PathBuf },
+}
+
+/// File content: a string for text, an array of bytes otherwise.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Contents {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Contents {
+    fn read(path: &Path) -> std::io::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(match String::from_utf8(bytes) {
+                Ok(text) => Contents::Text(text),
+                Err(e) => Contents::Bytes(e.into_bytes()),
+            })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Contents::Text(text) => text.as_bytes(),
+            Contents::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+struct Journal {
+    path: PathBuf,
+    /// Canonical project root; only paths under it are recorded.
+    root: PathBuf,
+    /// Opened on the first entry, so runs that never touch the project
+    /// leave no file behind.
+    out: Option<std::fs::File>,
+}
+
+impl Journal {
+    /// `path` with its parent canonicalized, if it lies inside the project.
+    /// The file name itself is kept so symlinks are journaled as links.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let full = std::fs::canonicalize(parent).ok()?.join(path.file_name()?);
+        full.starts_with(&self.root).then_some(full)
+    }
+
+    fn record(&mut self, entry: &Entry) -> std::io::Result<()> {
+        if self.out.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            self.out = Some(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        let out = self.out.as_mut().unwrap();
+        let mut line = serde_json::to_string(entry).unwrap();
+        line.push('\n');
+        out.write_all(line.as_bytes())?;
+        out.sync_data()
+    }
+
+    fn record_write(&mut self, path: &Path) -> std::io::Result<()> {
+        if let Some(path) = self.resolve(path) {
+            let original = Contents::read(&path)?;
+            self.record(&Entry::Write { path, original })?;
+        }
+        Ok(())
+    }
+}
+
+/// The journal of the current run. Every change holds this lock from
+/// recording to applying it, so once the interrupt handler takes it no
+/// runner can touch the project again.
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+fn lock() -> MutexGuard<'static, Option<Journal>> {
+    JOURNAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start journaling changes under `project` to `path`. Fails if `path`
+/// still holds entries from an interrupted run.
+pub fn open(path: &Path, project: &Path) -> Result<(), String> {
+    if std::fs::metadata(path).is_ok_and(|m| m.len() > 0) {
+        return Err(format!(
+            "{} holds changes from an interrupted run; run `lsp-bench restore` first",
+            path.display()
+        ));
+    }
+    let root =
+        std::fs::canonicalize(project).map_err(|e| format!("{}: {}", project.display(), e))?;
+    *lock() = Some(Journal {
+        path: path.to_path_buf(),
+        root,
+        out: None,
+    });
+    Ok(())
+}
+
+/// Forget every entry so far: the runner that made them has restored the
+/// project.
+pub fn checkpoint() {
+    if let Some(journal) = lock().as_mut() {
+        if let Some(out) = journal.out.take() {
+            drop(out);
+            let _ = std::fs::remove_file(&journal.path);
+        }
+    }
+}
+
+/// Stop journaling and remove the journal file.
+pub fn close() {
+    if let Some(journal) = lock().take() {
+        if journal.out.is_some() {
+            let _ = std::fs::remove_file(&journal.path);
+        }
+    }
+}
+
+// ── Journaled file operations ───────────────────────────────────────────────
+
+/// [`replace_file`], journaled. Creates the file if it doesn't exist.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut journal = lock();
+    if let Some(journal) = journal.as_mut() {
+        journal.record_write(path)?;
+    }
+    replace_file(path, contents)
+}
+
+/// [`std::fs::rename`], journaled.
+pub fn rename(from: &Path, to: &Path) -> std::io::Result<()> {
+    let mut journal = lock();
+    if let Some(journal) = journal.as_mut() {
+        if let (Some(from), Some(to)) = (journal.resolve(from), journal.resolve(to)) {
+            // A file the rename replaces comes back after the rename is undone.
+            if to.exists() {
+                journal.record_write(&to)?;
+            }
+            journal.record(&Entry::Rename { from, to })?;
+        }
+    }
+    std::fs::rename(from, to)
+}
+
+/// [`std::fs::remove_file`], journaled.
+pub fn remove(path: &Path) -> std::io::Result<()> {
+    let mut journal = lock();
+    if let Some(journal) = journal.as_mut() {
+        journal.record_write(path)?;
+    }
+    std::fs::remove_file(path)
+}
+
+// ── Restore ─────────────────────────────────────────────────────────────────
+
+/// Undo every change in the journal at `path`, newest first, then remove
+/// it. Returns the number of entries replayed.
+pub fn restore(path: &Path) -> Result<usize, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Entry>(line) {
+            Ok(entry) => entries.push(entry),
+            // A kill mid-append leaves a torn last line; nothing was changed
+            // after it.
+            Err(_) if i + 1 == text.lines().count() => {}
+            Err(e) => return Err(format!("{}:{}: {}", path.display(), i + 1, e)),
+        }
+    }
+    for entry in entries.iter().rev() {
+        undo(entry)?;
+    }
+    std::fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(entries.len())
+}
+
+fn undo(entry: &Entry) -> Result<(), String> {
+    match entry {
+        Entry::Write {
+            path,
+            original: Some(original),
+        } => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            }
+            replace_file(path, original.as_bytes())
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        Entry::Write {
+            path,
+            original: None,
+        } => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("{}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        },
+        Entry::Rename { from, to } => {
+            // The runner may have moved it back before it was interrupted.
+            if from.exists() || !to.exists() {
+                return Ok(());
+            }
+            std::fs::rename(to, from).map_err(|e| format!("{}: {}", to.display(), e))
+        }
+    }
+}
+
+/// On SIGINT/SIGTERM: stop further changes, shut the servers down, remove
+/// sandboxes, undo the journal and exit with status 130.
+pub fn install_signal_handler() {
+    let _ = ctrlc::set_handler(|| {
+        let journal = lock();
+        eprintln!(
+            "\n  {} interrupted, shutting down servers",
+            style("stop").yellow().bold()
+        );
+        shutdown_live_servers(Duration::from_secs(5));
+        remove_live_sandboxes();
+        if let Some(journal) = journal.as_ref().filter(|j| j.out.is_some()) {
+            match restore(&journal.path) {
+                Ok(n) => eprintln!(
+                    "  {} undid {} project change(s)",
+                    style("restored").green(),
+                    n
+                ),
+                Err(e) => eprintln!(
+                    "  {} {} -- run `lsp-bench restore` to retry",
+                    style("restore failed").red(),
+                    e
+                ),
+            }
+        }
+        std::process::exit(130);
+    });
+}
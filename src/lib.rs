@@ -0,0 +1,34 @@
+//! Benchmark framework for Language Server Protocol (LSP) servers.
+//!
+//! The `lsp-bench` binary is a thin front-end over this crate. Embedders can
+//! drive servers directly with [`client::LspClient`], run individual
+//! benchmarks with the `bench_*` functions in [`runners`], or run a whole
+//! config with [`run::run`]. `gen-report` renders results with [`report`].
+
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod benchmark;
+pub mod capabilities;
+pub mod client;
+pub mod compare;
+pub mod config;
+pub mod expect;
+pub mod helpers;
+pub mod history;
+pub mod journal;
+pub mod memory;
+pub mod position;
+pub mod replay;
+pub mod report;
+pub mod results;
+pub mod run;
+pub mod runners;
+pub mod sandbox;
+pub mod stats;
+
+pub use benchmark::{BenchContext, Benchmark, Registry};
+pub use client::LspClient;
+pub use config::{Config, MethodConfig, ServerConfig};
+pub use expect::check_expectation;
+pub use results::{load_results, BenchResult, BenchRow, Results};
+pub use run::{run, run_with, RunOptions};
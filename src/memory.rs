@@ -0,0 +1,389 @@
+//! Process memory and CPU measurement.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, RefreshKind, System};
+
+/// Get the resident set size (RSS) of a process in kilobytes.
+/// Uses sysinfo first, then falls back to `ps` on macOS/Linux.
+/// Returns None if measurement fails.
+pub fn get_rss(pid: u32) -> Option<u64> {
+    // Prefer native process inspection via sysinfo (works better in restricted shells).
+    let target = Pid::from_u32(pid);
+    let mut sys = System::new_with_specifics(
+        RefreshKind::new().with_processes(ProcessRefreshKind::new().with_memory()),
+    );
+    sys.refresh_pids_specifics(&[target], ProcessRefreshKind::new().with_memory());
+    if let Some(proc_) = sys.process(target) {
+        // sysinfo reports bytes; convert to kilobytes to match report schema.
+        let mem_bytes = proc_.memory();
+        if mem_bytes > 0 {
+            return Some(mem_bytes / 1024);
+        }
+    }
+
+    // Fallback: shell out to ps where available.
+    let output = Command::new("ps")
+        .args(["-o", "rss=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let s = String::from_utf8_lossy(&output.stdout);
+    s.trim().parse::<u64>().ok()
+}
+
+// ── Process tree ────────────────────────────────────────────────────────────
+
+/// Memory and CPU of a server process, alone and together with every
+/// process it forked (Node workers, `solc` subprocesses, ...).
+///
+/// PSS and CPU time come from procfs and are only available on Linux
+/// (CPU time falls back to `ps` elsewhere).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Resident set size of the server process.
+    pub rss_kb: u64,
+    /// Proportional set size of the server process: shared pages are split
+    /// between the processes mapping them, so the tree total isn't inflated
+    /// by shared libraries.
+    pub pss_kb: Option<u64>,
+    /// User + system CPU time of the server process so far.
+    pub cpu_ms: Option<u64>,
+    /// RSS summed over the server and all its descendants.
+    pub tree_rss_kb: u64,
+    /// PSS summed over the server and all its descendants.
+    pub tree_pss_kb: Option<u64>,
+    /// CPU time of the server and all its descendants, including exited
+    /// children that have been waited for.
+    pub tree_cpu_ms: Option<u64>,
+    /// Number of processes in the tree, the server included.
+    pub tree_processes: usize,
+}
+
+impl Usage {
+    /// Field-wise maximum, for peaks across iterations.
+    pub fn max(self, other: Usage) -> Usage {
+        Usage {
+            rss_kb: self.rss_kb.max(other.rss_kb),
+            pss_kb: self.pss_kb.max(other.pss_kb),
+            cpu_ms: self.cpu_ms.max(other.cpu_ms),
+            tree_rss_kb: self.tree_rss_kb.max(other.tree_rss_kb),
+            tree_pss_kb: self.tree_pss_kb.max(other.tree_pss_kb),
+            tree_cpu_ms: self.tree_cpu_ms.max(other.tree_cpu_ms),
+            tree_processes: self.tree_processes.max(other.tree_processes),
+        }
+    }
+}
+
+/// Sample memory and CPU of `pid` and its descendant tree.
+/// Returns None if the process can't be found.
+pub fn sample_usage(pid: u32) -> Option<Usage> {
+    let root = Pid::from_u32(pid);
+    let mut sys = System::new_with_specifics(
+        RefreshKind::new().with_processes(ProcessRefreshKind::new().with_memory()),
+    );
+    sys.refresh_processes_specifics(ProcessRefreshKind::new().with_memory());
+
+    let tree = process_tree(&children_of(&sys), root);
+
+    let rss_kb = match sys.process(root) {
+        Some(p) if p.memory() > 0 => p.memory() / 1024,
+        _ => get_rss(pid)?,
+    };
+    let children_rss: u64 = tree[1..]
+        .iter()
+        .filter_map(|p| sys.process(*p))
+        .map(|p| p.memory() / 1024)
+        .sum();
+    let pss: Vec<Option<u64>> = tree.iter().map(|p| get_pss(p.as_u32())).collect();
+    let cpu: Vec<Option<(u64, u64)>> = tree.iter().map(|p| get_cpu_ms(p.as_u32())).collect();
+    Some(Usage {
+        rss_kb,
+        pss_kb: pss[0],
+        cpu_ms: cpu[0].map(|(own, _)| own),
+        tree_rss_kb: rss_kb + children_rss,
+        // Children that exit between listing and reading are left out.
+        tree_pss_kb: pss[0].map(|own| own + pss[1..].iter().flatten().sum::<u64>()),
+        tree_cpu_ms: cpu[0].map(|_| cpu.iter().flatten().map(|(own, reaped)| own + reaped).sum()),
+        tree_processes: tree.len(),
+    })
+}
+
+/// Child processes of every process. sysinfo only records parents, so this
+/// is built in one pass over the process list and then walked top-down.
+fn children_of(sys: &System) -> HashMap<Pid, Vec<Pid>> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, p) in sys.processes() {
+        if let (Some(parent), None) = (p.parent(), p.thread_kind()) {
+            children.entry(parent).or_default().push(*pid);
+        }
+    }
+    children
+}
+
+/// `root` followed by all its descendants.
+fn process_tree(children: &HashMap<Pid, Vec<Pid>>, root: Pid) -> Vec<Pid> {
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        tree.extend(children.get(&tree[i]).into_iter().flatten());
+        i += 1;
+    }
+    tree
+}
+
+/// Proportional set size in kilobytes, from `/proc/<pid>/smaps_rollup`.
+#[cfg(target_os = "linux")]
+fn get_pss(pid: u32) -> Option<u64> {
+    let rollup = std::fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    rollup
+        .lines()
+        .find_map(|l| l.strip_prefix("Pss:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_pss(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// CPU time in milliseconds as (own user + system, waited-for children's
+/// user + system), from `/proc/<pid>/stat`.
+#[cfg(target_os = "linux")]
+fn get_cpu_ms(pid: u32) -> Option<(u64, u64)> {
+    // Clock ticks per second (USER_HZ), 100 on every Linux ABI.
+    const TICKS_PER_SEC: u64 = 100;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces; fields resume after its ')'.
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    // utime, stime, cutime, cstime are fields 14-17 of stat, 12-15 here.
+    let tick = |i: usize| fields.get(i)?.parse::<u64>().ok();
+    let own = tick(11)? + tick(12)?;
+    let reaped = tick(13)? + tick(14)?;
+    Some((own * 1000 / TICKS_PER_SEC, reaped * 1000 / TICKS_PER_SEC))
+}
+
+/// CPU time in milliseconds from `ps -o time=` (`[[dd-]hh:]mm:ss[.ss]`).
+/// `ps` doesn't report waited-for children separately.
+#[cfg(not(target_os = "linux"))]
+fn get_cpu_ms(pid: u32) -> Option<(u64, u64)> {
+    let output = Command::new("ps")
+        .args(["-o", "time=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let s = String::from_utf8_lossy(&output.stdout);
+    let s = s.trim();
+    let (days, clock) = match s.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, s),
+    };
+    let secs = clock.split(':').try_fold(0.0, |acc, part| {
+        part.parse::<f64>().ok().map(|v| acc * 60.0 + v)
+    })?;
+    Some((((days * 86400.0 + secs) * 1000.0) as u64, 0))
+}
+
+/// Thread count, from `/proc/<pid>/status`.
+#[cfg(target_os = "linux")]
+fn get_threads(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("Threads:"))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_threads(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Open file descriptors, from `/proc/<pid>/fd`.
+#[cfg(target_os = "linux")]
+fn get_fds(pid: u32) -> Option<u64> {
+    Some(std::fs::read_dir(format!("/proc/{}/fd", pid)).ok()?.count() as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_fds(_pid: u32) -> Option<u64> {
+    None
+}
+
+// ── Sampler ─────────────────────────────────────────────────────────────────
+
+/// One sample of a server's process trees, summed.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Milliseconds since the sampler started.
+    pub t_ms: f64,
+    pub rss_kb: u64,
+    /// CPU time used since the previous sample, as a percentage of one core.
+    pub cpu_pct: Option<f64>,
+    pub threads: Option<u64>,
+    pub fds: Option<u64>,
+    pub processes: usize,
+}
+
+/// Samples one server config's processes in a background thread, from
+/// [`Sampler::start`] until [`Sampler::finish`]. Every live server spawned
+/// for that label is included, so a benchmark that spawns a fresh server per
+/// iteration gets one continuous timeline.
+pub struct Sampler {
+    interval: Duration,
+    stop: mpsc::Sender<()>,
+    handle: std::thread::JoinHandle<Vec<Sample>>,
+}
+
+impl Sampler {
+    pub fn start(interval: Duration, label: &str) -> Self {
+        let label = label.to_string();
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut sys = System::new();
+            let mut samples = Vec::new();
+            // CPU time per server at the previous sample.
+            let mut prev_cpu: HashMap<u32, u64> = HashMap::new();
+            let mut prev_t = start;
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let pids = crate::client::live_server_pids(&label);
+                if pids.is_empty() {
+                    continue;
+                }
+                sys.refresh_processes_specifics(ProcessRefreshKind::new().with_memory());
+                let now = Instant::now();
+                let mut sample = Sample {
+                    t_ms: (now - start).as_secs_f64() * 1000.0,
+                    rss_kb: 0,
+                    cpu_pct: None,
+                    threads: Some(0),
+                    fds: Some(0),
+                    processes: 0,
+                };
+                let children = children_of(&sys);
+                let mut cpu: HashMap<u32, u64> = HashMap::new();
+                for pid in pids {
+                    let tree = process_tree(&children, Pid::from_u32(pid));
+                    if sys.process(tree[0]).is_none() {
+                        continue;
+                    }
+                    for p in &tree {
+                        let id = p.as_u32();
+                        sample.rss_kb += sys.process(*p).map_or(0, |p| p.memory() / 1024);
+                        sample.threads = sample.threads.zip(get_threads(id)).map(|(a, b)| a + b);
+                        sample.fds = sample.fds.zip(get_fds(id)).map(|(a, b)| a + b);
+                        if let Some((own, reaped)) = get_cpu_ms(id) {
+                            *cpu.entry(pid).or_default() += own + reaped;
+                        }
+                    }
+                    sample.processes += tree.len();
+                }
+                if sample.processes == 0 {
+                    continue;
+                }
+                // A server first seen in this sample has no previous CPU
+                // time to diff against; counting all of it would show its
+                // startup as a spike.
+                let used: Vec<u64> = cpu
+                    .iter()
+                    .filter_map(|(pid, ms)| prev_cpu.get(pid).map(|prev| ms.saturating_sub(*prev)))
+                    .collect();
+                let wall_ms = (now - prev_t).as_secs_f64() * 1000.0;
+                sample.cpu_pct =
+                    (!used.is_empty()).then(|| used.iter().sum::<u64>() as f64 / wall_ms * 100.0);
+                prev_cpu = cpu;
+                prev_t = now;
+                samples.push(sample);
+            }
+            samples
+        });
+        Self {
+            interval,
+            stop,
+            handle,
+        }
+    }
+
+    /// Stop sampling and return the timeline.
+    pub fn finish(self) -> Timeline {
+        let _ = self.stop.send(());
+        Timeline {
+            interval: self.interval,
+            samples: self.handle.join().unwrap_or_default(),
+        }
+    }
+}
+
+/// Resource samples over one server's run of a benchmark.
+pub struct Timeline {
+    pub interval: Duration,
+    pub samples: Vec<Sample>,
+}
+
+impl Timeline {
+    /// `{interval_ms, peak, mean, final, samples}`. `peak`, `mean` and
+    /// `final` hold `rss_kb`, `cpu_pct`, `threads` and `fds`, each omitted
+    /// when the platform doesn't report it.
+    pub fn to_json(&self) -> Value {
+        let round = |v: f64| (v * 10.0).round() / 10.0;
+        let fields: [(&str, fn(&Sample) -> Option<f64>); 4] = [
+            ("rss_kb", |s| Some(s.rss_kb as f64)),
+            ("cpu_pct", |s| s.cpu_pct),
+            ("threads", |s| s.threads.map(|v| v as f64)),
+            ("fds", |s| s.fds.map(|v| v as f64)),
+        ];
+        let mut peak = json!({});
+        let mut mean = json!({});
+        let mut last = json!({});
+        for (name, get) in fields {
+            let values: Vec<f64> = self.samples.iter().filter_map(get).collect();
+            if values.is_empty() {
+                continue;
+            }
+            // Only CPU is fractional; the rest are counts and kilobytes.
+            let num = |v: f64| match name {
+                "cpu_pct" => json!(round(v)),
+                _ => json!(v.round() as u64),
+            };
+            peak[name] = num(values.iter().cloned().fold(f64::MIN, f64::max));
+            mean[name] = num(values.iter().sum::<f64>() / values.len() as f64);
+            last[name] = num(values[values.len() - 1]);
+        }
+        let samples: Vec<Value> = self
+            .samples
+            .iter()
+            .map(|s| {
+                let mut obj = json!({
+                    "t_ms": round(s.t_ms),
+                    "rss_kb": s.rss_kb,
+                    "processes": s.processes,
+                });
+                if let Some(cpu) = s.cpu_pct {
+                    obj["cpu_pct"] = json!(round(cpu));
+                }
+                if let Some(threads) = s.threads {
+                    obj["threads"] = json!(threads);
+                }
+                if let Some(fds) = s.fds {
+                    obj["fds"] = json!(fds);
+                }
+                obj
+            })
+            .collect();
+        json!({
+            "interval_ms": self.interval.as_millis() as u64,
+            "peak": peak,
+            "mean": mean,
+            "final": last,
+            "samples": samples,
+        })
+    }
+}
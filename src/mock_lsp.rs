@@ -0,0 +1,628 @@
+use lsp_bench::capabilities;
+
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(name = "mock-lsp", version = env!("LONG_VERSION"))]
+#[command(about = "Scripted stdio LSP server for testing lsp-bench")]
+struct Cli {
+    /// YAML script of canned responses, latencies and failures
+    #[arg(short, long)]
+    script: Option<String>,
+
+    /// Accepted for command-line compatibility with real servers; mock-lsp
+    /// always speaks stdio
+    #[arg(long)]
+    stdio: bool,
+
+    /// Run as one of the server's worker processes: idle until stdin closes
+    #[arg(long, hide = true)]
+    worker: bool,
+}
+
+// ── Script ──────────────────────────────────────────────────────────────────
+
+/// Behaviour of the mock server, loaded from `--script`. Every field is
+/// optional; an empty script answers every benchmarked method immediately.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Script {
+    /// Delay before every response, in milliseconds.
+    latency_ms: u64,
+    /// Extra random delay, uniform in `0..=jitter_ms`.
+    jitter_ms: u64,
+    /// Seed for the jitter RNG (default: time-based).
+    seed: Option<u64>,
+    /// `ServerCapabilities` to advertise. Defaults to every provider
+    /// lsp-bench benchmarks.
+    capabilities: Option<Value>,
+    /// `client/registerCapability` registrations (`{id, method,
+    /// registerOptions}`) sent after `initialized`. Their methods are
+    /// answered as if advertised.
+    registrations: Vec<Value>,
+    /// Simulated project indexing reported via `$/progress` after
+    /// `initialized`. On by default; `progress: null` turns it off.
+    #[serde(default = "default_progress")]
+    progress: Option<ProgressScript>,
+    /// Diagnostics published after every `didOpen` and `didChange`.
+    /// Defaults to one error on the first line.
+    diagnostics: Option<Vec<Value>>,
+    /// Leave `version` out of `publishDiagnostics`, as many servers do.
+    unversioned_diagnostics: bool,
+    /// Exit (without answering) on the Nth request, counting every request
+    /// including `initialize`.
+    crash_on_request: Option<u64>,
+    /// Exit code used when crashing.
+    exit_code: Option<i32>,
+    /// Worker processes to fork at startup, the way Node-based servers do.
+    /// They idle until the server exits.
+    workers: usize,
+    /// Per-method overrides.
+    methods: HashMap<String, MethodScript>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+struct MethodScript {
+    /// Canned `result`. `$uri` in any string is replaced with the request's
+    /// `textDocument.uri`. An explicit `null` answers `null`.
+    #[serde(deserialize_with = "present")]
+    result: Option<Value>,
+    /// Canned JSON-RPC `error` object (`{code, message}`), sent instead of a result.
+    error: Option<Value>,
+    /// Overrides the script-wide `latency_ms` for this method.
+    latency_ms: Option<u64>,
+    /// Overrides the script-wide `jitter_ms` for this method.
+    jitter_ms: Option<u64>,
+    /// Exit (without answering) on the Nth request of this method.
+    crash_on: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct ProgressScript {
+    token: String,
+    title: String,
+    /// Time between the `begin` and `end` notifications.
+    duration_ms: u64,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self {
+            latency_ms: 0,
+            jitter_ms: 0,
+            seed: None,
+            capabilities: None,
+            registrations: Vec::new(),
+            progress: default_progress(),
+            diagnostics: None,
+            unversioned_diagnostics: false,
+            crash_on_request: None,
+            exit_code: None,
+            workers: 0,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+fn default_progress() -> Option<ProgressScript> {
+    Some(ProgressScript::default())
+}
+
+impl Default for ProgressScript {
+    fn default() -> Self {
+        Self {
+            token: "mock/indexing".into(),
+            title: "Indexing".into(),
+            duration_ms: 50,
+        }
+    }
+}
+
+/// Keep an explicit `null` as `Some(Value::Null)` rather than `None`.
+fn present<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(d).map(Some)
+}
+
+// ── Transport ───────────────────────────────────────────────────────────────
+
+type Out = Arc<Mutex<std::io::Stdout>>;
+
+fn write(out: &Out, msg: &Value) {
+    let body = serde_json::to_string(msg).unwrap();
+    let mut w = out.lock().unwrap();
+    let _ = write!(w, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = w.flush();
+}
+
+/// Read one framed message. `None` on EOF or a malformed frame.
+fn read(reader: &mut impl BufRead) -> Option<Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(v) = line.strip_prefix("Content-Length:") {
+            content_length = v.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0u8; content_length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+// ── Responses ───────────────────────────────────────────────────────────────
+
+/// Methods the mock answers without a script entry.
+const METHODS: &[&str] = &[
+    "textDocument/definition",
+    "textDocument/declaration",
+    "textDocument/typeDefinition",
+    "textDocument/implementation",
+    "textDocument/hover",
+    "textDocument/references",
+    "textDocument/completion",
+    "textDocument/signatureHelp",
+    "textDocument/rename",
+    "textDocument/prepareRename",
+    "textDocument/documentSymbol",
+    "textDocument/documentHighlight",
+    "textDocument/documentLink",
+    "textDocument/formatting",
+    "textDocument/foldingRange",
+    "textDocument/selectionRange",
+    "textDocument/codeLens",
+    "textDocument/inlayHint",
+    "textDocument/semanticTokens/full",
+    "textDocument/semanticTokens/range",
+    "textDocument/semanticTokens/full/delta",
+    "textDocument/documentColor",
+    "textDocument/codeAction",
+    "textDocument/prepareCallHierarchy",
+    "callHierarchy/incomingCalls",
+    "callHierarchy/outgoingCalls",
+    "workspace/symbol",
+    "workspace/executeCommand",
+    "workspace/willRenameFiles",
+    "workspace/willCreateFiles",
+    "workspace/willDeleteFiles",
+];
+
+/// Advertise the provider for every method in [`METHODS`].
+fn default_capabilities() -> Value {
+    let mut caps = json!({
+        "textDocumentSync": { "openClose": true, "change": 2 },
+        "diagnosticProvider": { "interFileDependencies": false, "workspaceDiagnostics": false },
+    });
+    for method in METHODS {
+        let Some(path) = capabilities::provider_for(method) else {
+            continue;
+        };
+        let mut cur = &mut caps;
+        let keys: Vec<&str> = path.split('.').collect();
+        for key in &keys[..keys.len() - 1] {
+            if !cur.get(*key).is_some_and(|v| v.is_object()) {
+                cur[*key] = json!({});
+            }
+            cur = &mut cur[*key];
+        }
+        cur[keys[keys.len() - 1]] = json!(true);
+    }
+    caps["completionProvider"] = json!({ "triggerCharacters": [".", "("] });
+    caps["signatureHelpProvider"] = json!({ "triggerCharacters": ["(", ","] });
+    caps["executeCommandProvider"] = json!({ "commands": ["mock.command"] });
+    caps["semanticTokensProvider"]["legend"] = json!({
+        "tokenTypes": ["variable", "function"],
+        "tokenModifiers": [],
+    });
+    caps
+}
+
+fn default_diagnostics() -> Vec<Value> {
+    vec![json!({
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+        "severity": 1,
+        "source": "mock-lsp",
+        "message": "mock diagnostic",
+    })]
+}
+
+/// A plausible result for `method`, built around the request's document
+/// and position.
+fn default_result(method: &str, params: &Value, diagnostics: &[Value]) -> Value {
+    let uri = params["textDocument"]["uri"].clone();
+    let pos = params
+        .get("position")
+        .cloned()
+        .unwrap_or(json!({ "line": 0, "character": 0 }));
+    let mut end = pos.clone();
+    end["character"] = json!(pos["character"].as_u64().unwrap_or(0) + 1);
+    let range = json!({ "start": pos, "end": end });
+    let location = json!({ "uri": uri, "range": range });
+    let item = json!({
+        "name": "mock",
+        "kind": 12,
+        "uri": uri,
+        "range": range,
+        "selectionRange": range,
+    });
+    match method {
+        "textDocument/definition"
+        | "textDocument/declaration"
+        | "textDocument/typeDefinition"
+        | "textDocument/implementation" => location,
+        "textDocument/hover" => json!({
+            "contents": { "kind": "markdown", "value": "mock hover" },
+            "range": range,
+        }),
+        "textDocument/references" => json!([location]),
+        "textDocument/completion" => json!({
+            "isIncomplete": false,
+            "items": [{ "label": "mock", "kind": 3 }],
+        }),
+        "textDocument/signatureHelp" => json!({
+            "signatures": [{ "label": "mock(uint256 x)", "parameters": [{ "label": "uint256 x" }] }],
+            "activeSignature": 0,
+            "activeParameter": 0,
+        }),
+        "textDocument/rename" => {
+            let mut changes = serde_json::Map::new();
+            if let Some(uri) = uri.as_str() {
+                changes.insert(
+                    uri.to_string(),
+                    json!([{ "range": range, "newText": params["newName"] }]),
+                );
+            }
+            json!({ "changes": changes })
+        }
+        "textDocument/prepareRename" => range,
+        "textDocument/documentSymbol" => json!([{
+            "name": "Mock",
+            "kind": 5,
+            "range": range,
+            "selectionRange": range,
+        }]),
+        "textDocument/documentHighlight" => json!([{ "range": range, "kind": 1 }]),
+        "textDocument/foldingRange" => json!([{ "startLine": 0, "endLine": 1 }]),
+        "textDocument/selectionRange" => {
+            let n = params["positions"].as_array().map_or(1, |p| p.len());
+            json!(vec![json!({ "range": range }); n])
+        }
+        "textDocument/codeLens" => json!([{
+            "range": range,
+            "command": { "title": "mock", "command": "mock.command" },
+        }]),
+        "textDocument/inlayHint" => json!([{ "position": pos, "label": "mock:" }]),
+        "textDocument/semanticTokens/full" => json!({ "resultId": "1", "data": [0, 0, 1, 0, 0] }),
+        "textDocument/semanticTokens/range" => json!({ "data": [0, 0, 1, 0, 0] }),
+        "textDocument/semanticTokens/full/delta" => json!({ "resultId": "2", "edits": [] }),
+        "textDocument/prepareCallHierarchy" => json!([item]),
+        "callHierarchy/incomingCalls" => {
+            json!([{ "from": params["item"], "fromRanges": [params["item"]["range"]] }])
+        }
+        "callHierarchy/outgoingCalls" => {
+            json!([{ "to": params["item"], "fromRanges": [params["item"]["range"]] }])
+        }
+        "workspace/symbol" => json!([{
+            "name": "Mock",
+            "kind": 5,
+            "location": { "uri": "file:///mock.sol", "range": range },
+        }]),
+        "textDocument/diagnostic" => json!({ "kind": "full", "items": diagnostics }),
+        "textDocument/documentLink"
+        | "textDocument/formatting"
+        | "textDocument/documentColor"
+        | "textDocument/codeAction" => json!([]),
+        _ => Value::Null,
+    }
+}
+
+/// Replace `$uri` in every string of `v`.
+fn substitute(v: &Value, uri: &str) -> Value {
+    match v {
+        Value::String(s) => Value::String(s.replace("$uri", uri)),
+        Value::Array(a) => Value::Array(a.iter().map(|x| substitute(x, uri)).collect()),
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .map(|(k, x)| (k.clone(), substitute(x, uri)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+// ── Server ──────────────────────────────────────────────────────────────────
+
+/// xorshift64: deterministic with a seed, good enough for jitter.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+struct Server {
+    script: Script,
+    caps: Value,
+    out: Out,
+    rng: Rng,
+    /// Requests waiting out their latency; `$/cancelRequest` removes them.
+    pending: Arc<Mutex<HashSet<String>>>,
+    requests: u64,
+    per_method: HashMap<String, u64>,
+}
+
+impl Server {
+    fn publish_diagnostics(&self, params: &Value) {
+        let doc = &params["textDocument"];
+        let mut publish = json!({
+            "uri": doc["uri"],
+            "version": doc["version"],
+            "diagnostics": self.diagnostics(),
+        });
+        if self.script.unversioned_diagnostics {
+            publish.as_object_mut().unwrap().remove("version");
+        }
+        write(
+            &self.out,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": publish,
+            }),
+        );
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        self.script
+            .diagnostics
+            .clone()
+            .unwrap_or_else(default_diagnostics)
+    }
+
+    fn register_capabilities(&self) {
+        if self.script.registrations.is_empty() {
+            return;
+        }
+        write(
+            &self.out,
+            &json!({
+                "jsonrpc": "2.0",
+                "id": "mock-register",
+                "method": "client/registerCapability",
+                "params": { "registrations": self.script.registrations },
+            }),
+        );
+    }
+
+    /// Whether `method` has a provider, advertised or registered.
+    fn supports(&self, method: &str) -> bool {
+        capabilities::supports(&self.caps, method)
+            || self
+                .script
+                .registrations
+                .iter()
+                .any(|r| r["method"] == method)
+    }
+
+    fn start_progress(&self) {
+        let Some(ref p) = self.script.progress else {
+            return;
+        };
+        let (token, title, duration) = (
+            p.token.clone(),
+            p.title.clone(),
+            Duration::from_millis(p.duration_ms),
+        );
+        let out = self.out.clone();
+        std::thread::spawn(move || {
+            write(
+                &out,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": "mock-progress",
+                    "method": "window/workDoneProgress/create",
+                    "params": { "token": token },
+                }),
+            );
+            let progress = |value: Value| {
+                write(
+                    &out,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "method": "$/progress",
+                        "params": { "token": token, "value": value },
+                    }),
+                );
+            };
+            progress(json!({ "kind": "begin", "title": title, "percentage": 0 }));
+            std::thread::sleep(duration / 2);
+            progress(json!({ "kind": "report", "percentage": 50 }));
+            std::thread::sleep(duration / 2);
+            progress(json!({ "kind": "end" }));
+        });
+    }
+
+    fn crash(&self) -> ! {
+        std::process::exit(self.script.exit_code.unwrap_or(1));
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) {
+        match method {
+            "initialized" => {
+                self.register_capabilities();
+                self.start_progress();
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => self.publish_diagnostics(params),
+            "$/cancelRequest" => {
+                let id = params["id"].to_string();
+                if self.pending.lock().unwrap().remove(&id) {
+                    write(
+                        &self.out,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": params["id"],
+                            "error": { "code": -32800, "message": "Request cancelled" },
+                        }),
+                    );
+                }
+            }
+            "exit" => std::process::exit(0),
+            _ => {}
+        }
+    }
+
+    fn handle_request(&mut self, id: Value, method: &str, params: &Value) {
+        self.requests += 1;
+        let nth = self.per_method.entry(method.to_string()).or_insert(0);
+        *nth += 1;
+        let nth = *nth;
+        if self.script.crash_on_request == Some(self.requests) {
+            self.crash();
+        }
+        let entry = self.script.methods.get(method);
+        if entry.and_then(|m| m.crash_on) == Some(nth) {
+            self.crash();
+        }
+
+        let reply = if let Some(err) = entry.and_then(|m| m.error.clone()) {
+            json!({ "jsonrpc": "2.0", "id": id, "error": err })
+        } else if let Some(result) = entry.and_then(|m| m.result.clone()) {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+            json!({ "jsonrpc": "2.0", "id": id, "result": substitute(&result, uri) })
+        } else {
+            match method {
+                "initialize" => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": {
+                        "capabilities": self.caps,
+                        "serverInfo": { "name": "mock-lsp", "version": env!("CARGO_PKG_VERSION") },
+                    },
+                }),
+                "shutdown" => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+                "textDocument/diagnostic" => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": default_result(method, params, &self.diagnostics()),
+                }),
+                _ if METHODS.contains(&method) && self.supports(method) => {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": default_result(method, params, &[]) })
+                }
+                _ => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+                }),
+            }
+        };
+
+        let latency = entry
+            .and_then(|m| m.latency_ms)
+            .unwrap_or(self.script.latency_ms);
+        let jitter = entry
+            .and_then(|m| m.jitter_ms)
+            .unwrap_or(self.script.jitter_ms);
+        let delay = latency
+            + if jitter > 0 {
+                self.rng.below(jitter + 1)
+            } else {
+                0
+            };
+        if delay == 0 || method == "initialize" || method == "shutdown" {
+            std::thread::sleep(Duration::from_millis(delay));
+            write(&self.out, &reply);
+            return;
+        }
+        // Answer from a thread so later requests (and cancellations) are
+        // handled while this one waits out its latency.
+        let key = reply["id"].to_string();
+        self.pending.lock().unwrap().insert(key.clone());
+        let pending = self.pending.clone();
+        let out = self.out.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(delay));
+            if pending.lock().unwrap().remove(&key) {
+                write(&out, &reply);
+            }
+        });
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if cli.worker {
+        // Exit once the server closes our stdin (or dies).
+        let _ = std::io::copy(&mut std::io::stdin(), &mut std::io::sink());
+        return;
+    }
+    let script: Script = match cli.script {
+        Some(ref path) => {
+            let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {}", path, e);
+                std::process::exit(1);
+            });
+            serde_yaml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("Error parsing {}: {}", path, e);
+                std::process::exit(1);
+            })
+        }
+        None => Script::default(),
+    };
+    let exe = std::env::current_exe().unwrap();
+    let _workers: Vec<std::process::Child> = (0..script.workers)
+        .filter_map(|_| {
+            std::process::Command::new(&exe)
+                .arg("--worker")
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .ok()
+        })
+        .collect();
+    let caps = script
+        .capabilities
+        .clone()
+        .unwrap_or_else(default_capabilities);
+    let seed = script.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |d| d.as_nanos() as u64)
+    });
+    let mut server = Server {
+        script,
+        caps,
+        out: Arc::new(Mutex::new(std::io::stdout())),
+        rng: Rng(seed.max(1)),
+        pending: Arc::new(Mutex::new(HashSet::new())),
+        requests: 0,
+        per_method: HashMap::new(),
+    };
+
+    let mut reader = BufReader::new(std::io::stdin());
+    while let Some(msg) = read(&mut reader) {
+        let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        match msg.get("id") {
+            // Responses to our own requests (e.g. workDoneProgress/create).
+            Some(_) if method.is_empty() => {}
+            Some(id) => server.handle_request(id.clone(), method, &params),
+            None => server.handle_notification(method, &params),
+        }
+    }
+}
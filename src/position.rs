@@ -0,0 +1,328 @@
+//! LSP position encodings and the text/position conversions built on them.
+
+use crate::config::SyncMode;
+use serde_json::{json, Value};
+
+/// Unit in which LSP `Position.character` is counted. Negotiated during
+/// `initialize`; servers that don't say otherwise use UTF-16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Read `positionEncoding` from a server's capabilities.
+    pub fn from_capabilities(caps: &Value) -> Self {
+        match caps.get("positionEncoding").and_then(|e| e.as_str()) {
+            Some("utf-8") => PositionEncoding::Utf8,
+            Some("utf-32") => PositionEncoding::Utf32,
+            _ => PositionEncoding::Utf16,
+        }
+    }
+
+    /// Number of code units `ch` occupies in this encoding.
+    pub fn unit_len(self, ch: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8(),
+            PositionEncoding::Utf16 => ch.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// Convert a column counted in characters (what the config and editors
+/// show) into code units of `encoding` on the given line of `text`.
+fn char_col_to_units(text: &str, line: u32, col: u32, encoding: PositionEncoding) -> u32 {
+    let line_text = text.lines().nth(line as usize).unwrap_or("");
+    let units: usize = line_text
+        .chars()
+        .take(col as usize)
+        .map(|ch| encoding.unit_len(ch))
+        .sum();
+    // Columns past the end of the line are passed through unchanged.
+    let extra = (col as usize).saturating_sub(line_text.chars().count());
+    (units + extra) as u32
+}
+
+/// Inverse of `char_col_to_units`: a column in code units back to characters.
+pub fn units_to_char_col(text: &str, line: u32, units: u32, encoding: PositionEncoding) -> u32 {
+    let line_text = text.lines().nth(line as usize).unwrap_or("");
+    let mut seen = 0usize;
+    let mut chars = 0u32;
+    for ch in line_text.chars() {
+        if seen >= units as usize {
+            return chars;
+        }
+        seen += encoding.unit_len(ch);
+        chars += 1;
+    }
+    chars + (units as usize).saturating_sub(seen) as u32
+}
+
+/// Rewrite the cursor positions in request `params` (`position`,
+/// `positions[]` and `range`) from character columns to the server's
+/// `encoding`, using `text` as the document content. Nested positions (e.g.
+/// diagnostics in a codeAction context) come from the server and are left
+/// alone.
+pub fn encode_positions(params: &mut Value, text: &str, encoding: PositionEncoding) {
+    if encoding == PositionEncoding::Utf32 {
+        return;
+    }
+    fn convert(pos: &mut Value, text: &str, encoding: PositionEncoding) {
+        let line = pos.get("line").and_then(|l| l.as_u64());
+        let col = pos.get("character").and_then(|c| c.as_u64());
+        if let (Some(line), Some(col)) = (line, col) {
+            pos["character"] = json!(char_col_to_units(text, line as u32, col as u32, encoding));
+        }
+    }
+    if let Some(pos) = params.get_mut("position") {
+        convert(pos, text, encoding);
+    }
+    if let Some(list) = params.get_mut("positions").and_then(|p| p.as_array_mut()) {
+        for pos in list {
+            convert(pos, text, encoding);
+        }
+    }
+    if let Some(range) = params.get_mut("range") {
+        for key in ["start", "end"] {
+            if let Some(pos) = range.get_mut(key) {
+                convert(pos, text, encoding);
+            }
+        }
+    }
+}
+
+/// LSP position of byte offset `byte` in `text`, counted in `encoding` units.
+fn byte_offset_to_lsp_pos(text: &str, byte: usize, encoding: PositionEncoding) -> Value {
+    let before = &text[..byte];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character: usize = before[line_start..]
+        .chars()
+        .map(|ch| encoding.unit_len(ch))
+        .sum();
+    json!({ "line": line, "character": character })
+}
+
+/// Build a `TextDocumentContentChangeEvent` that turns `old` into `new` with
+/// one range edit: the common prefix and suffix are kept and everything in
+/// between is replaced.
+pub fn incremental_change(old: &str, new: &str, encoding: PositionEncoding) -> Value {
+    let prefix: usize = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    json!({
+        "range": {
+            "start": byte_offset_to_lsp_pos(old, prefix, encoding),
+            "end": byte_offset_to_lsp_pos(old, old.len() - suffix, encoding),
+        },
+        "text": &new[prefix..new.len() - suffix],
+    })
+}
+
+/// Suffix for the "snapshot(s) via didChange" line.
+pub fn sync_label(sync: SyncMode) -> &'static str {
+    match sync {
+        SyncMode::Full => "",
+        SyncMode::Incremental => " (incremental)",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PositionEncoding::{Utf16, Utf32, Utf8};
+
+    /// `a`, a 2-byte and a 3-byte BMP character, an emoji (4 UTF-8 bytes,
+    /// 2 UTF-16 units) and `b`, with CRLF line endings.
+    const TEXT: &str = "a\u{e9}\u{4e2d}\u{1f600}b\r\nsecond\r\n";
+
+    #[test]
+    fn char_columns_become_code_units() {
+        let cols = 0..=5;
+        let units = |enc| -> Vec<u32> {
+            cols.clone()
+                .map(|c| char_col_to_units(TEXT, 0, c, enc))
+                .collect()
+        };
+        assert_eq!(units(Utf8), [0, 1, 3, 6, 10, 11]);
+        assert_eq!(units(Utf16), [0, 1, 2, 3, 5, 6]);
+        assert_eq!(units(Utf32), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn crlf_is_not_part_of_the_line() {
+        assert_eq!(char_col_to_units(TEXT, 1, 6, Utf16), 6);
+        assert_eq!(units_to_char_col(TEXT, 0, 6, Utf16), 5);
+        assert_eq!(units_to_char_col(TEXT, 0, 11, Utf8), 5);
+    }
+
+    #[test]
+    fn columns_past_the_end_pass_through() {
+        // Two characters past the end of line 0.
+        assert_eq!(char_col_to_units(TEXT, 0, 7, Utf16), 8);
+        assert_eq!(char_col_to_units(TEXT, 0, 7, Utf8), 13);
+        assert_eq!(units_to_char_col(TEXT, 0, 8, Utf16), 7);
+        // Lines past the end of the file are empty.
+        assert_eq!(char_col_to_units(TEXT, 9, 3, Utf16), 3);
+        assert_eq!(units_to_char_col(TEXT, 9, 3, Utf8), 3);
+    }
+
+    #[test]
+    fn columns_round_trip_through_every_encoding() {
+        for enc in [Utf8, Utf16, Utf32] {
+            for line in 0..3 {
+                for col in 0..=8 {
+                    let units = char_col_to_units(TEXT, line, col, enc);
+                    assert_eq!(
+                        units_to_char_col(TEXT, line, units, enc),
+                        col,
+                        "{:?} line {} col {}",
+                        enc,
+                        line,
+                        col
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn request_positions_are_encoded() {
+        let params = json!({
+            "position": { "line": 0, "character": 4 },
+            "positions": [{ "line": 0, "character": 3 }],
+            "range": {
+                "start": { "line": 0, "character": 1 },
+                "end": { "line": 0, "character": 5 },
+            },
+            "context": { "diagnostics": [{ "range": { "start": { "line": 0, "character": 4 } } }] },
+        });
+        let encoded = |enc| {
+            let mut p = params.clone();
+            encode_positions(&mut p, TEXT, enc);
+            p
+        };
+        let p = encoded(Utf16);
+        assert_eq!(p["position"]["character"], 5);
+        assert_eq!(p["positions"][0]["character"], 3);
+        assert_eq!(p["range"]["start"]["character"], 1);
+        assert_eq!(p["range"]["end"]["character"], 6);
+        assert_eq!(
+            p["context"]["diagnostics"][0]["range"]["start"]["character"],
+            4
+        );
+        let p = encoded(Utf8);
+        assert_eq!(p["position"]["character"], 10);
+        assert_eq!(p["range"]["end"]["character"], 11);
+        assert_eq!(encoded(Utf32), params);
+    }
+
+    /// Apply a UTF-8-encoded change event to `old`.
+    fn apply(old: &str, change: &Value) -> String {
+        let offset = |pos: &Value| {
+            let line = pos["line"].as_u64().unwrap() as usize;
+            let line_start: usize = old.split_inclusive('\n').take(line).map(str::len).sum();
+            line_start + pos["character"].as_u64().unwrap() as usize
+        };
+        let (start, end) = (
+            offset(&change["range"]["start"]),
+            offset(&change["range"]["end"]),
+        );
+        format!(
+            "{}{}{}",
+            &old[..start],
+            change["text"].as_str().unwrap(),
+            &old[end..]
+        )
+    }
+
+    /// `(start, end, text)` of the change from `old` to `new`, checking that
+    /// applying it gives `new`.
+    fn change(old: &str, new: &str, enc: PositionEncoding) -> ((u64, u64), (u64, u64), String) {
+        assert_eq!(apply(old, &incremental_change(old, new, Utf8)), new);
+        let c = incremental_change(old, new, enc);
+        let pos = |p: &Value| {
+            (
+                p["line"].as_u64().unwrap(),
+                p["character"].as_u64().unwrap(),
+            )
+        };
+        (
+            pos(&c["range"]["start"]),
+            pos(&c["range"]["end"]),
+            c["text"].as_str().unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn incremental_change_inserts_and_deletes() {
+        let old = "ab\ncd";
+        for (new, start, end, text) in [
+            ("Xab\ncd", (0, 0), (0, 0), "X"),
+            ("ab\nXcd", (1, 0), (1, 0), "X"),
+            ("ab\ncdX", (1, 2), (1, 2), "X"),
+            ("b\ncd", (0, 0), (0, 1), ""),
+            ("abcd", (0, 2), (1, 0), ""),
+            ("ab\nc", (1, 1), (1, 2), ""),
+        ] {
+            assert_eq!(
+                change(old, new, Utf16),
+                (start, end, text.into()),
+                "{:?}",
+                new
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_change_handles_overlapping_prefix_and_suffix() {
+        assert_eq!(change("aa", "aaa", Utf16), ((0, 2), (0, 2), "a".into()));
+        assert_eq!(change("aaa", "aa", Utf16), ((0, 2), (0, 3), "".into()));
+        assert_eq!(change("abab", "ab", Utf16), ((0, 2), (0, 4), "".into()));
+        assert_eq!(change("ab", "abab", Utf16), ((0, 2), (0, 2), "ab".into()));
+    }
+
+    #[test]
+    fn incremental_change_keeps_multibyte_characters_whole() {
+        // é and è share their first UTF-8 byte; the emoji share three.
+        assert_eq!(
+            change("a\u{e9}b", "a\u{e8}b", Utf16),
+            ((0, 1), (0, 2), "\u{e8}".into())
+        );
+        assert_eq!(
+            change("x\u{1f600}y", "x\u{1f601}y", Utf16),
+            ((0, 1), (0, 3), "\u{1f601}".into())
+        );
+        assert_eq!(
+            change("x\u{1f600}y", "x\u{1f601}y", Utf8),
+            ((0, 1), (0, 5), "\u{1f601}".into())
+        );
+        assert_eq!(
+            change("x\u{1f600}y", "x\u{1f600}\u{1f600}y", Utf32),
+            ((0, 2), (0, 2), "\u{1f600}".into())
+        );
+    }
+
+    #[test]
+    fn incremental_change_of_identical_text_is_empty() {
+        assert_eq!(
+            change("ab\ncd", "ab\ncd", Utf16),
+            ((1, 2), (1, 2), "".into())
+        );
+        assert_eq!(change("", "", Utf16), ((0, 0), (0, 0), "".into()));
+    }
+}
@@ -0,0 +1,1042 @@
+//! Replaying a recorded request or a whole wire-trace session against a
+//! server, and minimizing sessions that crash it.
+
+use crate::client::LspClient;
+use crate::helpers::uri;
+use clap::ValueEnum;
+use console::style;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub fn replay(
+    server: &str,
+    input: &str,
+    project: Option<&str>,
+    file: Option<&str>,
+    timeout_secs: u64,
+) {
+    // Parse the JSON-RPC input string
+    let rpc: Value = serde_json::from_str(input).unwrap_or_else(|e| {
+        eprintln!("Error: invalid JSON-RPC input: {}", e);
+        std::process::exit(1);
+    });
+    let method = rpc
+        .get("method")
+        .and_then(|m| m.as_str())
+        .unwrap_or_else(|| {
+            eprintln!("Error: input missing \"method\" field");
+            std::process::exit(1);
+        });
+    let params = rpc.get("params").cloned().unwrap_or(json!({}));
+
+    // Extract file URI from params if not provided explicitly
+    let file_uri = params
+        .get("textDocument")
+        .and_then(|td| td.get("uri"))
+        .and_then(|u| u.as_str());
+
+    let file_path: Option<PathBuf> = file.map(PathBuf::from).or_else(|| {
+        file_uri
+            .and_then(|u| u.strip_prefix("file://"))
+            .map(PathBuf::from)
+    });
+
+    // Resolve project root
+    let cwd = project
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+
+    if !cwd.exists() {
+        eprintln!("Error: project directory not found: {}", cwd.display());
+        std::process::exit(1);
+    }
+
+    // Parse server command
+    let parts: Vec<&str> = server.split_whitespace().collect();
+    if parts.is_empty() {
+        eprintln!("Error: empty server command");
+        std::process::exit(1);
+    }
+    let cmd = parts[0];
+    let args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
+
+    let root = uri(&cwd);
+    let timeout = Duration::from_secs(timeout_secs);
+
+    eprintln!("  {} {}", style("server").dim(), server);
+    eprintln!("  {} {}", style("method").dim(), method);
+    if let Some(ref fp) = file_path {
+        eprintln!("  {} {}", style("file").dim(), fp.display());
+    }
+    eprintln!();
+
+    // Spawn server
+    eprintln!("{}", style("Spawning server...").dim());
+    let mut client = LspClient::spawn(cmd, &args, &cwd, true, None).unwrap_or_else(|e| {
+        eprintln!("Error: failed to spawn server: {}", e);
+        std::process::exit(1);
+    });
+
+    // Initialize
+    eprintln!("{}", style("Initializing...").dim());
+    if let Err(e) = client.initialize(&root, None) {
+        eprintln!("Error: initialize failed: {}", e);
+        std::process::exit(1);
+    }
+
+    // Open file if we have one
+    if let Some(ref fp) = file_path {
+        if fp.exists() {
+            eprintln!("{}", style("Opening file...").dim());
+            if let Err(e) = client.open_file(fp) {
+                eprintln!("Error: open file failed: {}", e);
+                std::process::exit(1);
+            }
+            // Give server a moment to index
+            std::thread::sleep(Duration::from_millis(500));
+        } else {
+            eprintln!(
+                "  {} file not found: {}",
+                style("warn").yellow(),
+                fp.display()
+            );
+        }
+    }
+
+    // Send the request
+    eprintln!("{}", style("Sending request...").dim());
+    let req_id = match client.send(method, params) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Error: send failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Read response
+    match client.read_response(req_id, timeout) {
+        Ok(resp) => {
+            let pretty = serde_json::to_string_pretty(&resp).unwrap();
+            eprintln!();
+            println!("{}", pretty);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// ── Session replay ──────────────────────────────────────────────────────────
+
+/// How `replay --session` paces client messages.
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Timing {
+    /// Keep the recorded gaps between client messages.
+    Original,
+    /// Send each message as soon as the responses the recording had already
+    /// received at that point have arrived.
+    Compressed,
+}
+
+/// One server run from a wire trace.
+pub struct Session {
+    /// Server command from the trace's `spawn` line.
+    pub cmd: Option<(String, Vec<String>)>,
+    /// Client → server messages with their `t_ms`. Replies to server-initiated
+    /// requests are left out; those are answered live on replay.
+    pub sent: Vec<(f64, Value)>,
+    /// Server responses to client requests by id, with their `t_ms`.
+    pub responses: HashMap<String, (f64, Value)>,
+}
+
+impl Session {
+    /// Method of the client request with the given id.
+    pub fn method_of(&self, id: &str) -> &str {
+        self.sent
+            .iter()
+            .find(|(_, m)| m.get("id").map(|i| i.to_string()).as_deref() == Some(id))
+            .and_then(|(_, m)| m.get("method"))
+            .and_then(|m| m.as_str())
+            .unwrap_or("?")
+    }
+
+    /// Root directory from the recorded `initialize` params.
+    pub fn root(&self) -> Option<String> {
+        let init = self
+            .sent
+            .iter()
+            .find(|(_, m)| m.get("method").and_then(|m| m.as_str()) == Some("initialize"))?;
+        let params = init.1.get("params")?;
+        params
+            .get("rootUri")
+            .and_then(|u| u.as_str())
+            .and_then(|u| u.strip_prefix("file://"))
+            .or_else(|| params.get("rootPath").and_then(|p| p.as_str()))
+            .map(|s| s.to_string())
+    }
+}
+
+/// A JSON-RPC message with an `id` and no `method` is a response.
+fn is_response(msg: &Value) -> bool {
+    msg.get("id").is_some() && msg.get("method").is_none()
+}
+
+/// Split a wire trace into one [`Session`] per `spawn` line.
+pub fn load_sessions(path: &Path) -> Result<Vec<Session>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut sessions: Vec<Session> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Value = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+        let t = entry.get("t_ms").and_then(|t| t.as_f64()).unwrap_or(0.0);
+        let msg = entry.get("message").cloned().unwrap_or(Value::Null);
+        let dir = entry.get("dir").and_then(|d| d.as_str()).unwrap_or("");
+        if dir == "spawn" || sessions.is_empty() {
+            sessions.push(Session {
+                cmd: None,
+                sent: Vec::new(),
+                responses: HashMap::new(),
+            });
+        }
+        let session = sessions.last_mut().unwrap();
+        match dir {
+            "spawn" => {
+                let cmd = msg
+                    .get("cmd")
+                    .and_then(|c| c.as_str())
+                    .map(|c| c.to_string());
+                let args = msg
+                    .get("args")
+                    .and_then(|a| a.as_array())
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|s| s.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                session.cmd = cmd.map(|c| (c, args));
+            }
+            "send" if !is_response(&msg) => session.sent.push((t, msg)),
+            "recv" if is_response(&msg) => {
+                let id = msg["id"].to_string();
+                session.responses.insert(id, (t, msg));
+            }
+            _ => {}
+        }
+    }
+    sessions.retain(|s| !s.sent.is_empty());
+    if sessions.is_empty() {
+        return Err(format!("{}: no client messages recorded", path.display()));
+    }
+    Ok(sessions)
+}
+
+/// Replace every occurrence of the recorded root path in `msg` (URIs
+/// included, since they embed the path).
+fn rewrite_root(msg: &Value, from: &str, to: &str) -> Value {
+    if from == to {
+        return msg.clone();
+    }
+    let text = serde_json::to_string(msg).unwrap();
+    serde_json::from_str(&text.replace(from, to)).unwrap_or_else(|_| msg.clone())
+}
+
+/// How long a replayed server gets to exit after the last message before the
+/// session counts as surviving.
+pub const EXIT_GRACE: Duration = Duration::from_millis(500);
+
+/// Result of replaying a session against a live server.
+pub struct ReplayOutcome {
+    /// Replayed responses by request id, with latency in ms.
+    pub responses: HashMap<String, (f64, Value)>,
+    /// Why the replay stopped early: the server exited or stopped answering.
+    pub failure: Option<String>,
+    /// Whether the failure was the server exiting (rather than hanging).
+    pub exited: bool,
+}
+
+/// Where and how to run a recorded session.
+pub struct ReplayTarget {
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    /// Recorded root path and the path replacing it.
+    pub root: Option<(String, String)>,
+}
+
+impl ReplayTarget {
+    /// Resolve the server command (`server`, else the trace's `spawn` line)
+    /// and project directory (`project`, else the recorded root).
+    pub fn resolve(
+        server: Option<&str>,
+        project: Option<&str>,
+        session: &Session,
+    ) -> Result<Self, String> {
+        let (cmd, args) = match server {
+            Some(s) => {
+                let parts: Vec<&str> = s.split_whitespace().collect();
+                if parts.is_empty() {
+                    return Err("empty server command".to_string());
+                }
+                let args = parts[1..].iter().map(|s| s.to_string()).collect();
+                (parts[0].to_string(), args)
+            }
+            None => session
+                .cmd
+                .clone()
+                .ok_or("trace has no spawn line; pass --server")?,
+        };
+        let recorded_root = session.root();
+        let cwd = match project
+            .map(PathBuf::from)
+            .or(recorded_root.as_ref().map(PathBuf::from))
+        {
+            Some(p) => p,
+            None => std::env::current_dir().unwrap(),
+        };
+        if !cwd.exists() {
+            return Err(format!(
+                "project directory not found: {} (pass --project)",
+                cwd.display()
+            ));
+        }
+        let new_root = uri(&cwd);
+        let new_root = new_root
+            .strip_prefix("file://")
+            .unwrap_or(&new_root)
+            .to_string();
+        Ok(Self {
+            cmd,
+            args,
+            cwd,
+            root: recorded_root.map(|from| (from, new_root)),
+        })
+    }
+
+    /// Replay `session` against a fresh server process.
+    pub fn run(
+        &self,
+        session: &Session,
+        timing: Timing,
+        timeout: Duration,
+    ) -> Result<ReplayOutcome, String> {
+        let mut client = LspClient::spawn(&self.cmd, &self.args, &self.cwd, false, None)?;
+        let root = self
+            .root
+            .as_ref()
+            .map(|(from, to)| (from.as_str(), to.as_str()));
+        let outcome = replay_messages(&mut client, session, root, timing, timeout);
+        if outcome.failure.is_some() {
+            client.abort();
+        } else {
+            client.kill();
+        }
+        Ok(outcome)
+    }
+}
+
+/// Read server messages until the response to `until` arrives, or — with no
+/// id to wait for — until `deadline`. Server requests are answered and
+/// responses collected along the way.
+fn pump_responses(
+    client: &mut LspClient,
+    sent_at: &HashMap<String, Instant>,
+    got: &mut HashMap<String, (f64, Value)>,
+    until: Option<&str>,
+    deadline: Instant,
+) -> Result<(), String> {
+    loop {
+        if let Some(id) = until {
+            if got.contains_key(id) {
+                return Ok(());
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return match until {
+                Some(_) => Err("timeout".to_string()),
+                None => Ok(()),
+            };
+        }
+        let msg = match client.recv(deadline - now) {
+            Ok(msg) => msg,
+            Err(e) if e == "timeout" => continue,
+            Err(e) => return Err(e),
+        };
+        if client.handle_server_request(&msg) || !is_response(&msg) {
+            continue;
+        }
+        let id = msg["id"].to_string();
+        if let Some(at) = sent_at.get(&id) {
+            got.insert(id, (at.elapsed().as_secs_f64() * 1000.0, msg));
+        }
+    }
+}
+
+/// Send a recorded session's client messages to `client` in order.
+///
+/// Before each message, waits for the responses the recording had already
+/// received by then, so requests never overtake the edits they depended on.
+/// With [`Timing::Original`] it also sleeps out the recorded gap. `root`
+/// rewrites the recorded root directory to the one being replayed against.
+pub fn replay_messages(
+    client: &mut LspClient,
+    session: &Session,
+    root: Option<(&str, &str)>,
+    timing: Timing,
+    timeout: Duration,
+) -> ReplayOutcome {
+    let t0 = session.sent.first().map(|(t, _)| *t).unwrap_or(0.0);
+    let start = Instant::now();
+    let mut sent_at: HashMap<String, Instant> = HashMap::new();
+    let mut got: HashMap<String, (f64, Value)> = HashMap::new();
+    let mut exit_sent = false;
+    let mut failure = None;
+    let mut exited = false;
+
+    // Keep our own ids (e.g. the final `shutdown`) clear of the recording's.
+    let max_id = session
+        .sent
+        .iter()
+        .filter_map(|(_, m)| m.get("id").and_then(|i| i.as_i64()))
+        .max()
+        .unwrap_or(0);
+    client.id = client.id.max(max_id + 1);
+
+    let unanswered = |e: &str, id: &str| {
+        let what = format!("#{} {}", id, session.method_of(id));
+        if e == "timeout" {
+            format!("no response to {} within {}s", what, timeout.as_secs())
+        } else {
+            format!("server exited ({}) before answering {}", e, what)
+        }
+    };
+
+    'messages: for (i, (t, recorded)) in session.sent.iter().enumerate() {
+        let method = recorded
+            .get("method")
+            .and_then(|m| m.as_str())
+            .unwrap_or("?");
+        let mut waiting: Vec<&String> = session
+            .responses
+            .iter()
+            .filter(|(id, (rt, _))| rt < t && sent_at.contains_key(*id) && !got.contains_key(*id))
+            .map(|(id, _)| id)
+            .collect();
+        waiting.sort();
+        for id in waiting {
+            let deadline = Instant::now() + timeout;
+            if let Err(e) = pump_responses(client, &sent_at, &mut got, Some(id), deadline) {
+                exited = e != "timeout";
+                failure = Some(unanswered(&e, id));
+                break 'messages;
+            }
+        }
+        if timing == Timing::Original {
+            let due = start + Duration::from_secs_f64((t - t0).max(0.0) / 1000.0);
+            if let Err(e) = pump_responses(client, &sent_at, &mut got, None, due) {
+                exited = true;
+                failure = Some(format!(
+                    "server exited ({}) before message {} ({})",
+                    e,
+                    i + 1,
+                    method
+                ));
+                break;
+            }
+        }
+
+        let mut msg = match root {
+            Some((from, to)) => rewrite_root(recorded, from, to),
+            None => recorded.clone(),
+        };
+        if method == "initialize" && msg["params"].get("processId").is_some() {
+            msg["params"]["processId"] = json!(std::process::id());
+        }
+        if let Err(e) = client.write_message(&msg) {
+            exited = true;
+            failure = Some(format!(
+                "write failed at message {} ({}): {}",
+                i + 1,
+                method,
+                e
+            ));
+            break;
+        }
+        if let Some(id) = msg.get("id") {
+            sent_at.insert(id.to_string(), Instant::now());
+        }
+        // Nothing after `exit` reaches a compliant server.
+        if method == "exit" {
+            exit_sent = true;
+            break;
+        }
+    }
+
+    if failure.is_none() {
+        let mut pending: Vec<&String> = session
+            .responses
+            .keys()
+            .filter(|id| sent_at.contains_key(*id) && !got.contains_key(*id))
+            .collect();
+        pending.sort();
+        for id in pending {
+            let deadline = Instant::now() + timeout;
+            if let Err(e) = pump_responses(client, &sent_at, &mut got, Some(id), deadline) {
+                if !(exit_sent && e == "EOF") {
+                    exited = e != "timeout";
+                    failure = Some(unanswered(&e, id));
+                }
+                break;
+            }
+        }
+    }
+
+    // Requests the recording never saw answered (the server crashed on them,
+    // or they were cancelled) get a short grace period: a server that exits
+    // instead of answering them has crashed, even if `exit` followed.
+    if failure.is_none() {
+        let deadline = Instant::now() + EXIT_GRACE;
+        let mut unanswered_ids: Vec<&String> =
+            sent_at.keys().filter(|id| !got.contains_key(*id)).collect();
+        unanswered_ids.sort();
+        for id in unanswered_ids {
+            match pump_responses(client, &sent_at, &mut got, Some(id), deadline) {
+                Err(e) if e != "timeout" => {
+                    exited = true;
+                    failure = Some(unanswered(&e, id));
+                    break;
+                }
+                Err(_) => break,
+                Ok(()) => {}
+            }
+        }
+    }
+
+    // A crash on the last notification (e.g. a `didChange`) leaves nothing
+    // to time out on, so give the server a moment to fall over.
+    if failure.is_none() && !exit_sent {
+        let deadline = Instant::now() + EXIT_GRACE;
+        if let Err(e) = pump_responses(client, &sent_at, &mut got, None, deadline) {
+            exited = true;
+            failure = Some(format!(
+                "server exited ({}) after the last message ({})",
+                e,
+                session.sent.len()
+            ));
+        }
+    }
+
+    ReplayOutcome {
+        responses: got,
+        failure,
+        exited,
+    }
+}
+
+/// Paths at which two JSON values differ, as `path: old → new` lines.
+fn json_diff(path: &str, old: &Value, new: &Value, out: &mut Vec<String>) {
+    let short = |v: Option<&Value>| match v {
+        Some(v) => {
+            let s = serde_json::to_string(v).unwrap_or_default();
+            if s.len() > 60 {
+                let cut = (0..=57).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+                format!("{}...", &s[..cut])
+            } else {
+                s
+            }
+        }
+        None => "(absent)".to_string(),
+    };
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for k in keys {
+                let p = if path.is_empty() {
+                    k.to_string()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                match (a.get(k), b.get(k)) {
+                    (Some(x), Some(y)) => json_diff(&p, x, y, out),
+                    (x, y) => out.push(format!("{}: {} → {}", p, short(x), short(y))),
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let p = format!("{}[{}]", path, i);
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => json_diff(&p, x, y, out),
+                    (x, y) => out.push(format!("{}: {} → {}", p, short(x), short(y))),
+                }
+            }
+        }
+        _ if old != new => out.push(format!(
+            "{}: {} → {}",
+            path,
+            short(Some(old)),
+            short(Some(new))
+        )),
+        _ => {}
+    }
+}
+
+/// `result` or `error` of a response, the part compared on replay.
+fn response_body(msg: &Value) -> Value {
+    match msg.get("error") {
+        Some(err) => json!({ "error": err }),
+        None => json!({ "result": msg.get("result").cloned().unwrap_or(Value::Null) }),
+    }
+}
+
+pub fn replay_session(
+    server: Option<&str>,
+    session_path: &str,
+    project: Option<&str>,
+    timeout_secs: u64,
+    timing: Timing,
+) {
+    let sessions = load_sessions(Path::new(session_path)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    let timeout = Duration::from_secs(timeout_secs);
+
+    eprintln!(
+        "  {} {} ({} session{})",
+        style("session").dim(),
+        session_path,
+        sessions.len(),
+        if sessions.len() == 1 { "" } else { "s" }
+    );
+    eprintln!(
+        "  {} {}",
+        style("timing").dim(),
+        match timing {
+            Timing::Original => "original",
+            Timing::Compressed => "compressed",
+        }
+    );
+
+    let (mut same, mut differ, mut missing, mut failed) = (0, 0, 0, 0);
+    for (n, session) in sessions.iter().enumerate() {
+        let target = ReplayTarget::resolve(server, project, session).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        });
+
+        eprintln!();
+        eprintln!(
+            "{} {}  {}",
+            style(format!("Session {}/{}", n + 1, sessions.len())).bold(),
+            style(format!("{} {}", target.cmd, target.args.join(" "))).dim(),
+            style(format!(
+                "{} messages, {} responses",
+                session.sent.len(),
+                session.responses.len()
+            ))
+            .dim()
+        );
+        if let Some((ref from, ref to)) = target.root {
+            if from != to {
+                eprintln!("  {} {} → {}", style("root").dim(), from, to);
+            }
+        }
+
+        let outcome = target.run(session, timing, timeout).unwrap_or_else(|e| {
+            eprintln!("Error: failed to spawn server: {}", e);
+            std::process::exit(1);
+        });
+        let root = target
+            .root
+            .as_ref()
+            .map(|(from, to)| (from.as_str(), to.as_str()));
+
+        let mut ids: Vec<(&String, &(f64, Value))> = session.responses.iter().collect();
+        ids.sort_by(|a, b| a.1 .0.total_cmp(&b.1 .0));
+        for (id, (rt, recorded)) in ids {
+            let method = session.method_of(id);
+            let sent_t = session
+                .sent
+                .iter()
+                .find(|(_, m)| m.get("id").map(|i| i.to_string()).as_ref() == Some(id))
+                .map(|(t, _)| *t)
+                .unwrap_or(*rt);
+            let recorded_ms = rt - sent_t;
+            let Some((ms, replayed)) = outcome.responses.get(id) else {
+                missing += 1;
+                eprintln!(
+                    "  {} #{} {}  {}",
+                    style("✗").red(),
+                    id,
+                    method,
+                    style("no response").red()
+                );
+                continue;
+            };
+            let expected = match root {
+                Some((from, to)) => rewrite_root(recorded, from, to),
+                None => recorded.clone(),
+            };
+            let mut diffs = Vec::new();
+            json_diff(
+                "",
+                &response_body(&expected),
+                &response_body(replayed),
+                &mut diffs,
+            );
+            let timing = style(format!("{:.1}ms (recorded {:.1}ms)", ms, recorded_ms)).dim();
+            if diffs.is_empty() {
+                same += 1;
+                eprintln!("  {} #{} {}  {}", style("=").green(), id, method, timing);
+            } else {
+                differ += 1;
+                eprintln!("  {} #{} {}  {}", style("≠").yellow(), id, method, timing);
+                for d in diffs.iter().take(20) {
+                    eprintln!("      {}", d);
+                }
+                if diffs.len() > 20 {
+                    eprintln!(
+                        "      {}",
+                        style(format!("... {} more", diffs.len() - 20)).dim()
+                    );
+                }
+            }
+        }
+        if let Some(ref f) = outcome.failure {
+            failed += 1;
+            eprintln!("  {} {}", style("!").red().bold(), style(f).red());
+        }
+    }
+
+    eprintln!();
+    eprintln!(
+        "{} identical, {} differ, {} missing",
+        style(same).green(),
+        style(differ).yellow(),
+        style(missing).red()
+    );
+    if differ > 0 || missing > 0 || failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// ── Minimize ────────────────────────────────────────────────────────────────
+
+/// Delta debugging (Zeller's ddmin): shrink `items` to a 1-minimal subset
+/// for which `test` still holds. `test` is assumed to hold for `items`.
+pub fn ddmin<T: Clone>(mut items: Vec<T>, test: &mut dyn FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut n = 2;
+    while items.len() >= 2 {
+        let chunk = items.len().div_ceil(n);
+        let parts: Vec<Vec<T>> = items.chunks(chunk).map(|c| c.to_vec()).collect();
+        let mut reduced = false;
+        for (i, part) in parts.iter().enumerate() {
+            if test(part) {
+                items = part.clone();
+                n = 2;
+                reduced = true;
+                break;
+            }
+            if parts.len() == 2 {
+                // With two parts the complement of one is the other.
+                continue;
+            }
+            let complement: Vec<T> = parts
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .flat_map(|(_, p)| p.iter().cloned())
+                .collect();
+            if test(&complement) {
+                items = complement;
+                n = (n - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+        if !reduced {
+            if n >= items.len() {
+                break;
+            }
+            n = (n * 2).min(items.len());
+        }
+    }
+    if items.len() == 1 && test(&[]) {
+        items.clear();
+    }
+    items
+}
+
+/// JSON pointers to the document texts a message carries: the opened file
+/// of a `didOpen`, and each content change of a `didChange`.
+fn text_fields(msg: &Value) -> Vec<String> {
+    match msg.get("method").and_then(|m| m.as_str()) {
+        Some("textDocument/didOpen") => vec!["/params/textDocument/text".to_string()],
+        Some("textDocument/didChange") => {
+            let n = msg["params"]["contentChanges"]
+                .as_array()
+                .map_or(0, |c| c.len());
+            (0..n)
+                .map(|i| format!("/params/contentChanges/{}/text", i))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Total size of the document texts in a session.
+fn session_text_bytes(session: &Session) -> usize {
+    session
+        .sent
+        .iter()
+        .flat_map(|(_, m)| {
+            text_fields(m)
+                .into_iter()
+                .filter_map(|p| m.pointer(&p).and_then(|t| t.as_str()).map(|t| t.len()))
+        })
+        .sum()
+}
+
+/// Write `session` as a wire trace that `replay --session` accepts, with the
+/// responses from `outcome` as the recording.
+fn write_session(
+    path: &Path,
+    target: &ReplayTarget,
+    session: &Session,
+    outcome: &ReplayOutcome,
+) -> Result<(), String> {
+    let root = target
+        .root
+        .as_ref()
+        .map(|(from, to)| (from.as_str(), to.as_str()));
+    let mut lines = vec![json!({
+        "t_ms": 0.0,
+        "dir": "spawn",
+        "bytes": 0,
+        "message": { "cmd": target.cmd, "args": target.args },
+    })];
+    for (t, msg) in &session.sent {
+        let msg = match root {
+            Some((from, to)) => rewrite_root(msg, from, to),
+            None => msg.clone(),
+        };
+        let body = serde_json::to_string(&msg).unwrap();
+        lines.push(json!({ "t_ms": t, "dir": "send", "bytes": body.len(), "message": msg }));
+        if let Some((ms, resp)) = msg
+            .get("id")
+            .and_then(|id| outcome.responses.get(&id.to_string()))
+        {
+            let body = serde_json::to_string(resp).unwrap();
+            lines.push(json!({
+                "t_ms": ((t + ms) * 1000.0).round() / 1000.0,
+                "dir": "recv",
+                "bytes": body.len(),
+                "message": resp,
+            }));
+        }
+    }
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&serde_json::to_string(&line).unwrap());
+        out.push('\n');
+    }
+    std::fs::write(path, out).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn minimize(
+    server: Option<&str>,
+    session_path: &str,
+    project: Option<&str>,
+    output: Option<&str>,
+    timeout_secs: u64,
+    timing: Timing,
+    max_runs: usize,
+) {
+    let sessions = load_sessions(Path::new(session_path)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    let timeout = Duration::from_secs(timeout_secs);
+    let fail = |e: String| -> ! {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    };
+
+    // Find the first session that reproduces a crash or hang.
+    let mut found = None;
+    for (n, session) in sessions.iter().enumerate() {
+        let target = ReplayTarget::resolve(server, project, session).unwrap_or_else(|e| fail(e));
+        let outcome = target
+            .run(session, timing, timeout)
+            .unwrap_or_else(|e| fail(format!("failed to spawn server: {}", e)));
+        if let Some(ref f) = outcome.failure {
+            eprintln!(
+                "  {} session {}/{}: {}",
+                style("reproduced").red(),
+                n + 1,
+                sessions.len(),
+                f
+            );
+            found = Some((session, target, outcome.exited));
+            break;
+        }
+        eprintln!(
+            "  {} session {}/{}",
+            style("survived").dim(),
+            n + 1,
+            sessions.len()
+        );
+    }
+    let Some((original, target, exited)) = found else {
+        eprintln!(
+            "Error: no session in {} crashes or hangs the server",
+            session_path
+        );
+        std::process::exit(1);
+    };
+    let kind = if exited { "exit" } else { "hang" };
+
+    let mut runs = 1;
+    // A candidate is interesting if the server fails the same way: a crash
+    // must stay a crash, a hang stays a hang.
+    let interesting = |candidate: &Session, runs: &mut usize| -> bool {
+        if *runs >= max_runs {
+            return false;
+        }
+        *runs += 1;
+        let outcome = match target.run(candidate, timing, timeout) {
+            Ok(o) => o,
+            Err(_) => return false,
+        };
+        let hit = outcome.failure.is_some() && outcome.exited == exited;
+        eprintln!(
+            "  {} run {:>3}  {} messages, {} bytes of text",
+            if hit {
+                style("✓").green()
+            } else {
+                style("·").dim()
+            },
+            runs,
+            candidate.sent.len(),
+            session_text_bytes(candidate)
+        );
+        hit
+    };
+    let with_messages = |sent: Vec<(f64, Value)>| Session {
+        cmd: original.cmd.clone(),
+        sent,
+        responses: original.responses.clone(),
+    };
+
+    // 1. Drop messages. The handshake stays: without it a server fails for
+    //    reasons unrelated to the bug.
+    let is_handshake = |m: &Value| {
+        matches!(
+            m.get("method").and_then(|m| m.as_str()),
+            Some("initialize") | Some("initialized")
+        )
+    };
+    let reducible: Vec<usize> = (0..original.sent.len())
+        .filter(|&i| !is_handshake(&original.sent[i].1))
+        .collect();
+    eprintln!();
+    eprintln!(
+        "{} {} messages ({} droppable), server {}s",
+        style("Minimizing").bold(),
+        original.sent.len(),
+        reducible.len(),
+        kind
+    );
+    let keep = ddmin(reducible, &mut |subset: &[usize]| {
+        let sent = original
+            .sent
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, m))| is_handshake(m) || subset.contains(i))
+            .map(|(_, m)| m.clone())
+            .collect();
+        interesting(&with_messages(sent), &mut runs)
+    });
+    let mut sent: Vec<(f64, Value)> = original
+        .sent
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, m))| is_handshake(m) || keep.contains(i))
+        .map(|(_, m)| m.clone())
+        .collect();
+
+    // 2. Shrink the document texts line by line.
+    for i in 0..sent.len() {
+        for field in text_fields(&sent[i].1) {
+            let text = match sent[i].1.pointer(&field).and_then(|t| t.as_str()) {
+                Some(t) if !t.is_empty() => t.to_string(),
+                _ => continue,
+            };
+            let lines: Vec<String> = text.split_inclusive('\n').map(|l| l.to_string()).collect();
+            let kept = ddmin(lines, &mut |subset: &[String]| {
+                let mut candidate = sent.clone();
+                *candidate[i].1.pointer_mut(&field).unwrap() = json!(subset.concat());
+                interesting(&with_messages(candidate), &mut runs)
+            });
+            *sent[i].1.pointer_mut(&field).unwrap() = json!(kept.concat());
+        }
+    }
+
+    let minimized = with_messages(sent);
+    if runs >= max_runs {
+        eprintln!(
+            "  {} stopped after --max-runs {}; the result may not be minimal",
+            style("warn").yellow(),
+            max_runs
+        );
+    }
+    // Re-run the final sequence so the written responses match it.
+    let outcome = target
+        .run(&minimized, timing, timeout)
+        .unwrap_or_else(|e| fail(format!("failed to spawn server: {}", e)));
+    if outcome.failure.is_none() {
+        eprintln!(
+            "  {} the minimized session survived a re-run; the failure may be flaky",
+            style("warn").yellow()
+        );
+    }
+
+    let out_path = match output {
+        Some(o) => PathBuf::from(o),
+        None => {
+            let p = Path::new(session_path);
+            let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("session");
+            p.with_file_name(format!("{}.min.jsonl", stem))
+        }
+    };
+    write_session(&out_path, &target, &minimized, &outcome).unwrap_or_else(|e| fail(e));
+
+    eprintln!();
+    eprintln!(
+        "  {} {} → {}, text {} → {} bytes, {} runs",
+        style("messages").dim(),
+        original.sent.len(),
+        minimized.sent.len(),
+        session_text_bytes(original),
+        session_text_bytes(&minimized),
+        runs
+    );
+    for (_, m) in &minimized.sent {
+        let method = m.get("method").and_then(|m| m.as_str()).unwrap_or("?");
+        eprintln!("    {}", method);
+    }
+    if let Some(ref f) = outcome.failure {
+        eprintln!("  {} {}", style("failure").dim(), f);
+    }
+    eprintln!("  {} {}", style("->").green(), out_path.display());
+}
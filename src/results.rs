@@ -0,0 +1,555 @@
+//! Per-server benchmark results and the `results.json` writer.
+
+use crate::config::{date_stamp, timestamp, MethodConfig, ServerConfig, SyncMode};
+use crate::helpers::ServerProbe;
+use crate::memory::Usage;
+use crate::stats::Stats;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub enum BenchResult {
+    Ok {
+        iterations: Vec<(f64, Value)>, // (ms, response json)
+        usage: Option<Usage>,          // memory and CPU after indexing
+        details: Option<Value>,        // runner-specific extras (e.g. typing breakdown)
+    },
+    Invalid {
+        first_response: Value,
+        usage: Option<Usage>,
+    },
+    Fail {
+        error: String,
+        usage: Option<Usage>,
+    },
+    /// The server lacks a provider the benchmark needs (e.g. the request
+    /// `$/cancelRequest` cancels).
+    Unsupported {
+        provider: String,
+    },
+}
+
+pub struct BenchRow {
+    pub label: String,
+    /// Latency statistics over `iterations`.
+    pub stats: Stats,
+    pub iterations: Vec<(f64, Value)>, // (ms, response json)
+    pub usage: Option<Usage>,          // memory and CPU after indexing
+    pub kind: u8,
+    pub fail_msg: String,
+    pub summary: Value,
+    pub details: Option<Value>,
+    /// Resource timeline from the background sampler, as JSON.
+    pub timeline: Option<Value>,
+}
+
+impl BenchRow {
+    pub fn to_json(&self) -> Value {
+        match self.kind {
+            0 => {
+                let iter_json: Vec<Value> = self
+                    .iterations
+                    .iter()
+                    .map(|(ms, resp)| {
+                        let mut it = json!({
+                            "ms": (ms * 100.0).round() / 100.0,
+                            "response": resp,
+                        });
+                        if self.stats.is_outlier(*ms) {
+                            it["outlier"] = json!(true);
+                        }
+                        it
+                    })
+                    .collect();
+                let round = |ms: f64| (ms * 100.0).round() / 100.0;
+                let s = &self.stats;
+                let mut obj = json!({
+                    "server": self.label,
+                    "status": "ok",
+                    "p50_ms": round(s.p50),
+                    "p95_ms": round(s.p95),
+                    "mean_ms": round(s.mean),
+                    "min_ms": round(s.min),
+                    "max_ms": round(s.max),
+                    "p90_ms": round(s.p90),
+                    "p99_ms": round(s.p99),
+                    "stddev_ms": round(s.stddev),
+                    "mad_ms": round(s.mad),
+                    "mean_ci_ms": [round(s.mean_ci.0), round(s.mean_ci.1)],
+                    "p50_ci_ms": [round(s.p50_ci.0), round(s.p50_ci.1)],
+                    "cv": (s.cv * 1000.0).round() / 1000.0,
+                    "fences_ms": [round(s.fences.0), round(s.fences.1)],
+                    "outliers": s.outliers,
+                    "trimmed_mean_ms": round(s.trimmed_mean),
+                    "trimmed_p50_ms": round(s.trimmed_p50),
+                    "trimmed_p95_ms": round(s.trimmed_p95),
+                    "iterations": iter_json,
+                    "response": self.summary,
+                });
+                self.write_resources(&mut obj);
+                if let Some(ref details) = self.details {
+                    obj["details"] = details.clone();
+                }
+                obj
+            }
+            1 => {
+                let mut obj = json!({
+                    "server": self.label,
+                    "status": "invalid",
+                    "response": self.summary,
+                });
+                self.write_resources(&mut obj);
+                obj
+            }
+            3 => json!({
+                "server": self.label,
+                "status": "unsupported",
+                "error": self.fail_msg,
+            }),
+            _ => {
+                let mut obj = json!({
+                    "server": self.label,
+                    "status": "fail",
+                    "error": self.fail_msg,
+                });
+                self.write_resources(&mut obj);
+                obj
+            }
+        }
+    }
+
+    /// Add the `rss_kb`-style fields of [`Usage`] (PSS and CPU only where
+    /// the platform reports them) and the resource timeline.
+    fn write_resources(&self, obj: &mut Value) {
+        if let Some(ref timeline) = self.timeline {
+            obj["timeline"] = timeline.clone();
+        }
+        let Some(u) = self.usage else {
+            return;
+        };
+        obj["rss_kb"] = json!(u.rss_kb);
+        if let Some(pss) = u.pss_kb {
+            obj["pss_kb"] = json!(pss);
+        }
+        if let Some(cpu) = u.cpu_ms {
+            obj["cpu_ms"] = json!(cpu);
+        }
+        obj["tree_rss_kb"] = json!(u.tree_rss_kb);
+        if let Some(pss) = u.tree_pss_kb {
+            obj["tree_pss_kb"] = json!(pss);
+        }
+        if let Some(cpu) = u.tree_cpu_ms {
+            obj["tree_cpu_ms"] = json!(cpu);
+        }
+        obj["tree_processes"] = json!(u.tree_processes);
+    }
+
+    /// Rebuild a row from its saved form, for `--resume`. Latencies are the
+    /// rounded values from the file. Statistics are the saved ones, computed
+    /// from the unrounded latencies, so saving the row again writes them
+    /// unchanged; only those missing from older files are recomputed from the
+    /// rounded latencies.
+    pub fn from_entry(entry: &ServerEntry) -> Self {
+        let latencies: Vec<f64> = entry.iterations.iter().map(|it| it.ms).collect();
+        let computed = Stats::of(&latencies);
+        let pair = |saved: Option<[f64; 2]>, computed: (f64, f64)| {
+            saved.map_or(computed, |[low, high]| (low, high))
+        };
+        let stats = Stats {
+            n: latencies.len(),
+            min: entry.min_ms.unwrap_or(computed.min),
+            max: entry.max_ms.unwrap_or(computed.max),
+            mean: entry.mean_ms.unwrap_or(computed.mean),
+            p50: entry.p50_ms.unwrap_or(computed.p50),
+            p90: entry.p90_ms.unwrap_or(computed.p90),
+            p95: entry.p95_ms.unwrap_or(computed.p95),
+            p99: entry.p99_ms.unwrap_or(computed.p99),
+            stddev: entry.stddev_ms.unwrap_or(computed.stddev),
+            mad: entry.mad_ms.unwrap_or(computed.mad),
+            mean_ci: pair(entry.mean_ci_ms, computed.mean_ci),
+            p50_ci: pair(entry.p50_ci_ms, computed.p50_ci),
+            cv: entry.cv.unwrap_or(computed.cv),
+            fences: pair(entry.fences_ms, computed.fences),
+            outliers: entry.outliers.unwrap_or(computed.outliers),
+            trimmed_mean: entry.trimmed_mean_ms.unwrap_or(computed.trimmed_mean),
+            trimmed_p50: entry.trimmed_p50_ms.unwrap_or(computed.trimmed_p50),
+            trimmed_p95: entry.trimmed_p95_ms.unwrap_or(computed.trimmed_p95),
+        };
+        BenchRow {
+            label: entry.server.clone(),
+            stats,
+            iterations: entry
+                .iterations
+                .iter()
+                .map(|it| (it.ms, it.response.clone()))
+                .collect(),
+            usage: entry.rss_kb.map(|rss_kb| Usage {
+                rss_kb,
+                pss_kb: entry.pss_kb,
+                cpu_ms: entry.cpu_ms,
+                tree_rss_kb: entry.tree_rss_kb.unwrap_or(rss_kb),
+                tree_pss_kb: entry.tree_pss_kb,
+                tree_cpu_ms: entry.tree_cpu_ms,
+                tree_processes: entry.tree_processes.unwrap_or(1),
+            }),
+            kind: match entry.status {
+                Status::Ok => 0,
+                Status::Invalid => 1,
+                Status::Fail => 2,
+                Status::Unsupported => 3,
+            },
+            fail_msg: entry.error.clone().unwrap_or_default(),
+            summary: entry.response.clone().unwrap_or(Value::Null),
+            details: entry.details.clone(),
+            timeline: entry.timeline.clone(),
+        }
+    }
+}
+
+// ── Results schema ──────────────────────────────────────────────────────────
+
+/// A `results.json` file as written by [`save_json`].
+///
+/// Fields the benchmark loop adds only in some cases (per-method overrides,
+/// runner `details`, probe data) are optional; anything runner-specific stays
+/// as raw JSON.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Results {
+    pub timestamp: String,
+    pub date: String,
+    pub settings: Settings,
+    pub servers: Vec<ServerInfo>,
+    pub benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Settings {
+    pub iterations: usize,
+    pub warmup: usize,
+    pub timeout_secs: u64,
+    pub index_timeout_secs: u64,
+    pub project: String,
+    pub file: String,
+    pub line: u32,
+    pub col: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Git ref the server was built from, when `commit` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_configuration: Option<Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registrations: Option<Vec<Value>>,
+    /// [`settings_hash`] of the run settings and this server's command and
+    /// version; `--resume` only reuses results whose hash still matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Benchmark {
+    pub name: String,
+    /// Request params sent for the benchmark, when it sends a single request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Value>,
+    pub servers: Vec<ServerEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Invalid,
+    Fail,
+    Unsupported,
+}
+
+/// One server's outcome for a benchmark: the JSON form of a [`BenchRow`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerEntry {
+    pub server: String,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p50_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p90_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p99_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stddev_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mad_ms: Option<f64>,
+    /// Bootstrap 95% confidence interval of the mean, `[low, high]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean_ci_ms: Option<[f64; 2]>,
+    /// Bootstrap 95% confidence interval of the median, `[low, high]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p50_ci_ms: Option<[f64; 2]>,
+    /// Coefficient of variation of the latencies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cv: Option<f64>,
+    /// Tukey's fences `[low, high]` outside which iterations are outliers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fences_ms: Option<[f64; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outliers: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trimmed_mean_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trimmed_p50_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trimmed_p95_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub iterations: Vec<Iteration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rss_kb: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pss_kb: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_rss_kb: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_pss_kb: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_cpu_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_processes: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// `{interval_ms, peak, mean, final, samples}` from the resource sampler.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeline: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Iteration {
+    pub ms: f64,
+    pub response: Value,
+    /// Outside the row's Tukey fences.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub outlier: bool,
+}
+
+/// Read and parse a `results.json` file.
+pub fn load_results(path: &str) -> Result<Results, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// ── Resume ──────────────────────────────────────────────────────────────────
+
+/// Stable hash of everything a server's results depend on: the run-wide
+/// `settings` plus the server's command, arguments, workspace configuration
+/// and detected version. FNV-1a over the canonical JSON, as 16 hex digits.
+pub fn settings_hash(settings: &Value, srv: &ServerConfig, version: &str) -> String {
+    let key = json!({
+        "settings": settings,
+        "cmd": srv.cmd,
+        "args": srv.args,
+        "workspace_configuration": srv.workspace_configuration,
+        "version": version,
+    });
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in key.to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Rows in `saved` that can be reused, keyed by (benchmark, server): those
+/// of servers whose saved settings hash equals the one in `hashes`.
+pub fn resumable_rows(
+    saved: &Results,
+    hashes: &HashMap<String, String>,
+) -> HashMap<(String, String), BenchRow> {
+    let current: Vec<&str> = saved
+        .servers
+        .iter()
+        .filter(|s| s.settings_hash.is_some() && s.settings_hash.as_ref() == hashes.get(&s.name))
+        .map(|s| s.name.as_str())
+        .collect();
+    let mut rows = HashMap::new();
+    for bench in &saved.benchmarks {
+        for entry in &bench.servers {
+            if current.contains(&entry.server.as_str()) {
+                rows.insert(
+                    (bench.name.clone(), entry.server.clone()),
+                    BenchRow::from_entry(entry),
+                );
+            }
+        }
+    }
+    rows
+}
+
+// ── JSON output ─────────────────────────────────────────────────────────────
+
+/// Write `results.json` into `dir` and return its path.
+pub fn save_json(
+    results: &[(&str, Option<Value>, Vec<BenchRow>)],
+    versions: &[(&str, String)],
+    servers: &[&ServerConfig],
+    n: usize,
+    w: usize,
+    timeout: &Duration,
+    index_timeout: &Duration,
+    project: &str,
+    bench_file: &str,
+    target_line: u32,
+    target_col: u32,
+    methods: &HashMap<String, MethodConfig>,
+    probes: &HashMap<String, ServerProbe>,
+    settings_hashes: &HashMap<String, String>,
+    dir: &str,
+) -> String {
+    let ts = timestamp();
+    let date = date_stamp();
+    let json_benchmarks: Vec<Value> = results
+        .iter()
+        .map(|(name, input, rows)| {
+            let mut obj = json!({
+                "name": name,
+                "servers": rows.iter().map(|r| r.to_json()).collect::<Vec<_>>(),
+            });
+            if let Some(params) = input {
+                obj["input"] = params.clone();
+            }
+            obj
+        })
+        .collect();
+    let json_servers: Vec<Value> = versions
+        .iter()
+        .map(|(label, ver)| {
+            let mut obj = json!({"name": label, "version": ver});
+            if let Some(srv) = servers.iter().find(|s| s.label == *label) {
+                if !srv.description.is_empty() {
+                    obj["description"] = json!(srv.description);
+                }
+                if !srv.link.is_empty() {
+                    obj["link"] = json!(srv.link);
+                }
+                if let Some(ref commit) = srv.commit {
+                    obj["commit"] = json!(commit);
+                }
+            }
+            if let Some(probe) = probes.get(*label) {
+                obj["capabilities"] = probe.capabilities.clone();
+                if !probe.config_exchanges.is_empty() {
+                    obj["workspace_configuration"] = json!(probe.config_exchanges);
+                }
+                if !probe.registrations.is_empty() {
+                    obj["registrations"] = json!(probe.registrations);
+                }
+            }
+            if let Some(hash) = settings_hashes.get(*label) {
+                obj["settings_hash"] = json!(hash);
+            }
+            obj
+        })
+        .collect();
+    // Build methods overrides JSON (only include non-empty)
+    let methods_json: Value = if methods.is_empty() {
+        Value::Null
+    } else {
+        let map: serde_json::Map<String, Value> = methods
+            .iter()
+            .map(|(k, v)| {
+                let mut obj = serde_json::Map::new();
+                if let Some(l) = v.line {
+                    obj.insert("line".into(), json!(l));
+                }
+                if let Some(c) = v.col {
+                    obj.insert("col".into(), json!(c));
+                }
+                if let Some(ref t) = v.trigger {
+                    obj.insert("trigger".into(), json!(t));
+                }
+                if let Some(ref n) = v.new_name {
+                    obj.insert("newName".into(), json!(n));
+                }
+                if let Some(ref cmd) = v.command {
+                    obj.insert("command".into(), json!(cmd));
+                }
+                if !v.arguments.is_empty() {
+                    obj.insert("arguments".into(), json!(v.arguments));
+                }
+                if v.wait_for_progress {
+                    obj.insert("waitForProgress".into(), json!(true));
+                }
+                if v.sync != SyncMode::Full {
+                    obj.insert("sync".into(), json!(v.sync));
+                }
+                if let Some(ref t) = v.text {
+                    obj.insert("text".into(), json!(t));
+                }
+                if !v.requests.is_empty() {
+                    obj.insert("requests".into(), json!(v.requests));
+                }
+                if let Some(ref r) = v.request {
+                    obj.insert("request".into(), json!(r));
+                }
+                if let Some(ms) = v.cancel_after_ms {
+                    obj.insert("cancelAfterMs".into(), json!(ms));
+                }
+                if let Some(k) = v.concurrency {
+                    obj.insert("concurrency".into(), json!(k));
+                }
+                (k.clone(), Value::Object(obj))
+            })
+            .collect();
+        Value::Object(map)
+    };
+    let mut settings = json!({
+        "iterations": n,
+        "warmup": w,
+        "timeout_secs": timeout.as_secs(),
+        "index_timeout_secs": index_timeout.as_secs(),
+        "project": project,
+        "file": bench_file,
+        "line": target_line,
+        "col": target_col,
+    });
+    if !methods.is_empty() {
+        settings["methods"] = methods_json;
+    }
+    let output = json!({
+        "timestamp": ts,
+        "date": date,
+        "settings": settings,
+        "servers": json_servers,
+        "benchmarks": json_benchmarks,
+    });
+    let _ = std::fs::create_dir_all(dir);
+    let path = format!("{}/results.json", dir);
+    let pretty = serde_json::to_string_pretty(&output).unwrap();
+    std::fs::write(&path, &pretty).unwrap();
+    path
+}
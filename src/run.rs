@@ -0,0 +1,570 @@
+//! Running every benchmark in a config: `lsp-bench` without a subcommand.
+
+use crate::benchmark::{execute, sandboxed, BenchContext, Registry};
+use crate::capabilities;
+use crate::config::{
+    discover_servers_file, load_config, load_server_registry, resolve_servers, ServerConfig,
+};
+use crate::expect::VerifyTally;
+use crate::helpers::{
+    available, build_from_commit, detect_version, probe_server, uri, ServerProbe,
+};
+use crate::history;
+use crate::journal;
+use crate::results::{load_results, resumable_rows, save_json, settings_hash, BenchRow};
+use crate::runners::run_bench;
+use console::style;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Options for [`run`], mirroring the `lsp-bench` command line.
+pub struct RunOptions {
+    /// Config file path.
+    pub config: String,
+    /// Server registry path (overrides auto-discovery).
+    pub servers: Option<String>,
+    /// Verify responses against `expect` fields; exit non-zero on mismatch.
+    pub verify: bool,
+    /// Show server logs.
+    pub verbose: bool,
+    /// Write wire traces to `<output>/traces/`.
+    pub trace: bool,
+    /// Reuse results from `<output>/partial/results.json` whose settings
+    /// hash still matches, and only run the rest.
+    pub resume: bool,
+}
+
+/// Run every benchmark in one config file and write its results.
+pub fn run(opts: &RunOptions) {
+    run_with(opts, &Registry::builtin());
+}
+
+/// [`run`] with benchmarks looked up in `registry` instead of the built-in
+/// set, so downstream crates can add their own.
+pub fn run_with(opts: &RunOptions, registry: &Registry) {
+    // Load config
+    let mut cfg = load_config(&opts.config);
+
+    // Load server registry and resolve string references
+    let servers_file_hint = cfg.servers_file.clone().or(opts.servers.clone());
+    let registry_path = discover_servers_file(&opts.config, servers_file_hint.as_deref());
+    let server_registry = match &registry_path {
+        Some(p) => {
+            eprintln!("  {} {}", style("servers").dim(), p.display());
+            load_server_registry(p)
+        }
+        None => HashMap::new(),
+    };
+    resolve_servers(&mut cfg.servers, &server_registry);
+    let verify = opts.verify;
+    let verbose = opts.verbose;
+
+    let n = cfg.iterations;
+    let w = cfg.warmup;
+    let timeout = Duration::from_secs(cfg.timeout);
+    let index_timeout = Duration::from_secs(cfg.index_timeout);
+    let target_line = cfg.line;
+    let target_col = cfg.col;
+    let methods = cfg.methods.clone();
+    // Support legacy trigger_character — migrate to methods map
+    let trigger_character = cfg.trigger_character.clone().or_else(|| {
+        methods
+            .get("textDocument/completion")
+            .and_then(|m| m.trigger.clone())
+    });
+    let output_dir = cfg.output;
+    let report_path = cfg.report;
+    let _report_style = cfg.report_style;
+    let response_limit = cfg.response_limit;
+    let init_settings = cfg.initialize_settings;
+    let partial_dir = format!("{}/partial", output_dir);
+    let sample_interval =
+        (cfg.sample_interval_ms > 0).then(|| Duration::from_millis(cfg.sample_interval_ms));
+    let trace_dir = if cfg.trace || opts.trace {
+        let dir = PathBuf::from(format!("{}/traces", output_dir));
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Error: {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+        Some(dir)
+    } else {
+        None
+    };
+
+    // Resolve which benchmarks to run from config
+    let benchmarks: Vec<&str> = {
+        let base: Vec<&str> =
+            if cfg.benchmarks.is_empty() || cfg.benchmarks.iter().any(|c| c == "all") {
+                registry.names()
+            } else {
+                cfg.benchmarks.iter().map(|s| s.as_str()).collect()
+            };
+        if cfg.exclude.is_empty() {
+            base
+        } else {
+            base.into_iter()
+                .filter(|b| !cfg.exclude.iter().any(|e| e == b))
+                .collect()
+        }
+    };
+
+    for b in &benchmarks {
+        if registry.get(b).is_none() {
+            eprintln!(
+                "Error: unknown benchmark '{}'. See DOCS.md for valid names.",
+                b
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let project = cfg.project.clone();
+    let cwd = PathBuf::from(&project);
+    if !cwd.exists() {
+        eprintln!("Error: project directory not found: {}", project);
+        std::process::exit(1);
+    }
+    let root = uri(&cwd);
+    let journal_path = PathBuf::from(&output_dir).join(journal::JOURNAL_FILE);
+    if let Err(e) = journal::open(&journal_path, &cwd) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    let bench_file_rel = &cfg.file;
+    let bench_sol = cwd.join(bench_file_rel);
+    if !bench_sol.exists() {
+        eprintln!("Error: benchmark file not found: {}", bench_sol.display());
+        std::process::exit(1);
+    }
+
+    eprintln!("  {} {}", style("config").dim(), opts.config);
+    eprintln!(
+        "  {} {}  (line {}, col {})",
+        style("file").dim(),
+        bench_file_rel,
+        target_line,
+        target_col
+    );
+    if let Some(ref dir) = trace_dir {
+        eprintln!("  {} {}", style("trace").dim(), dir.display());
+    }
+
+    // Build from commit if configured — mutates cmd to the built binary path
+    for srv in &mut cfg.servers {
+        if let Some(ref commit) = srv.commit {
+            let repo_path = srv.repo.as_deref().unwrap_or_else(|| {
+                eprintln!("Error: server '{}' has commit but no repo path", srv.label);
+                std::process::exit(1);
+            });
+            match build_from_commit(repo_path, commit, &srv.cmd) {
+                Ok(bin_path) => {
+                    eprintln!("  {} {} -> {}", style("built").green(), srv.label, bin_path);
+                    srv.cmd = bin_path;
+                }
+                Err(e) => {
+                    eprintln!("  {} {} -- {}", style("build failed").red(), srv.label, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    // Filter to available servers
+    cfg.servers.retain(|s| {
+        let ok = available(&s.cmd);
+        if !ok {
+            eprintln!("  {} {} -- not found", style("skip").yellow(), s.label);
+        }
+        ok
+    });
+
+    // Detect versions and resolve "latest" labels
+    eprintln!("\n{}", style("Detecting versions...").dim());
+    let mut detected_versions: Vec<String> = Vec::new();
+    for srv in &mut cfg.servers {
+        let ver = detect_version(&srv.cmd);
+        eprintln!("  {} = {}", style(&srv.label).bold(), ver);
+
+        // Replace "latest" with the actual version in the label and link
+        if srv.label.ends_with(" latest") {
+            let base = srv.label.trim_end_matches(" latest");
+            srv.label = base.to_string();
+            if !srv.link.is_empty() && !ver.is_empty() {
+                // Extract semver from version string (e.g. "solidity-language-server 0.1.24+commit..." → "0.1.24")
+                let semver = ver
+                    .split_whitespace()
+                    .last()
+                    .unwrap_or(&ver)
+                    .split('+')
+                    .next()
+                    .unwrap_or(&ver);
+                srv.link = format!(
+                    "{}/releases/tag/v{}",
+                    srv.link.trim_end_matches('/'),
+                    semver
+                );
+            }
+        }
+        detected_versions.push(ver);
+    }
+
+    let versions: Vec<(&str, String)> = cfg
+        .servers
+        .iter()
+        .zip(detected_versions)
+        .map(|(s, ver)| (s.label.as_str(), ver))
+        .collect();
+    let avail: Vec<&ServerConfig> = cfg.servers.iter().collect();
+
+    // Probe capabilities so benchmarks a server never advertised are skipped
+    // instead of burning a full timeout. Servers that fail the probe are
+    // benchmarked as before.
+    eprintln!("\n{}", style("Probing capabilities...").dim());
+    let mut probes: HashMap<String, ServerProbe> = HashMap::new();
+    for srv in &avail {
+        match probe_server(srv, &root, &cwd, init_settings.as_ref(), verbose) {
+            Ok(probe) => {
+                let supported = benchmarks
+                    .iter()
+                    .filter(|b| capabilities::supports(&probe.capabilities, b))
+                    .count();
+                eprintln!(
+                    "  {} = {}/{} benchmarks supported",
+                    style(&srv.label).bold(),
+                    supported,
+                    benchmarks.len()
+                );
+                if !probe.config_exchanges.is_empty() {
+                    eprintln!(
+                        "  {} answered {} workspace/configuration request(s)",
+                        style(&srv.label).bold(),
+                        probe.config_exchanges.len()
+                    );
+                }
+                probes.insert(srv.label.clone(), probe);
+            }
+            Err(e) => {
+                eprintln!(
+                    "  {} {} -- probe failed: {}",
+                    style("warn").yellow(),
+                    srv.label,
+                    e
+                );
+            }
+        }
+    }
+
+    // Everything the results depend on besides the server itself. A saved
+    // benchmark × server result is only resumed if this, the server's
+    // command and its version are unchanged.
+    let settings = json!({
+        "iterations": n,
+        "warmup": w,
+        "timeout": cfg.timeout,
+        "index_timeout": cfg.index_timeout,
+        "project": project,
+        "file": bench_file_rel,
+        "line": target_line,
+        "col": target_col,
+        "response": response_limit,
+        "trigger_character": trigger_character,
+        "methods": serde_json::to_value(&methods).unwrap_or_default(),
+        "initializeSettings": init_settings,
+        "sandbox": cfg.sandbox,
+        "sample_interval_ms": cfg.sample_interval_ms,
+    });
+    let settings_hashes: HashMap<String, String> = avail
+        .iter()
+        .zip(&versions)
+        .map(|(srv, (_, ver))| (srv.label.clone(), settings_hash(&settings, srv, ver)))
+        .collect();
+    let mut resumed = if opts.resume {
+        load_resumable(&partial_dir, &settings_hashes)
+    } else {
+        HashMap::new()
+    };
+
+    let ctx = BenchContext {
+        root: &root,
+        cwd: &cwd,
+        file: &bench_sol,
+        line: target_line,
+        col: target_col,
+        methods: &methods,
+        trigger_character: trigger_character.as_deref(),
+        timeout,
+        index_timeout,
+        warmup: w,
+        iterations: n,
+        response_limit,
+        init_settings: init_settings.as_ref(),
+        verbose,
+        probes: &probes,
+        registry,
+        sandbox: cfg.sandbox,
+        sandbox_clone: cfg.sandbox_clone,
+    };
+
+    let total = benchmarks.len();
+    let mut num = 0usize;
+    let mut all_results: Vec<(&str, Option<Value>, Vec<BenchRow>)> = Vec::new();
+    let mut tally = VerifyTally::new();
+
+    for bench in registry.iter() {
+        let name = bench.name();
+        if !benchmarks.contains(&name) {
+            continue;
+        }
+        num += 1;
+        eprintln!(
+            "\n{}",
+            style(format!("[{}/{}] {}", num, total, name)).bold()
+        );
+        let mut done: HashMap<&str, BenchRow> = HashMap::new();
+        for srv in &avail {
+            if let Some(row) = resumed.remove(&(name.to_string(), srv.label.clone())) {
+                eprintln!(
+                    "  {} {} (from partial results)",
+                    style("resumed").cyan(),
+                    srv.label
+                );
+                done.insert(srv.label.as_str(), row);
+            }
+        }
+        let pending: Vec<&ServerConfig> = avail
+            .iter()
+            .copied()
+            .filter(|s| !done.contains_key(s.label.as_str()))
+            .collect();
+        let mut fresh = Vec::new().into_iter();
+        let mut skipped = false;
+        if !pending.is_empty() {
+            match bench.prepare(&ctx) {
+                Err(reason) => {
+                    eprintln!("  {} {}", style("skip").yellow(), reason);
+                    // Rows resumed for other servers are still kept.
+                    if done.is_empty() {
+                        continue;
+                    }
+                    skipped = true;
+                }
+                Ok(()) => {
+                    if sandboxed(bench, &ctx) {
+                        eprintln!(
+                            "  {} fresh copy of the project per server",
+                            style("sandbox").cyan()
+                        );
+                    }
+                    fresh = run_bench(
+                        &pending,
+                        name,
+                        &probes,
+                        response_limit,
+                        trace_dir.as_deref(),
+                        sample_interval,
+                        |srv, on_progress| execute(bench, &ctx, srv, on_progress),
+                    )
+                    .into_iter();
+                    journal::checkpoint();
+                }
+            }
+        }
+        // run_bench returns one row per pending server, in order; none when
+        // the benchmark was skipped.
+        let rows: Vec<BenchRow> = avail
+            .iter()
+            .filter_map(|s| done.remove(s.label.as_str()).or_else(|| fresh.next()))
+            .collect();
+        for row in &rows {
+            if pending.iter().any(|s| s.label == row.label) {
+                warn_if_noisy(name, row, cfg.cv_threshold);
+            }
+        }
+        if verify && !skipped {
+            bench.verify(&ctx, &rows, &mut tally);
+        }
+        all_results.push((name, bench.input(&ctx), rows));
+        let p = save_json(
+            &all_results,
+            &versions,
+            &avail,
+            n,
+            w,
+            &timeout,
+            &index_timeout,
+            &project,
+            bench_file_rel,
+            target_line,
+            target_col,
+            &methods,
+            &probes,
+            &settings_hashes,
+            &partial_dir,
+        );
+        eprintln!("  {} {}", style("saved").dim(), style(&p).dim());
+    }
+
+    journal::close();
+
+    // ── Final output ─────────────────────────────────────────────────────
+
+    if !all_results.is_empty() {
+        let path = save_json(
+            &all_results,
+            &versions,
+            &avail,
+            n,
+            w,
+            &timeout,
+            &index_timeout,
+            &project,
+            bench_file_rel,
+            target_line,
+            target_col,
+            &methods,
+            &probes,
+            &settings_hashes,
+            &output_dir,
+        );
+        eprintln!("\n  {} {}", style("->").green().bold(), path);
+
+        // Clean up partial saves — the final snapshot has everything
+        let _ = std::fs::remove_dir_all(&partial_dir);
+
+        if cfg.history {
+            let dir = PathBuf::from(&output_dir).join(history::HISTORY_DIR);
+            match history::record(&dir, &path) {
+                Ok(stored) => eprintln!(
+                    "  {} {}",
+                    style("history").dim(),
+                    style(stored.display()).dim()
+                ),
+                Err(e) => eprintln!("  {} history: {}", style("warn").yellow(), e),
+            }
+        }
+
+        // Generate report if configured
+        if let Some(ref report_out) = report_path {
+            // Resolve report path relative to output_dir so session files
+            // land alongside results.json (not in the CWD)
+            let resolved_report = if std::path::Path::new(report_out.as_str()).is_relative() {
+                format!("{}/{}", output_dir, report_out)
+            } else {
+                report_out.clone()
+            };
+            let exe = std::env::current_exe().unwrap();
+            let bin_dir = exe.parent().unwrap();
+            let bin_name = "gen-report";
+            let args: Vec<&str> = vec!["--quiet", "--session", &path, "-o", &resolved_report];
+            let bin = bin_dir.join(bin_name);
+            eprintln!("  {} -> {}", style("report").dim(), resolved_report);
+            match std::process::Command::new(&bin).args(&args).status() {
+                Ok(s) if s.success() => {}
+                Ok(s) => eprintln!(
+                    "  {} {} exited with {}",
+                    style("warn").yellow(),
+                    bin_name,
+                    s
+                ),
+                Err(e) => eprintln!(
+                    "  {} {} not found: {} (run cargo build --release)",
+                    style("warn").yellow(),
+                    bin_name,
+                    e
+                ),
+            }
+        }
+    }
+
+    // ── Verify summary ────────────────────────────────────────────────
+    if verify {
+        eprintln!();
+        let total_checks = tally.passed + tally.failed;
+        if total_checks == 0 && tally.skipped > 0 {
+            eprintln!(
+                "  {} no expect fields found in config (skipped {})",
+                style("warn").yellow(),
+                tally.skipped
+            );
+        } else if tally.failed == 0 {
+            eprintln!(
+                "  {} {}/{} expectations passed",
+                style("verify").green().bold(),
+                tally.passed,
+                total_checks
+            );
+        } else {
+            eprintln!(
+                "  {} {}/{} expectations failed",
+                style("verify").red().bold(),
+                tally.failed,
+                total_checks
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Warn when a server's latencies on `bench` vary too much to trust: their
+/// coefficient of variation is above `threshold`.
+fn warn_if_noisy(bench: &str, row: &BenchRow, threshold: f64) {
+    let s = &row.stats;
+    if row.kind != 0 || threshold <= 0.0 || s.n < 2 || s.cv <= threshold {
+        return;
+    }
+    eprintln!(
+        "  {} {} is noisy on {}: CV {:.0}% over {} iterations, {} outlier(s); \
+         raise `iterations` for a steadier result",
+        style("warn").yellow(),
+        row.label,
+        bench,
+        s.cv * 100.0,
+        s.n,
+        s.outliers
+    );
+}
+
+/// Results in `<partial_dir>/results.json` that `--resume` can reuse.
+fn load_resumable(
+    partial_dir: &str,
+    settings_hashes: &HashMap<String, String>,
+) -> HashMap<(String, String), BenchRow> {
+    let path = format!("{}/results.json", partial_dir);
+    if !std::path::Path::new(&path).exists() {
+        eprintln!(
+            "  {} no partial results at {}, starting from scratch",
+            style("resume").dim(),
+            path
+        );
+        return HashMap::new();
+    }
+    let saved = match load_results(&path) {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!(
+                "  {} {} -- starting from scratch",
+                style("warn").yellow(),
+                e
+            );
+            return HashMap::new();
+        }
+    };
+    let rows = resumable_rows(&saved, settings_hashes);
+    let total: usize = saved.benchmarks.iter().map(|b| b.servers.len()).sum();
+    eprintln!(
+        "  {} {}/{} benchmark × server results from {}",
+        style("resume").dim(),
+        rows.len(),
+        total,
+        path
+    );
+    if rows.len() < total {
+        eprintln!(
+            "  {} the rest don't match the current settings, server command or version",
+            style("resume").dim()
+        );
+    }
+    rows
+}
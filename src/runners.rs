@@ -0,0 +1,2837 @@
+//! Benchmark runners, one per benchmark style, and [`run_bench`] to run one
+//! across every server.
+
+use crate::benchmark::Readiness;
+use crate::capabilities;
+use crate::client::{trace_file_part, LspClient};
+use crate::config::{CreateStep, DeleteStep, ExpectConfig, RenameStep, ServerConfig, SyncMode};
+use crate::helpers::{
+    is_diagnostics_for, is_valid_response, is_valid_response_for_method, merge_registrations,
+    response_summary, uri, ServerProbe,
+};
+use crate::journal;
+use crate::memory::{sample_usage, Sampler, Usage};
+use crate::position::{encode_positions, PositionEncoding};
+use crate::results::{BenchResult, BenchRow};
+use crate::stats::{stats, Stats};
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// ── Progress ────────────────────────────────────────────────────────────────
+
+fn spinner(label: &str) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("  {spinner:.cyan} {prefix:<20} {msg}")
+            .unwrap()
+            .tick_chars("🌑🌒🌓🌔🌕🌖🌗🌘 "),
+    );
+    pb.set_prefix(label.to_string());
+    pb.enable_steady_tick(Duration::from_millis(80));
+    pb
+}
+
+fn finish_pass(pb: &ProgressBar, mean: f64, p50: f64, p95: f64) {
+    pb.finish_with_message(format!(
+        "{}  {:.1}ms mean  ({:.1}ms p50, {:.1}ms p95)",
+        style("pass").green().bold(),
+        mean,
+        p50,
+        p95
+    ));
+}
+
+fn finish_fail(pb: &ProgressBar, msg: &str) {
+    pb.finish_with_message(format!("{}  {}", style("fail").red().bold(), msg));
+}
+
+fn finish_unsupported(pb: &ProgressBar, provider: &str) {
+    pb.finish_with_message(format!(
+        "{}  no {}",
+        style("unsupported").yellow().bold(),
+        provider
+    ));
+}
+
+fn iter_msg(i: usize, w: usize, n: usize) -> String {
+    if i < w {
+        format!("warmup {}/{}", i + 1, w)
+    } else {
+        format!("iter {}/{}", i - w + 1, n)
+    }
+}
+
+// ── Reusable benchmark runners ──────────────────────────────────────────────
+
+/// Benchmark that spawns a fresh server each iteration (e.g. spawn+init).
+///
+/// Each iteration is timed from spawn through the `initialize` response.
+/// The target file is then opened and startup broken into phases, each with
+/// its own percentiles in `details.phases`:
+///   - `first_byte`: spawn -> first byte on the server's stdout
+///   - `initialize`: `initialize` request -> response
+///   - `diagnostics`: `initialized` -> first `publishDiagnostics` for the file
+///   - `indexing`: `initialized` -> the last `$/progress` end
+///
+/// A phase the server never reaches within `index_timeout` is left out of
+/// that iteration.
+pub fn bench_spawn(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    index_timeout: Duration,
+    w: usize,
+    n: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    const PHASES: [&str; 4] = ["first_byte", "initialize", "diagnostics", "indexing"];
+    let file_uri = uri(target_file);
+    let mut iterations = Vec::new();
+    let mut peak_usage: Option<Usage> = None;
+    let mut phase_samples: [Vec<f64>; 4] = Default::default();
+    let mut runs = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+        let start = Instant::now();
+        let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+            Ok(c) => c,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: e,
+                    usage: None,
+                }
+            }
+        };
+        if let Err(e) = c.initialize(root, init_settings) {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail { error: e, usage };
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        // Sampled after the clock stops: walking the process tree isn't free.
+        if let Some(usage) = sample_usage(c.child.id()) {
+            peak_usage = Some(peak_usage.map_or(usage, |prev: Usage| prev.max(usage)));
+        }
+        on_progress(&format!("{}  {:.1}ms  indexing", iter_msg(i, w, n), ms));
+        let (diagnostics, indexed) = match c.open_file(target_file) {
+            Ok(()) => c.wait_for_startup(&file_uri, DIAGNOSTICS_SETTLE, index_timeout),
+            Err(_) => (None, None),
+        };
+        on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+        if i >= w {
+            iterations.push((ms, json!("ok")));
+            let t = &c.startup;
+            let between = |from: Option<Instant>, to: Option<Instant>| {
+                from.zip(to).map(|(from, to)| {
+                    (to.saturating_duration_since(from).as_secs_f64() * 100_000.0).round() / 100.0
+                })
+            };
+            let phases = [
+                between(Some(t.spawned), t.first_byte.get().copied()),
+                between(t.initialize_sent, t.initialize_answered),
+                between(t.initialized_sent, diagnostics),
+                between(t.initialized_sent, indexed),
+            ];
+            let mut run = json!({});
+            for ((name, ms), samples) in PHASES.iter().zip(phases).zip(&mut phase_samples) {
+                run[format!("{}_ms", name)] = json!(ms);
+                samples.extend(ms);
+            }
+            runs.push(run);
+        }
+        c.kill();
+    }
+    let mut phases = json!({});
+    for (name, samples) in PHASES.iter().zip(&mut phase_samples) {
+        if samples.is_empty() {
+            continue;
+        }
+        let (p50, p95, mean) = stats(samples);
+        phases[*name] = json!({
+            "mean_ms": (mean * 100.0).round() / 100.0,
+            "p50_ms": p50,
+            "p95_ms": p95,
+            "n": samples.len(),
+        });
+    }
+    BenchResult::Ok {
+        iterations,
+        usage: peak_usage,
+        details: Some(json!({ "phases": phases, "runs": runs })),
+    }
+}
+
+/// Benchmark that spawns fresh each iteration, measures didOpen -> diagnostics.
+pub fn bench_diagnostics(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    let mut iterations = Vec::new();
+    let mut peak_usage: Option<Usage> = None;
+    for i in 0..(w + n) {
+        on_progress(&format!("{}  waiting for diagnostics", iter_msg(i, w, n)));
+        let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+            Ok(c) => c,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: e,
+                    usage: None,
+                }
+            }
+        };
+        if let Err(e) = c.initialize(root, init_settings) {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            };
+        }
+        let start = Instant::now();
+        if let Err(e) = c.open_file(target_file) {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            };
+        }
+        match c.wait_for_valid_diagnostics(timeout) {
+            Ok(diag_info) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                // Sample RSS after indexing, before kill
+                if let Some(usage) = sample_usage(c.child.id()) {
+                    peak_usage = Some(peak_usage.map_or(usage, |prev: Usage| prev.max(usage)));
+                }
+                on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                if i >= w {
+                    let summary = response_summary(&diag_info.message, response_limit);
+                    iterations.push((ms, summary));
+                }
+            }
+            Err(e) => {
+                // Sample RSS even on timeout — server is still alive
+                let usage = sample_usage(c.child.id());
+                return BenchResult::Fail { error: e, usage };
+            }
+        }
+        c.kill();
+    }
+    BenchResult::Ok {
+        iterations,
+        usage: peak_usage,
+        details: None,
+    }
+}
+
+/// Edit-to-diagnostics benchmark: how long after a `didChange` until the
+/// server publishes updated diagnostics for the file.
+///
+/// Flow:
+///   1. Spawn server, open the target file, wait for diagnostics to settle
+///   2. For each snapshot: send its content via didChange, then time until
+///      the next `publishDiagnostics` for the file that carries the new
+///      document version, or no version at all
+///
+/// Each snapshot is one iteration. Its response is the published diagnostics
+/// array, so `expect.count` checks the number of diagnostics after the edit.
+pub fn bench_edit_diagnostics(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    snapshots: &[ResolvedSnapshot],
+    sync: SyncMode,
+    index_timeout: Duration,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    let file_uri = uri(target_file);
+    // Let follow-up publishes for the opened file land so they aren't
+    // mistaken for the response to the first edit.
+    c.wait_for_diagnostics_settle(&file_uri, DIAGNOSTICS_SETTLE, index_timeout);
+    let usage = sample_usage(c.child.id());
+    let mut prev_text = std::fs::read_to_string(target_file).unwrap_or_default();
+
+    let total = snapshots.len();
+    let mut iterations = Vec::new();
+    for (si, snap) in snapshots.iter().enumerate() {
+        let version = (si + 2) as i32; // didOpen was version 1
+        let snap_name = snap
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        on_progress(&format!("[{}/{}] didChange {}", si + 1, total, snap_name));
+        let content = match std::fs::read_to_string(&snap.path) {
+            Ok(content) => content,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("{}: {}", snap.path.display(), e),
+                    usage,
+                }
+            }
+        };
+
+        let start = Instant::now();
+        if let Err(e) = c.sync_change(&file_uri, version, &prev_text, &content, sync) {
+            return BenchResult::Fail { error: e, usage };
+        }
+        prev_text = content;
+        let deadline = start + index_timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let msg = match c.recv(remaining) {
+                Ok(m) => m,
+                Err(e) => {
+                    return BenchResult::Fail {
+                        error: format!("{}: no updated diagnostics ({})", snap_name, e),
+                        usage,
+                    }
+                }
+            };
+            if c.handle_server_request(&msg) || !is_diagnostics_for(&msg, &file_uri) {
+                continue;
+            }
+            // Publishes for an earlier version are stale. One without a
+            // version is the answer even if the diagnostics didn't change:
+            // a comment edit, or two error-free snapshots in a row.
+            let published_version = msg
+                .get("params")
+                .and_then(|p| p.get("version"))
+                .and_then(|v| v.as_i64());
+            if published_version.is_none_or(|v| v == version as i64) {
+                let diagnostics = msg
+                    .get("params")
+                    .and_then(|p| p.get("diagnostics"))
+                    .cloned()
+                    .unwrap_or_else(|| json!([]));
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                let count = diagnostics.as_array().map(|a| a.len()).unwrap_or(0);
+                on_progress(&format!(
+                    "[{}/{}] {}  {:.1}ms  ({} diagnostics)",
+                    si + 1,
+                    total,
+                    snap_name,
+                    ms,
+                    count
+                ));
+                iterations.push((ms, diagnostics));
+                break;
+            }
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// textDocument/codeAction benchmark.
+///
+/// Opens the file, waits for diagnostics (which carry the error codes), then
+/// sends `textDocument/codeAction` with those diagnostics in the context so the
+/// server can return quick-fix actions.  The server iterates
+/// `params.context.diagnostics` to build actions, so we must forward the real
+/// diagnostic objects — an empty context would always return an empty array.
+pub fn bench_code_action(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    line: u32,
+    col: u32,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    let diag_info = match c.wait_for_diagnostics_with_min(index_timeout, 1) {
+        Ok(d) => d,
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    };
+
+    // Extract the diagnostics array from the publishDiagnostics notification.
+    let diagnostics: Vec<Value> = diag_info
+        .message
+        .get("params")
+        .and_then(|p| p.get("diagnostics"))
+        .and_then(|d| d.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+
+    let mut params = json!({
+        "textDocument": { "uri": file_uri },
+        "range": {
+            "start": { "line": line, "character": col },
+            "end":   { "line": line, "character": col },
+        },
+        "context": {
+            "diagnostics": diagnostics,
+            "only": ["quickfix"],
+            "triggerKind": 1,
+        },
+    });
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    encode_positions(&mut params, &target_text, c.encoding);
+
+    let mut iterations = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+        let start = Instant::now();
+        let req_id = match c.send("textDocument/codeAction", params.clone()) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                if i >= w {
+                    let summary = response_summary(&resp, response_limit);
+                    iterations.push((ms, summary));
+                }
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Two-phase call hierarchy benchmark for `callHierarchy/incomingCalls` and
+/// `callHierarchy/outgoingCalls`.
+///
+/// Phase 1: sends `textDocument/prepareCallHierarchy` at the given position to
+/// obtain a `CallHierarchyItem`.
+/// Phase 2: sends the target method (`callHierarchy/incomingCalls` or
+/// `callHierarchy/outgoingCalls`) with the item from phase 1 as params.
+///
+/// Only phase 2 is timed.
+pub fn bench_call_hierarchy(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str, // "callHierarchy/incomingCalls" or "callHierarchy/outgoingCalls"
+    line: u32,
+    col: u32,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+    wait_for_progress_token: Option<&str>,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    match c.wait_for_valid_diagnostics(index_timeout) {
+        Ok(_) => {}
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    }
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, wait_for_progress_token);
+
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+
+    // Phase 1: prepareCallHierarchy to obtain the CallHierarchyItem
+    on_progress("prepareCallHierarchy");
+    let mut prepare_params = json!({
+        "textDocument": { "uri": file_uri },
+        "position": { "line": line, "character": col },
+    });
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    encode_positions(&mut prepare_params, &target_text, c.encoding);
+    let prepare_id = match c.send("textDocument/prepareCallHierarchy", prepare_params) {
+        Ok(id) => id,
+        Err(e) => return BenchResult::Fail { error: e, usage },
+    };
+    let prepare_resp = match c.read_response(prepare_id, timeout) {
+        Ok(r) => r,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: format!("prepareCallHierarchy failed: {}", e),
+                usage,
+            }
+        }
+    };
+
+    // Extract the first CallHierarchyItem from the response.
+    // Response is { "result": [ { ...item... } ] }
+    let item = prepare_resp
+        .get("result")
+        .and_then(|r| r.as_array())
+        .and_then(|a| a.first())
+        .cloned();
+    let item = match item {
+        Some(i) => i,
+        None => {
+            return BenchResult::Fail {
+                error: "prepareCallHierarchy returned no items".into(),
+                usage,
+            }
+        }
+    };
+
+    // Phase 2: call the target method with { "item": <CallHierarchyItem> }
+    let params = json!({ "item": item });
+    let mut iterations = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+        let start = Instant::now();
+        let req_id = match c.send(method, params.clone()) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                if i >= w {
+                    let summary = response_summary(&resp, response_limit);
+                    iterations.push((ms, summary));
+                }
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Cold-start benchmark: spawns a fresh server per iteration, measures the full
+/// end-to-end time from didOpen through diagnostics through the method response.
+/// This captures the real user experience — compilation + request latency.
+pub fn bench_lsp_method_cold(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str,
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    ready: Readiness,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+    progress_token: Option<&str>,
+) -> BenchResult {
+    let mut iterations = Vec::new();
+    let mut peak_usage: Option<Usage> = None;
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    for i in 0..(w + n) {
+        on_progress(&format!("{}  cold start", iter_msg(i, w, n)));
+        let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+            Ok(c) => c,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: e,
+                    usage: None,
+                }
+            }
+        };
+        if let Err(e) = c.initialize(root, init_settings) {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            };
+        }
+
+        let file_uri = uri(target_file);
+
+        // Start timing from didOpen — this is what the user feels
+        let start = Instant::now();
+        if let Err(e) = c.open_file(target_file) {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            };
+        }
+
+        if ready == Readiness::ProjectIndex {
+            if let Some(token) = progress_token {
+                // Wait for diagnostics first (file build from didOpen),
+                // then wait for the specific progress token (e.g.
+                // "solidity/projectIndexFull" for the full two-phase index).
+                on_progress(&format!("{}  waiting for diagnostics", iter_msg(i, w, n)));
+                match c.wait_for_valid_diagnostics(timeout) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        let usage = sample_usage(c.child.id());
+                        return BenchResult::Fail {
+                            error: format!("wait_for_diagnostics: {}", e),
+                            usage,
+                        };
+                    }
+                }
+                on_progress(&format!(
+                    "{}  waiting for progress: {}",
+                    iter_msg(i, w, n),
+                    token
+                ));
+                c.wait_for_progress_end(timeout, Some(token));
+            } else {
+                // No specific progress token — wait for diagnostics
+                // (the file build) which is sufficient for methods that
+                // only need the file build (e.g. implementation from a
+                // concrete function to its interface).
+                on_progress(&format!("{}  waiting for diagnostics", iter_msg(i, w, n)));
+                match c.wait_for_diagnostics_with_min(timeout, 1) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        let usage = sample_usage(c.child.id());
+                        return BenchResult::Fail {
+                            error: format!("wait_for_diagnostics: {}", e),
+                            usage,
+                        };
+                    }
+                }
+            }
+        } else {
+            // For methods that only need single-file data, wait for
+            // diagnostics (the single-file compile).
+            on_progress(&format!("{}  waiting for diagnostics", iter_msg(i, w, n)));
+            match c.wait_for_valid_diagnostics(timeout) {
+                Ok(_) => {}
+                Err(e) => {
+                    let usage = sample_usage(c.child.id());
+                    return BenchResult::Fail {
+                        error: format!("wait_for_diagnostics: {}", e),
+                        usage,
+                    };
+                }
+            }
+        }
+
+        // Send the actual method request
+        on_progress(&format!("{}  sending {}", iter_msg(i, w, n), method));
+        let mut params = params_fn(method, &file_uri);
+        encode_positions(&mut params, &target_text, c.encoding);
+        let req_id = match c.send(method, params) {
+            Ok(id) => id,
+            Err(e) => {
+                let usage = sample_usage(c.child.id());
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                if let Some(usage) = sample_usage(c.child.id()) {
+                    peak_usage = Some(peak_usage.map_or(usage, |prev: Usage| prev.max(usage)));
+                }
+                on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                if i >= w {
+                    let summary = response_summary(&resp, response_limit);
+                    iterations.push((ms, summary));
+                }
+            }
+            Err(e) => {
+                let usage = sample_usage(c.child.id());
+                return BenchResult::Fail { error: e, usage };
+            }
+        }
+        // Print server logs for this iteration
+        if verbose {
+            if let Ok(logs) = c.logs.lock() {
+                for log in logs.iter() {
+                    eprintln!("    {}", style(log).dim());
+                }
+            }
+        }
+        c.kill();
+    }
+    BenchResult::Ok {
+        iterations,
+        usage: peak_usage,
+        details: None,
+    }
+}
+
+/// Benchmark an LSP method on a single persistent server session.
+/// Spawns once, waits for diagnostics, then iterates the given method.
+pub fn bench_lsp_method(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str,
+    params_fn: &dyn Fn(&str, &str) -> Value, // takes (method, file_uri), returns params
+    ready: Readiness,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+    // When true, wait for a $/progress end notification after each response.
+    // Useful for workspace/executeCommand when the command triggers background
+    // work (e.g. solidity.reindex) and you want to measure end-to-end time.
+    wait_for_progress: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    match c.wait_for_valid_diagnostics(index_timeout) {
+        Ok(_) => {}
+        Err(e) => {
+            // Sample RSS even on timeout — server is still alive
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    }
+    // Requests that depend on the background project index wait for the
+    // $/progress end notification so the full index is in the cache before
+    // we send requests.
+    if ready == Readiness::ProjectIndex {
+        on_progress("waiting for project index");
+        c.wait_for_progress_end(index_timeout, None);
+    }
+
+    // Sample RSS after indexing
+    let usage = sample_usage(c.child.id());
+
+    let file_uri = uri(target_file);
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    let mut params = params_fn(method, &file_uri);
+    encode_positions(&mut params, &target_text, c.encoding);
+    let mut iterations = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let start = Instant::now();
+            let req_id = match c.send(method, params.clone()) {
+                Ok(id) => id,
+                Err(e) => return BenchResult::Fail { error: e, usage },
+            };
+            match c.read_response(req_id, timeout) {
+                Ok(resp) => {
+                    if is_valid_response_for_method(method, &resp) {
+                        if wait_for_progress {
+                            on_progress(&format!("{}  waiting for progress", iter_msg(i, w, n)));
+                            c.wait_for_progress_end(index_timeout, None);
+                        }
+                        let ms = start.elapsed().as_secs_f64() * 1000.0;
+                        on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                        if i >= w {
+                            let summary = response_summary(&resp, response_limit);
+                            iterations.push((ms, summary));
+                        }
+                        break;
+                    }
+                    if Instant::now() >= deadline {
+                        return BenchResult::Invalid {
+                            first_response: resp,
+                            usage,
+                        };
+                    }
+                }
+                Err(e) => return BenchResult::Fail { error: e, usage },
+            }
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// A resolved snapshot: absolute path + position to benchmark at.
+pub struct ResolvedSnapshot {
+    pub path: PathBuf,
+    pub line: u32,
+    pub col: u32,
+    pub expect: Option<ExpectConfig>,
+}
+
+/// Benchmark an LSP method across sequential file snapshots on a single server.
+/// Spawns once, opens the original file, waits for diagnostics, then for each
+/// snapshot: sends didChange → sends one request at that snapshot's line/col.
+/// Each snapshot is one iteration. Returns a single BenchResult with one
+/// iteration per snapshot.
+pub fn bench_lsp_snapshots(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str,
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    snapshots: &[ResolvedSnapshot],
+    sync: SyncMode,
+    index_timeout: Duration,
+    timeout: Duration,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    match c.wait_for_valid_diagnostics(index_timeout) {
+        Ok(_) => {}
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    }
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+    let mut prev_text = std::fs::read_to_string(target_file).unwrap_or_default();
+
+    let total = snapshots.len();
+    let mut iterations = Vec::new();
+    for (si, snap) in snapshots.iter().enumerate() {
+        let version = (si + 2) as i32; // didOpen was version 1
+        let snap_name = snap
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        on_progress(&format!("[{}/{}] didChange {}", si + 1, total, snap_name));
+
+        // Send the snapshot content
+        let content = match std::fs::read_to_string(&snap.path) {
+            Ok(content) => {
+                if let Err(e) = c.sync_change(&file_uri, version, &prev_text, &content, sync) {
+                    return BenchResult::Fail { error: e, usage };
+                }
+                prev_text = content.clone();
+                content
+            }
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("{}: {}", snap.path.display(), e),
+                    usage,
+                }
+            }
+        };
+
+        // Build params from the method's params_fn, then override position
+        let mut params = params_fn(method, &file_uri);
+        if let Some(obj) = params.as_object_mut() {
+            obj.insert(
+                "position".to_string(),
+                json!({ "line": snap.line, "character": snap.col }),
+            );
+        }
+        encode_positions(&mut params, &content, c.encoding);
+        let start = Instant::now();
+        let req_id = match c.send(method, params) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                let summary = response_summary(&resp, response_limit);
+                on_progress(&format!(
+                    "[{}/{}] {}  {:.1}ms{}",
+                    si + 1,
+                    total,
+                    snap_name,
+                    ms,
+                    if is_valid_response_for_method(method, &resp) {
+                        ""
+                    } else {
+                        "  (null)"
+                    }
+                ));
+                iterations.push((ms, summary));
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Quiet period after which diagnostics for a file count as settled.
+pub const DIAGNOSTICS_SETTLE: Duration = Duration::from_millis(1000);
+
+/// Simulate typing `text` at `line`/`col`, one character per keystroke.
+///
+/// Flow:
+///   1. Spawn server, open the target file, wait for diagnostics
+///   2. For each character: send an incremental didChange inserting it at the
+///      cursor, fire every method in `requests` at the new cursor position
+///      and wait for all of their responses
+///   3. After the last keystroke, wait for diagnostics on the file to settle
+///   4. Put the original content back (full didChange) before the next run
+///
+/// Each keystroke is one iteration, timed from its didChange to the last
+/// response. Per-request latencies and each run's diagnostics settle time
+/// are returned in `details`.
+pub fn bench_typing(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    text: &str,
+    requests: &[String],
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    line: u32,
+    col: u32,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    let init = match c.initialize(root, init_settings) {
+        Ok(init) => init,
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail { error: e, usage };
+        }
+    };
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+    let original = std::fs::read_to_string(target_file).unwrap_or_default();
+
+    // Only fire requests the server has a provider for, statically or
+    // registered since `initialized`.
+    let mut caps = init.get("capabilities").cloned().unwrap_or(Value::Null);
+    merge_registrations(&mut caps, &c.registrations);
+    let (active, skipped): (Vec<&str>, Vec<&str>) = requests
+        .iter()
+        .map(|r| r.as_str())
+        .partition(|r| capabilities::supports(&caps, r));
+    if active.is_empty() {
+        return BenchResult::Fail {
+            error: format!("server advertises none of {}", requests.join(", ")),
+            usage,
+        };
+    }
+    let trigger_chars: Vec<&str> = caps
+        .get("completionProvider")
+        .and_then(|p| p.get("triggerCharacters"))
+        .and_then(|t| t.as_array())
+        .map(|a| a.iter().filter_map(|ch| ch.as_str()).collect())
+        .unwrap_or_default();
+
+    let keys: Vec<char> = text.chars().collect();
+    let mut version = 1;
+    let mut iterations = Vec::new();
+    let mut runs = Vec::new();
+    for run in 0..(w + n) {
+        let mut current = original.clone();
+        let (mut cur_line, mut cur_col) = (line as usize, col as usize);
+        let mut keystrokes = Vec::new();
+        let mut last_key = Instant::now();
+        let mut last_diag: Option<Instant> = None;
+        for (ki, &ch) in keys.iter().enumerate() {
+            on_progress(&format!(
+                "{}  key {}/{}",
+                iter_msg(run, w, n),
+                ki + 1,
+                keys.len()
+            ));
+            let typed = ch.to_string();
+            let offset =
+                lsp_pos_to_byte_offset(&current, cur_line, cur_col, PositionEncoding::Utf32);
+            let mut next = current.clone();
+            next.insert(offset, ch);
+            version += 1;
+            last_key = Instant::now();
+            if let Err(e) =
+                c.sync_change(&file_uri, version, &current, &next, SyncMode::Incremental)
+            {
+                return BenchResult::Fail { error: e, usage };
+            }
+            current = next;
+            if ch == '\n' {
+                cur_line += 1;
+                cur_col = 0;
+            } else {
+                cur_col += 1;
+            }
+
+            let mut pending: HashMap<i64, &str> = HashMap::new();
+            for &method in &active {
+                let mut params = params_fn(method, &file_uri);
+                params["position"] = json!({ "line": cur_line, "character": cur_col });
+                if method == "textDocument/completion" {
+                    params["context"] = if trigger_chars.contains(&typed.as_str()) {
+                        json!({ "triggerKind": 2, "triggerCharacter": typed })
+                    } else {
+                        json!({ "triggerKind": 1 })
+                    };
+                }
+                encode_positions(&mut params, &current, c.encoding);
+                match c.send(method, params) {
+                    Ok(id) => {
+                        pending.insert(id, method);
+                    }
+                    Err(e) => return BenchResult::Fail { error: e, usage },
+                }
+            }
+
+            // Collect responses in whatever order they arrive.
+            let deadline = last_key + timeout;
+            let mut request_ms = serde_json::Map::new();
+            let mut responses = serde_json::Map::new();
+            while !pending.is_empty() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let msg = match c.recv(remaining) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        let waiting: Vec<&str> = pending.values().copied().collect();
+                        return BenchResult::Fail {
+                            error: format!(
+                                "keystroke {} ({:?}): {} waiting for {}",
+                                ki + 1,
+                                ch,
+                                e,
+                                waiting.join(", ")
+                            ),
+                            usage,
+                        };
+                    }
+                };
+                if c.handle_server_request(&msg) {
+                    continue;
+                }
+                if is_diagnostics_for(&msg, &file_uri) {
+                    last_diag = Some(Instant::now());
+                    continue;
+                }
+                let id = msg.get("id").and_then(|v| v.as_i64());
+                if let Some(method) = id.and_then(|id| pending.remove(&id)) {
+                    let ms = last_key.elapsed().as_secs_f64() * 1000.0;
+                    request_ms.insert(method.to_string(), json!((ms * 100.0).round() / 100.0));
+                    responses.insert(method.to_string(), response_summary(&msg, response_limit));
+                }
+            }
+            let ms = last_key.elapsed().as_secs_f64() * 1000.0;
+            on_progress(&format!(
+                "{}  key {}/{}  {:.1}ms",
+                iter_msg(run, w, n),
+                ki + 1,
+                keys.len(),
+                ms
+            ));
+            if run >= w {
+                iterations.push((ms, json!({ "char": typed, "responses": responses })));
+                keystrokes.push(json!({
+                    "char": typed,
+                    "ms": (ms * 100.0).round() / 100.0,
+                    "requests": request_ms,
+                }));
+            }
+        }
+
+        on_progress(&format!(
+            "{}  waiting for diagnostics to settle",
+            iter_msg(run, w, n)
+        ));
+        if let Some((t, _)) =
+            c.wait_for_diagnostics_settle(&file_uri, DIAGNOSTICS_SETTLE, index_timeout)
+        {
+            last_diag = Some(t);
+        }
+        // Only diagnostics published after the final keystroke count.
+        let settle_ms = last_diag
+            .filter(|t| *t >= last_key)
+            .map(|t| ((t - last_key).as_secs_f64() * 100_000.0).round() / 100.0);
+        if run >= w {
+            runs.push(json!({
+                "keystrokes": keystrokes,
+                "diagnostics_settle_ms": settle_ms,
+            }));
+        }
+
+        // Put the original text back for the next run.
+        if run + 1 < w + n {
+            version += 1;
+            if let Err(e) = c.did_change(&file_uri, version, &original) {
+                return BenchResult::Fail { error: e, usage };
+            }
+            c.wait_for_diagnostics_settle(&file_uri, DIAGNOSTICS_SETTLE, index_timeout);
+        }
+    }
+    let details = json!({
+        "text": text,
+        "requests": active,
+        "skipped_requests": skipped,
+        "sync": if c.sync_kind == 2 { "incremental" } else { "full" },
+        "runs": runs,
+    });
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: Some(details),
+    }
+}
+
+/// `$/cancelRequest` benchmark: does the server honour cancellation?
+///
+/// Flow:
+///   1. Spawn server, open the target file, wait for diagnostics and the
+///      project index
+///   2. Time one uncancelled `method` request as the baseline
+///   3. Each iteration: send `method`, cancel it after `cancel_after`, time
+///      until the server answers (`RequestCancelled` or a result), then time
+///      an uncancelled follow-up request to see if abandoned work delays it
+///
+/// Iteration time is cancel → response. A response that arrives before the
+/// cancel is due had nothing to cancel: it's only counted as
+/// `completed_before_cancel` in `details.outcomes`, not as an iteration, and
+/// the benchmark fails if every request finished that early.
+pub fn bench_cancel(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str,
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    cancel_after: Duration,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    // Slow requests worth cancelling depend on the project index.
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, None);
+    let usage = sample_usage(c.child.id());
+
+    let file_uri = uri(target_file);
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    let mut params = params_fn(method, &file_uri);
+    encode_positions(&mut params, &target_text, c.encoding);
+
+    // Time an uncancelled request; used for the baseline and follow-ups.
+    let timed_request = |c: &mut LspClient| -> Result<f64, String> {
+        let start = Instant::now();
+        let id = c.send(method, params.clone())?;
+        c.read_response(id, timeout)?;
+        Ok(start.elapsed().as_secs_f64() * 1000.0)
+    };
+
+    on_progress("baseline");
+    let baseline_ms = match timed_request(&mut c) {
+        Ok(ms) => ms,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: format!("baseline: {}", e),
+                usage,
+            }
+        }
+    };
+
+    let mut iterations = Vec::new();
+    let mut outcomes: HashMap<&str, usize> = HashMap::new();
+    let mut followups = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+        let id = match c.send(method, params.clone()) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        // A response before the cancel is due means there was nothing to cancel.
+        let (ms, resp, cancelled) = match c.read_response(id, cancel_after) {
+            Ok(resp) => (0.0, resp, false),
+            Err(e) if e == "timeout" => {
+                if let Err(e) = c.cancel(id) {
+                    return BenchResult::Fail { error: e, usage };
+                }
+                let start = Instant::now();
+                match c.read_response(id, timeout) {
+                    Ok(resp) => (start.elapsed().as_secs_f64() * 1000.0, resp, true),
+                    Err(e) => {
+                        return BenchResult::Fail {
+                            error: format!("no response after $/cancelRequest: {}", e),
+                            usage,
+                        }
+                    }
+                }
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        let code = resp
+            .get("error")
+            .and_then(|e| e.get("code"))
+            .and_then(|c| c.as_i64());
+        let outcome = match (cancelled, code) {
+            (false, _) => "completed_before_cancel",
+            (true, Some(-32800)) => "cancelled",
+            (true, Some(_)) => "error",
+            (true, None) => "completed",
+        };
+
+        let followup_ms = match timed_request(&mut c) {
+            Ok(ms) => ms,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("follow-up request: {}", e),
+                    usage,
+                }
+            }
+        };
+        on_progress(&format!(
+            "{}  {:.1}ms {}  (follow-up {:.1}ms)",
+            iter_msg(i, w, n),
+            ms,
+            outcome,
+            followup_ms
+        ));
+        if i >= w {
+            *outcomes.entry(outcome).or_insert(0) += 1;
+            followups.push(followup_ms);
+            if !cancelled {
+                continue;
+            }
+            iterations.push((
+                ms,
+                json!({
+                    "outcome": outcome,
+                    "code": code,
+                    "followup_ms": (followup_ms * 100.0).round() / 100.0,
+                }),
+            ));
+        }
+    }
+    c.kill();
+    if iterations.is_empty() && n > 0 {
+        return BenchResult::Fail {
+            error: format!(
+                "every request completed before the cancel was due after {}ms; lower cancelAfterMs",
+                cancel_after.as_millis()
+            ),
+            usage,
+        };
+    }
+    let followup_mean = followups.iter().sum::<f64>() / followups.len().max(1) as f64;
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: Some(json!({
+            "request": method,
+            "cancel_after_ms": cancel_after.as_millis() as u64,
+            "baseline_ms": (baseline_ms * 100.0).round() / 100.0,
+            "followup_mean_ms": (followup_mean * 100.0).round() / 100.0,
+            "outcomes": outcomes,
+        })),
+    }
+}
+
+/// Concurrent load benchmark: keep `concurrency` requests in flight.
+///
+/// Flow:
+///   1. Spawn server, open the target file, wait for diagnostics and the
+///      project index
+///   2. Warmup rounds send each method once, one at a time; one more such
+///      round is timed as the isolated latency of every method
+///   3. Cycle through `requests` until `n` requests per method have
+///      completed, topping up to `concurrency` in flight after every
+///      response and matching responses by id
+///
+/// Each completed request is one iteration. Throughput, per-method latency
+/// under load vs isolated (the head-of-line delay) and the fraction of
+/// responses that came back in send order are returned in `details`.
+pub fn bench_load(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    requests: &[String],
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    concurrency: usize,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    let init = match c.initialize(root, init_settings) {
+        Ok(init) => init,
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail { error: e, usage };
+        }
+    };
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, None);
+    let usage = sample_usage(c.child.id());
+
+    // Only load the server with requests it has a provider for, statically
+    // or registered since `initialized`.
+    let mut caps = init.get("capabilities").cloned().unwrap_or(Value::Null);
+    merge_registrations(&mut caps, &c.registrations);
+    let (active, skipped): (Vec<&str>, Vec<&str>) = requests
+        .iter()
+        .map(|r| r.as_str())
+        .partition(|r| capabilities::supports(&caps, r));
+    if active.is_empty() {
+        return BenchResult::Fail {
+            error: format!("server advertises none of {}", requests.join(", ")),
+            usage,
+        };
+    }
+    let file_uri = uri(target_file);
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    let params: Vec<Value> = active
+        .iter()
+        .map(|method| {
+            let mut p = params_fn(method, &file_uri);
+            encode_positions(&mut p, &target_text, c.encoding);
+            p
+        })
+        .collect();
+
+    // Warmup rounds, then one timed round for isolated latencies.
+    let mut isolated: Vec<f64> = vec![0.0; active.len()];
+    for round in 0..=w {
+        on_progress(&if round < w {
+            format!("warmup {}/{}", round + 1, w)
+        } else {
+            "isolated".to_string()
+        });
+        for (mi, method) in active.iter().enumerate() {
+            let start = Instant::now();
+            let result = c
+                .send(method, params[mi].clone())
+                .and_then(|id| c.read_response(id, timeout));
+            if let Err(e) = result {
+                return BenchResult::Fail {
+                    error: format!("{}: {}", method, e),
+                    usage,
+                };
+            }
+            isolated[mi] = start.elapsed().as_secs_f64() * 1000.0;
+        }
+    }
+
+    let total = n * active.len();
+    let mut in_flight: HashMap<i64, (usize, Instant)> = HashMap::new();
+    let mut send_order: VecDeque<i64> = VecDeque::new();
+    let mut per_method: Vec<Vec<f64>> = vec![Vec::new(); active.len()];
+    let mut iterations = Vec::new();
+    let mut sent = 0usize;
+    let mut in_order = 0usize;
+    let wall = Instant::now();
+    while iterations.len() < total {
+        while in_flight.len() < concurrency && sent < total {
+            let mi = sent % active.len();
+            match c.send(active[mi], params[mi].clone()) {
+                Ok(id) => {
+                    in_flight.insert(id, (mi, Instant::now()));
+                    send_order.push_back(id);
+                }
+                Err(e) => return BenchResult::Fail { error: e, usage },
+            }
+            sent += 1;
+        }
+        let msg = match c.recv(timeout) {
+            Ok(m) => m,
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("{} with {} request(s) in flight", e, in_flight.len()),
+                    usage,
+                }
+            }
+        };
+        if c.handle_server_request(&msg) {
+            continue;
+        }
+        let id = match msg.get("id").and_then(|v| v.as_i64()) {
+            Some(id) => id,
+            None => continue,
+        };
+        let (mi, start) = match in_flight.remove(&id) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let ordered = send_order.front() == Some(&id);
+        if ordered {
+            in_order += 1;
+        }
+        send_order.retain(|&x| x != id);
+        per_method[mi].push(ms);
+        let mut entry = json!({ "method": active[mi], "in_order": ordered });
+        if let Some(err) = msg.get("error") {
+            entry["error"] = err.get("code").cloned().unwrap_or(Value::Null);
+        }
+        iterations.push((ms, entry));
+        on_progress(&format!("load {}/{}  {:.1}ms", iterations.len(), total, ms));
+    }
+    let wall_secs = wall.elapsed().as_secs_f64();
+    c.kill();
+
+    let methods_json: serde_json::Map<String, Value> = active
+        .iter()
+        .enumerate()
+        .map(|(mi, method)| {
+            let (p50, p95, mean) = stats(&mut per_method[mi]);
+            (
+                method.to_string(),
+                json!({
+                    "count": per_method[mi].len(),
+                    "p50_ms": (p50 * 100.0).round() / 100.0,
+                    "p95_ms": (p95 * 100.0).round() / 100.0,
+                    "mean_ms": (mean * 100.0).round() / 100.0,
+                    "isolated_ms": (isolated[mi] * 100.0).round() / 100.0,
+                    "hol_delay_ms": ((p50 - isolated[mi]) * 100.0).round() / 100.0,
+                }),
+            )
+        })
+        .collect();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: Some(json!({
+            "concurrency": concurrency,
+            "requests": active,
+            "skipped_requests": skipped,
+            "throughput_rps": ((total as f64 / wall_secs) * 100.0).round() / 100.0,
+            "in_order_fraction": ((in_order as f64 / total as f64) * 1000.0).round() / 1000.0,
+            "methods": methods_json,
+        })),
+    }
+}
+
+/// A resolved didOpen step: absolute path + optional position override.
+pub struct ResolvedDidOpen {
+    pub path: PathBuf,
+    pub line: Option<u32>,
+    pub col: Option<u32>,
+    pub expect: Option<ExpectConfig>,
+}
+
+/// Benchmark an LSP method with sequential didOpen steps.
+///
+/// Flow:
+///   1. Spawn server, open primary file, wait for diagnostics
+///   2. Send the benchmark request (iteration 0 = baseline)
+///   3. For each didOpen step:
+///      a. Open the additional file via textDocument/didOpen
+///      b. Wait for diagnostics on the new file
+///      c. Re-send the benchmark request on the **original** file
+///   4. Each step produces one iteration in the result
+///
+/// This tests cross-file features like forward references: opening more files
+/// populates the AST cache, so the reference count should grow.
+pub fn bench_lsp_didopen(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    method: &str,
+    params_fn: &dyn Fn(&str, &str) -> Value,
+    steps: &[ResolvedDidOpen],
+    base_line: u32,
+    base_col: u32,
+    index_timeout: Duration,
+    timeout: Duration,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+    wait_for_progress_token: Option<&str>,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    match c.wait_for_valid_diagnostics(index_timeout) {
+        Ok(_) => {}
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    }
+    if wait_for_progress_token.is_some() {
+        on_progress("waiting for project index");
+        c.wait_for_progress_end(index_timeout, wait_for_progress_token);
+    }
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+    let target_text = std::fs::read_to_string(target_file).unwrap_or_default();
+    let total = steps.len() + 1; // +1 for baseline
+    let mut iterations = Vec::new();
+
+    // Iteration 0: baseline request before any didOpen
+    {
+        on_progress(&format!("[1/{}] baseline", total));
+        let mut params = params_fn(method, &file_uri);
+        encode_positions(&mut params, &target_text, c.encoding);
+        let start = Instant::now();
+        let req_id = match c.send(method, params) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                let summary = response_summary(&resp, response_limit);
+                on_progress(&format!("[1/{}] baseline  {:.1}ms", total, ms));
+                iterations.push((ms, summary));
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+
+    // Subsequent iterations: open each file, wait for diagnostics, re-request
+    for (si, step) in steps.iter().enumerate() {
+        let step_name = step
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        on_progress(&format!("[{}/{}] didOpen {}", si + 2, total, step_name));
+
+        // Open the additional file
+        if let Err(e) = c.open_file(&step.path) {
+            return BenchResult::Fail { error: e, usage };
+        }
+
+        // Wait for diagnostics on the newly opened file
+        match c.wait_for_valid_diagnostics(index_timeout) {
+            Ok(_) => {}
+            Err(e) => {
+                return BenchResult::Fail {
+                    error: format!("wait_for_diagnostics after didOpen {}: {}", step_name, e),
+                    usage,
+                };
+            }
+        }
+
+        // Re-send the benchmark request on the original file
+        let req_line = step.line.unwrap_or(base_line);
+        let req_col = step.col.unwrap_or(base_col);
+        let mut params = json!({
+            "textDocument": { "uri": &file_uri },
+            "position": { "line": req_line, "character": req_col },
+            "context": { "includeDeclaration": true },
+        });
+        encode_positions(&mut params, &target_text, c.encoding);
+        let start = Instant::now();
+        let req_id = match c.send(method, params) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                let summary = response_summary(&resp, response_limit);
+                on_progress(&format!(
+                    "[{}/{}] {}  {:.1}ms",
+                    si + 2,
+                    total,
+                    step_name,
+                    ms
+                ));
+                iterations.push((ms, summary));
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Benchmark `workspace/willRenameFiles` with a full multi-rename lifecycle.
+///
+/// Flow:
+///   1. Spawn server, open a file, wait for diagnostics + project index
+///   2. For each rename step:
+///      a. Send `workspace/willRenameFiles` — record the WorkspaceEdit response
+///      b. Apply the returned text edits to files on disk
+///      c. Rename the file on disk (oldUri → newUri)
+///      d. Send `workspace/didRenameFiles` notification
+///      e. Send `didChange` for each file that was edited (so server text_cache is updated)
+///      f. Wait for the server to re-index ($/progress end)
+///   3. After all steps, restore all files to their original state
+///
+/// Each step produces one iteration in the result, recording timing and the
+/// response (WorkspaceEdit). This tests the real-world scenario where a user
+/// renames files multiple times and each rename must work on the mutated state.
+pub fn bench_lsp_rename_sequence(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    steps: &[RenameStep],
+    index_timeout: Duration,
+    timeout: Duration,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, None);
+
+    let usage = sample_usage(c.child.id());
+    // Always run rename cycles and return to the original filename by renaming
+    // back. If the provided steps do not return to the starting file path,
+    // synthesize a final reverse step.
+    let mut run_steps: Vec<RenameStep> = steps.to_vec();
+    if !run_steps.is_empty() {
+        let original = PathBuf::from(&run_steps[0].file);
+        let original_name = original
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| run_steps[0].file.clone());
+
+        let mut current = original.clone();
+        for step in &run_steps {
+            let old_path = PathBuf::from(&step.file);
+            // Prefer the declared step source if it diverges, so we still
+            // compute a reasonable final path for mixed step sequences.
+            if old_path != current {
+                current = old_path;
+            }
+            current = current
+                .parent()
+                .map(|p| p.join(&step.new_name))
+                .unwrap_or_else(|| PathBuf::from(&step.new_name));
+        }
+
+        if current != original {
+            run_steps.push(RenameStep {
+                file: current.to_string_lossy().to_string(),
+                new_name: original_name,
+                expect: None,
+            });
+        }
+    }
+    let total = run_steps.len();
+    let mut iterations = Vec::new();
+
+    // Track file renames so we can restore at the end.
+    // Each entry: (current_path, original_path, original_content).
+    let mut restore_list: Vec<(PathBuf, PathBuf, Vec<u8>)> = Vec::new();
+    // Snapshot which rename paths existed before the sequence started.
+    // Only these paths are restored from rename_list; paths created during
+    // the sequence (e.g. intermediate rename targets) are not treated as
+    // originals.
+    let mut initially_existing: HashSet<PathBuf> = HashSet::new();
+    for step in &run_steps {
+        let old_path = cwd.join(&step.file);
+        let new_path = old_path.parent().unwrap().join(&step.new_name);
+        if old_path.exists() {
+            initially_existing.insert(old_path);
+        }
+        if new_path.exists() {
+            initially_existing.insert(new_path);
+        }
+    }
+    // Track content changes to non-renamed files so we can restore them too.
+    // Key: absolute path, Value: original content.
+    let mut content_restore: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+
+    // didChange version counter (per-file)
+    let mut versions: HashMap<String, i32> = HashMap::new();
+
+    for (si, step) in run_steps.iter().enumerate() {
+        let step_label = format!("{} → {}", step.file, step.new_name);
+        on_progress(&format!("[{}/{}] {}", si + 1, total, step_label));
+
+        let old_path = cwd.join(&step.file);
+        if !old_path.exists() {
+            // File might have been renamed in a previous step — check restore_list
+            let found = restore_list.iter().find(|(cur, _, _)| {
+                cur.file_name().map(|f| f.to_string_lossy().to_string())
+                    == old_path
+                        .file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+            });
+            if found.is_none() {
+                return BenchResult::Fail {
+                    error: format!(
+                        "rename step {}: file not found: {}",
+                        si + 1,
+                        old_path.display()
+                    ),
+                    usage,
+                };
+            }
+        }
+
+        let old_uri_str = uri(&old_path);
+        let new_path = old_path.parent().unwrap().join(&step.new_name);
+        let new_uri_str = uri(&new_path);
+
+        // Save original content for restore (only on first touch)
+        if initially_existing.contains(&old_path)
+            && !restore_list.iter().any(|(_, orig, _)| orig == &old_path)
+        {
+            if let Ok(content) = std::fs::read(&old_path) {
+                restore_list.push((old_path.clone(), old_path.clone(), content));
+            }
+        }
+
+        // 1. Send workspace/willRenameFiles
+        let params = json!({
+            "files": [{
+                "oldUri": old_uri_str,
+                "newUri": new_uri_str,
+            }]
+        });
+        let start = Instant::now();
+        let req_id = match c.send("workspace/willRenameFiles", params) {
+            Ok(id) => id,
+            Err(e) => {
+                restore_files(&restore_list, &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        let resp = match c.read_response(req_id, timeout) {
+            Ok(r) => r,
+            Err(e) => {
+                restore_files(&restore_list, &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let summary = response_summary(&resp, response_limit);
+        on_progress(&format!(
+            "[{}/{}] {}  {:.1}ms",
+            si + 1,
+            total,
+            step_label,
+            ms
+        ));
+        iterations.push((ms, summary.clone()));
+
+        // Print server logs accumulated so far
+        if verbose {
+            if let Ok(logs) = c.logs.lock() {
+                for log in logs.iter() {
+                    eprintln!("  {} {}", style("log").dim(), log);
+                }
+            }
+        }
+
+        // Print the response for debugging
+        let edit_count = resp
+            .get("result")
+            .and_then(|r| r.get("changes"))
+            .and_then(|c| c.as_object())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        if edit_count > 0 {
+            eprintln!("  {} {} file(s) with edits", style("→").green(), edit_count);
+        } else {
+            eprintln!("  {} no edits returned", style("→").yellow());
+        }
+
+        // 2. Apply the returned text edits to files on disk
+        let edits = resp
+            .get("result")
+            .and_then(|r| r.get("changes"))
+            .and_then(|c| c.as_object())
+            .cloned()
+            .unwrap_or_default();
+
+        for (file_uri, file_edits) in &edits {
+            let file_path = file_uri.strip_prefix("file://").unwrap_or(file_uri);
+            let file_path = PathBuf::from(file_path);
+
+            // Save original content for restore (only on first touch)
+            if !content_restore.contains_key(&file_path) {
+                if let Ok(orig) = std::fs::read(&file_path) {
+                    content_restore.insert(file_path.clone(), orig);
+                }
+            }
+
+            // Read current content
+            let mut content = match std::fs::read_to_string(&file_path) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!(
+                        "  {} failed to read {} for edit: {}",
+                        style("warn").yellow(),
+                        file_path.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            if let Some(edit_arr) = file_edits.as_array() {
+                content = apply_text_edits_from_json(content, edit_arr, c.encoding);
+            }
+
+            // Write the edited content back to disk
+            if let Err(e) = journal::write(&file_path, &content) {
+                eprintln!(
+                    "  {} failed to write {}: {}",
+                    style("warn").yellow(),
+                    file_path.display(),
+                    e
+                );
+            }
+
+            // Send didChange to the server so its text_cache is updated
+            let ver = versions.entry(file_uri.clone()).or_insert(1);
+            *ver += 1;
+            if let Err(e) = c.did_change(file_uri, *ver, &content) {
+                eprintln!(
+                    "  {} failed to send didChange for {}: {}",
+                    style("warn").yellow(),
+                    file_uri,
+                    e
+                );
+            }
+        }
+
+        // 3. Rename the file on disk
+        if old_path.exists() {
+            if let Err(e) = journal::rename(&old_path, &new_path) {
+                restore_files(&restore_list, &content_restore);
+                return BenchResult::Fail {
+                    error: format!("rename on disk failed: {}", e),
+                    usage,
+                };
+            }
+            // Update restore_list to track the new current location
+            for entry in &mut restore_list {
+                if entry.0 == old_path {
+                    entry.0 = new_path.clone();
+                }
+            }
+        }
+
+        // 4. Send workspace/didRenameFiles notification
+        let did_rename_params = json!({
+            "files": [{
+                "oldUri": old_uri_str,
+                "newUri": new_uri_str,
+            }]
+        });
+        if let Err(e) = c.notif("workspace/didRenameFiles", did_rename_params) {
+            restore_files(&restore_list, &content_restore);
+            return BenchResult::Fail {
+                error: format!("didRenameFiles notification failed: {}", e),
+                usage,
+            };
+        }
+
+        // 5. Wait for the server to re-index
+        on_progress(&format!(
+            "[{}/{}] {} — waiting for re-index",
+            si + 1,
+            total,
+            step_label
+        ));
+        c.wait_for_progress_end(index_timeout, None);
+    }
+
+    // Restore all files to original state
+    restore_files(&restore_list, &content_restore);
+
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Restore files to their original state after a rename sequence.
+fn restore_files(
+    rename_list: &[(PathBuf, PathBuf, Vec<u8>)],
+    content_map: &HashMap<PathBuf, Vec<u8>>,
+) {
+    // Restore renamed files: move back to original path and restore content
+    for (current_path, original_path, original_content) in rename_list {
+        if current_path != original_path && current_path.exists() {
+            let _ = journal::rename(current_path, original_path);
+        }
+        let _ = journal::write(original_path, original_content);
+    }
+    // Restore content changes to non-renamed files
+    for (path, content) in content_map {
+        let _ = journal::write(path, content);
+    }
+}
+
+/// Convert an LSP line/character position to a byte offset in UTF-8 text.
+/// `character` is counted in the code units of the negotiated `encoding`.
+fn lsp_pos_to_byte_offset(
+    text: &str,
+    line: usize,
+    character: usize,
+    encoding: PositionEncoding,
+) -> usize {
+    let mut byte_idx = 0usize;
+
+    for (current_line, l) in text.split_inclusive('\n').enumerate() {
+        if current_line == line {
+            let line_text = l.strip_suffix('\n').unwrap_or(l);
+            let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+            let mut units = 0usize;
+            let mut line_byte = 0usize;
+            for ch in line_text.chars() {
+                if units >= character {
+                    break;
+                }
+                units += encoding.unit_len(ch);
+                line_byte += ch.len_utf8();
+            }
+            return byte_idx + line_byte;
+        }
+        byte_idx += l.len();
+    }
+
+    text.len()
+}
+
+/// Apply a list of LSP TextEdits (JSON form) to UTF-8 text. Edit ranges are
+/// interpreted in the server's negotiated position `encoding`.
+fn apply_text_edits_from_json(
+    mut content: String,
+    edits_json: &[Value],
+    encoding: PositionEncoding,
+) -> String {
+    let mut edits: Vec<(usize, usize, String)> = edits_json
+        .iter()
+        .filter_map(|e| {
+            let new_text = e.get("newText")?.as_str()?.to_string();
+            let start_line = e.get("range")?.get("start")?.get("line")?.as_u64()? as usize;
+            let start_col = e.get("range")?.get("start")?.get("character")?.as_u64()? as usize;
+            let end_line = e.get("range")?.get("end")?.get("line")?.as_u64()? as usize;
+            let end_col = e.get("range")?.get("end")?.get("character")?.as_u64()? as usize;
+            let start = lsp_pos_to_byte_offset(&content, start_line, start_col, encoding);
+            let end = lsp_pos_to_byte_offset(&content, end_line, end_col, encoding);
+            Some((start, end, new_text))
+        })
+        .collect();
+
+    edits.sort_by_key(|e| std::cmp::Reverse((e.0, e.1)));
+    for (start, end, new_text) in edits {
+        if start <= end && end <= content.len() {
+            content.replace_range(start..end, &new_text);
+        }
+    }
+    content
+}
+
+/// Apply WorkspaceEdit.changes (JSON form) to disk and notify didChange.
+/// Files that don't exist yet are created. Stops at the first file that
+/// can't be edited, written or synced to the server.
+pub fn apply_workspace_changes_to_disk(
+    c: &mut LspClient,
+    edits_obj: &serde_json::Map<String, Value>,
+    content_restore: &mut HashMap<PathBuf, Vec<u8>>,
+    versions: &mut HashMap<String, i32>,
+) -> Result<(), String> {
+    for (file_uri, file_edits) in edits_obj {
+        let file_path = file_uri
+            .strip_prefix("file://")
+            .map(PathBuf::from)
+            .ok_or_else(|| format!("{} is not a file URI", file_uri))?;
+        let edits_arr = file_edits
+            .as_array()
+            .ok_or_else(|| format!("{}: edits are not an array", file_uri))?;
+
+        if !content_restore.contains_key(&file_path) {
+            if let Ok(orig) = std::fs::read(&file_path) {
+                content_restore.insert(file_path.clone(), orig);
+            }
+        }
+
+        let current = std::fs::read_to_string(&file_path).unwrap_or_default();
+        let next = apply_text_edits_from_json(current, edits_arr, c.encoding);
+
+        if let Some(parent) = file_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        journal::write(&file_path, &next).map_err(|e| format!("{}: {}", file_path.display(), e))?;
+        // Never go back behind a version a runner already sent.
+        let ver = versions.entry(file_uri.clone()).or_insert(1);
+        *ver = (*ver).max(c.doc_versions.get(file_uri).copied().unwrap_or(1)) + 1;
+        c.did_change(file_uri, *ver, &next)?;
+    }
+    Ok(())
+}
+
+pub fn bench_lsp_create_sequence(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    steps: &[CreateStep],
+    index_timeout: Duration,
+    timeout: Duration,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, None);
+
+    let usage = sample_usage(c.child.id());
+    let mut iterations = Vec::new();
+    let mut content_restore: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+    let mut created_paths: Vec<PathBuf> = Vec::new();
+    let mut versions: HashMap<String, i32> = HashMap::new();
+    let total = steps.len();
+
+    for (si, step) in steps.iter().enumerate() {
+        let new_path = cwd.join(&step.file);
+        let new_uri = uri(&new_path);
+        on_progress(&format!("[{}/{}] create {}", si + 1, total, step.file));
+
+        if new_path.exists() {
+            if !content_restore.contains_key(&new_path) {
+                if let Ok(orig) = std::fs::read(&new_path) {
+                    content_restore.insert(new_path.clone(), orig);
+                }
+            }
+            let _ = journal::remove(&new_path);
+        }
+
+        let params = json!({ "files": [{ "uri": new_uri }] });
+        let start = Instant::now();
+        let req_id = match c.send("workspace/willCreateFiles", params) {
+            Ok(id) => id,
+            Err(e) => {
+                restore_files(&[], &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        let resp = match c.read_response(req_id, timeout) {
+            Ok(r) => r,
+            Err(e) => {
+                restore_files(&[], &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        if !is_valid_response_for_method("workspace/willCreateFiles", &resp) {
+            restore_files(&[], &content_restore);
+            return BenchResult::Invalid {
+                first_response: resp,
+                usage,
+            };
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let summary = response_summary(&resp, response_limit);
+        iterations.push((ms, summary));
+
+        if let Some(changes) = resp
+            .get("result")
+            .and_then(|r| r.get("changes"))
+            .and_then(|c| c.as_object())
+        {
+            // Best effort: a bad edit shows up as a missing file or stale diagnostics.
+            let _ = apply_workspace_changes_to_disk(
+                &mut c,
+                changes,
+                &mut content_restore,
+                &mut versions,
+            );
+        }
+
+        if !new_path.exists() {
+            if let Some(parent) = new_path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = journal::write(&new_path, "");
+            created_paths.push(new_path.clone());
+        }
+
+        let did_create = json!({ "files": [{ "uri": uri(&new_path) }] });
+        let _ = c.notif("workspace/didCreateFiles", did_create);
+        c.wait_for_progress_end(index_timeout, None);
+    }
+
+    for p in created_paths {
+        let _ = journal::remove(&p);
+    }
+    restore_files(&[], &content_restore);
+
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+pub fn bench_lsp_delete_sequence(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    steps: &[DeleteStep],
+    index_timeout: Duration,
+    timeout: Duration,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    if let Err(e) = c.wait_for_valid_diagnostics(index_timeout) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail {
+            error: format!("wait_for_diagnostics: {}", e),
+            usage,
+        };
+    }
+    on_progress("waiting for project index");
+    c.wait_for_progress_end(index_timeout, None);
+
+    let usage = sample_usage(c.child.id());
+    let mut iterations = Vec::new();
+    let mut content_restore: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+    let mut versions: HashMap<String, i32> = HashMap::new();
+    let total = steps.len();
+
+    for (si, step) in steps.iter().enumerate() {
+        let del_path = cwd.join(&step.file);
+        on_progress(&format!("[{}/{}] delete {}", si + 1, total, step.file));
+
+        if !del_path.exists() {
+            restore_files(&[], &content_restore);
+            return BenchResult::Fail {
+                error: format!("delete step target missing: {}", del_path.display()),
+                usage,
+            };
+        }
+        if !content_restore.contains_key(&del_path) {
+            if let Ok(orig) = std::fs::read(&del_path) {
+                content_restore.insert(del_path.clone(), orig);
+            }
+        }
+
+        let params = json!({ "files": [{ "uri": uri(&del_path) }] });
+        let start = Instant::now();
+        let req_id = match c.send("workspace/willDeleteFiles", params) {
+            Ok(id) => id,
+            Err(e) => {
+                restore_files(&[], &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        let resp = match c.read_response(req_id, timeout) {
+            Ok(r) => r,
+            Err(e) => {
+                restore_files(&[], &content_restore);
+                return BenchResult::Fail { error: e, usage };
+            }
+        };
+        if !is_valid_response_for_method("workspace/willDeleteFiles", &resp) {
+            restore_files(&[], &content_restore);
+            return BenchResult::Invalid {
+                first_response: resp,
+                usage,
+            };
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let summary = response_summary(&resp, response_limit);
+        iterations.push((ms, summary));
+
+        if let Some(changes) = resp
+            .get("result")
+            .and_then(|r| r.get("changes"))
+            .and_then(|c| c.as_object())
+        {
+            // Best effort: a bad edit shows up as a missing file or stale diagnostics.
+            let _ = apply_workspace_changes_to_disk(
+                &mut c,
+                changes,
+                &mut content_restore,
+                &mut versions,
+            );
+        }
+
+        let _ = journal::remove(&del_path);
+        let did_delete = json!({ "files": [{ "uri": uri(&del_path) }] });
+        let _ = c.notif("workspace/didDeleteFiles", did_delete);
+        c.wait_for_progress_end(index_timeout, None);
+    }
+
+    restore_files(&[], &content_restore);
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Benchmark `textDocument/semanticTokens/full/delta`.
+///
+/// Flow:
+///   1. Spawn server, open file, wait for diagnostics
+///   2. Send `semanticTokens/full` to prime the cache and get a `resultId`
+///   3. Optionally send `didChange` snapshots to mutate the file
+///   4. For each iteration: send `semanticTokens/full/delta` with `previousResultId`,
+///      extract the new `resultId` from the response for the next iteration
+pub fn bench_lsp_delta(
+    srv: &ServerConfig,
+    root: &str,
+    cwd: &Path,
+    target_file: &Path,
+    snapshots: &[ResolvedSnapshot],
+    sync: SyncMode,
+    index_timeout: Duration,
+    timeout: Duration,
+    w: usize,
+    n: usize,
+    response_limit: usize,
+    on_progress: &dyn Fn(&str),
+    init_settings: Option<&Value>,
+    verbose: bool,
+) -> BenchResult {
+    on_progress("spawning");
+    let mut c = match LspClient::spawn_server(srv, cwd, verbose) {
+        Ok(c) => c,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: e,
+                usage: None,
+            }
+        }
+    };
+    if let Err(e) = c.initialize(root, init_settings) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    if let Err(e) = c.open_file(target_file) {
+        let usage = sample_usage(c.child.id());
+        return BenchResult::Fail { error: e, usage };
+    }
+    on_progress("waiting for diagnostics");
+    match c.wait_for_valid_diagnostics(index_timeout) {
+        Ok(_) => {}
+        Err(e) => {
+            let usage = sample_usage(c.child.id());
+            return BenchResult::Fail {
+                error: format!("wait_for_diagnostics: {}", e),
+                usage,
+            };
+        }
+    }
+    let usage = sample_usage(c.child.id());
+    let file_uri = uri(target_file);
+
+    // Step 2: Send semanticTokens/full to prime the cache
+    on_progress("priming semanticTokens/full");
+    let prime_params = json!({ "textDocument": { "uri": &file_uri } });
+    let prime_id = match c.send("textDocument/semanticTokens/full", prime_params) {
+        Ok(id) => id,
+        Err(e) => return BenchResult::Fail { error: e, usage },
+    };
+    let prime_resp = match c.read_response(prime_id, timeout) {
+        Ok(r) => r,
+        Err(e) => {
+            return BenchResult::Fail {
+                error: format!("prime semanticTokens/full: {}", e),
+                usage,
+            }
+        }
+    };
+    if !is_valid_response(&prime_resp) {
+        c.kill();
+        return BenchResult::Invalid {
+            first_response: prime_resp,
+            usage,
+        };
+    }
+    let mut result_id = prime_resp
+        .pointer("/result/resultId")
+        .and_then(|v| v.as_str())
+        .unwrap_or("0")
+        .to_string();
+
+    // Step 3: Send didChange snapshots if any
+    if !snapshots.is_empty() {
+        let mut prev_text = std::fs::read_to_string(target_file).unwrap_or_default();
+        for (si, snap) in snapshots.iter().enumerate() {
+            let version = (si + 2) as i32;
+            match std::fs::read_to_string(&snap.path) {
+                Ok(content) => {
+                    if let Err(e) = c.sync_change(&file_uri, version, &prev_text, &content, sync) {
+                        return BenchResult::Fail { error: e, usage };
+                    }
+                    prev_text = content;
+                }
+                Err(e) => {
+                    return BenchResult::Fail {
+                        error: format!("{}: {}", snap.path.display(), e),
+                        usage,
+                    }
+                }
+            }
+        }
+    }
+
+    // Step 4: Iterate delta requests
+    let mut iterations = Vec::new();
+    for i in 0..(w + n) {
+        on_progress(&iter_msg(i, w, n));
+
+        let params = json!({
+            "textDocument": { "uri": &file_uri },
+            "previousResultId": &result_id,
+        });
+        let start = Instant::now();
+        let req_id = match c.send("textDocument/semanticTokens/full/delta", params) {
+            Ok(id) => id,
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        };
+        match c.read_response(req_id, timeout) {
+            Ok(resp) => {
+                if !is_valid_response(&resp) {
+                    c.kill();
+                    return BenchResult::Invalid {
+                        first_response: resp,
+                        usage,
+                    };
+                }
+                let ms = start.elapsed().as_secs_f64() * 1000.0;
+                // Extract new resultId for next iteration (works for both full and delta responses)
+                if let Some(rid) = resp.pointer("/result/resultId").and_then(|v| v.as_str()) {
+                    result_id = rid.to_string();
+                }
+                on_progress(&format!("{}  {:.1}ms", iter_msg(i, w, n), ms));
+                if i >= w {
+                    let summary = response_summary(&resp, response_limit);
+                    iterations.push((ms, summary));
+                }
+            }
+            Err(e) => return BenchResult::Fail { error: e, usage },
+        }
+    }
+    c.kill();
+    BenchResult::Ok {
+        iterations,
+        usage,
+        details: None,
+    }
+}
+
+/// Row for a server that doesn't advertise `provider`.
+fn unsupported_row(label: &str, provider: &str) -> BenchRow {
+    BenchRow {
+        label: label.to_string(),
+        stats: Stats::default(),
+        iterations: vec![],
+        usage: None,
+        summary: Value::Null,
+        kind: 3,
+        fail_msg: format!("server does not advertise {}", provider),
+        details: None,
+        timeline: None,
+    }
+}
+
+/// Run a benchmark across all servers, showing a spinner per server.
+/// Servers whose probed capabilities don't include the provider for `method`
+/// are skipped and recorded as `unsupported`. With `trace_dir` set, each
+/// server's messages are traced to `<trace_dir>/<server>__<method>.jsonl`.
+/// With `sample_interval` set, each server's resources are sampled in the
+/// background for the whole of its run and stored as the row's timeline.
+pub fn run_bench<F>(
+    servers: &[&ServerConfig],
+    method: &str,
+    probes: &HashMap<String, ServerProbe>,
+    response_limit: usize,
+    trace_dir: Option<&Path>,
+    sample_interval: Option<Duration>,
+    f: F,
+) -> Vec<BenchRow>
+where
+    F: Fn(&ServerConfig, &dyn Fn(&str)) -> BenchResult,
+{
+    let mut rows = Vec::new();
+    for srv in servers {
+        let pb = spinner(&srv.label);
+        if let Some(probe) = probes.get(&srv.label) {
+            if !capabilities::supports(&probe.capabilities, method) {
+                let provider = capabilities::provider_for(method).unwrap_or(method);
+                finish_unsupported(&pb, provider);
+                rows.push(unsupported_row(&srv.label, provider));
+                continue;
+            }
+        }
+        let traced;
+        let srv: &ServerConfig = match trace_dir {
+            Some(dir) => {
+                let path = dir.join(format!(
+                    "{}__{}.jsonl",
+                    trace_file_part(&srv.label),
+                    trace_file_part(method)
+                ));
+                // Runners may spawn several servers; they all append to one file.
+                let _ = std::fs::remove_file(&path);
+                let mut s = (*srv).clone();
+                s.trace = Some(path);
+                traced = s;
+                &traced
+            }
+            None => srv,
+        };
+        let on_progress = |msg: &str| pb.set_message(msg.to_string());
+        let sampler = sample_interval.map(|interval| Sampler::start(interval, &srv.label));
+        let result = f(srv, &on_progress);
+        let timeline = sampler
+            .map(Sampler::finish)
+            .filter(|t| !t.samples.is_empty())
+            .map(|t| t.to_json());
+        match result {
+            BenchResult::Ok {
+                iterations,
+                usage,
+                details,
+            } => {
+                let latencies: Vec<f64> = iterations.iter().map(|(ms, _)| *ms).collect();
+                let stats = Stats::of(&latencies);
+                let summary = iterations
+                    .first()
+                    .map(|(_, s)| s.clone())
+                    .unwrap_or(Value::Null);
+                finish_pass(&pb, stats.mean, stats.p50, stats.p95);
+                rows.push(BenchRow {
+                    label: srv.label.to_string(),
+                    stats,
+                    iterations,
+                    usage,
+                    summary,
+                    kind: 0,
+                    fail_msg: String::new(),
+                    details,
+                    timeline,
+                });
+            }
+            BenchResult::Invalid {
+                first_response,
+                usage,
+            } => {
+                let summary = response_summary(&first_response, response_limit);
+                finish_fail(&pb, "invalid response");
+                rows.push(BenchRow {
+                    label: srv.label.to_string(),
+                    stats: Stats::default(),
+                    iterations: vec![],
+                    usage,
+                    summary,
+                    kind: 1,
+                    fail_msg: String::new(),
+                    details: None,
+                    timeline,
+                });
+            }
+            BenchResult::Unsupported { provider } => {
+                finish_unsupported(&pb, &provider);
+                rows.push(unsupported_row(&srv.label, &provider));
+            }
+            BenchResult::Fail { error, usage } => {
+                finish_fail(&pb, &error);
+                rows.push(BenchRow {
+                    label: srv.label.to_string(),
+                    stats: Stats::default(),
+                    iterations: vec![],
+                    usage,
+                    summary: Value::Null,
+                    kind: 2,
+                    fail_msg: error,
+                    details: None,
+                    timeline,
+                });
+            }
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::units_to_char_col;
+    use PositionEncoding::{Utf16, Utf32, Utf8};
+
+    /// `a`, a 2-byte and a 3-byte BMP character, an emoji (4 UTF-8 bytes,
+    /// 2 UTF-16 units) and `b`, with CRLF line endings.
+    const TEXT: &str = "a\u{e9}\u{4e2d}\u{1f600}b\r\nsecond\r\n";
+
+    #[test]
+    fn lsp_positions_map_to_byte_offsets() {
+        let bytes = [0, 1, 3, 6, 10, 11];
+        for (enc, units) in [
+            (Utf8, [0, 1, 3, 6, 10, 11]),
+            (Utf16, [0, 1, 2, 3, 5, 6]),
+            (Utf32, [0, 1, 2, 3, 4, 5]),
+        ] {
+            let offsets = units.map(|u| lsp_pos_to_byte_offset(TEXT, 0, u, enc));
+            assert_eq!(offsets, bytes, "{:?}", enc);
+        }
+        assert_eq!(lsp_pos_to_byte_offset(TEXT, 1, 0, Utf16), 13);
+    }
+
+    #[test]
+    fn out_of_range_positions_clamp() {
+        // Past the end of a CRLF line: before the `\r`, not between it and `\n`.
+        assert_eq!(lsp_pos_to_byte_offset(TEXT, 0, 99, Utf16), 11);
+        assert_eq!(lsp_pos_to_byte_offset(TEXT, 1, 99, Utf8), 19);
+        // Past the last line: the end of the text.
+        assert_eq!(lsp_pos_to_byte_offset(TEXT, 9, 0, Utf16), TEXT.len());
+    }
+
+    #[test]
+    fn byte_offsets_round_trip_through_every_encoding() {
+        for enc in [Utf8, Utf16, Utf32] {
+            for (line, line_text) in TEXT.lines().enumerate() {
+                let mut units = 0;
+                for (col, ch) in line_text.chars().chain(['\0']).enumerate() {
+                    let byte = lsp_pos_to_byte_offset(TEXT, line, units, enc);
+                    let line_start: usize =
+                        TEXT.split_inclusive('\n').take(line).map(str::len).sum();
+                    assert_eq!(
+                        TEXT[line_start..byte].chars().count(),
+                        col,
+                        "{:?} line {} col {}",
+                        enc,
+                        line,
+                        col
+                    );
+                    assert_eq!(
+                        units_to_char_col(TEXT, line as u32, units as u32, enc),
+                        col as u32
+                    );
+                    units += enc.unit_len(ch);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Throwaway copies of the project for benchmarks that write to it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Which benchmarks run in a sandbox (`sandbox` in the config).
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    /// Only benchmarks that rename, create, delete or edit project files.
+    #[default]
+    Auto,
+    /// Every benchmark.
+    Always,
+    /// None: mutating benchmarks edit the project and restore it afterwards.
+    Never,
+}
+
+/// How the project is cloned into a sandbox (`sandbox_clone` in the config).
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CloneMode {
+    /// Copy every file.
+    #[default]
+    Copy,
+    /// Hardlink every file. Much faster for large projects; files lsp-bench
+    /// edits are replaced rather than written in place, so the original
+    /// project is never touched.
+    Hardlink,
+}
+
+/// A clone of a project under the system temp directory, removed on drop.
+pub struct Sandbox {
+    dir: PathBuf,
+    project: PathBuf,
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Directories of sandboxes that haven't been dropped yet.
+static LIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Remove every sandbox still on disk. For exits that skip destructors,
+/// like the interrupt handler.
+pub fn remove_live_sandboxes() {
+    let dirs = std::mem::take(&mut *LIVE.lock().unwrap_or_else(|e| e.into_inner()));
+    for dir in dirs {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
+
+impl Sandbox {
+    /// Clone `project` into a fresh temp directory. The clone keeps the
+    /// project's directory name; the top-level `.git` is skipped.
+    pub fn create(project: &Path, mode: CloneMode) -> Result<Self, String> {
+        let project =
+            std::fs::canonicalize(project).map_err(|e| format!("{}: {}", project.display(), e))?;
+        let dir = std::env::temp_dir().join(format!(
+            "lsp-bench-sandbox-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let name = project.file_name().unwrap_or("project".as_ref());
+        let sandbox = Self {
+            project: dir.join(name),
+            dir,
+        };
+        std::fs::create_dir_all(&sandbox.project)
+            .map_err(|e| format!("{}: {}", sandbox.project.display(), e))?;
+        LIVE.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sandbox.dir.clone());
+        clone_dir(&project, &sandbox.project, mode, true)?;
+        Ok(sandbox)
+    }
+
+    /// The cloned project directory.
+    pub fn path(&self) -> &Path {
+        &self.project
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        LIVE.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|d| d != &self.dir);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn clone_dir(src: &Path, dst: &Path, mode: CloneMode, top: bool) -> Result<(), String> {
+    let entries = std::fs::read_dir(src).map_err(|e| format!("{}: {}", src.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("{}: {}", src.display(), e))?;
+        let from = entry.path();
+        if top && entry.file_name() == ".git" {
+            continue;
+        }
+        let to = dst.join(entry.file_name());
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("{}: {}", from.display(), e))?;
+        let result = if file_type.is_symlink() {
+            clone_symlink(&from, &to)
+        } else if file_type.is_dir() {
+            std::fs::create_dir(&to).map_err(|e| format!("{}: {}", to.display(), e))?;
+            clone_dir(&from, &to, mode, false)?;
+            Ok(())
+        } else {
+            match mode {
+                CloneMode::Copy => std::fs::copy(&from, &to).map(|_| ()),
+                CloneMode::Hardlink => std::fs::hard_link(&from, &to),
+            }
+        };
+        result.map_err(|e| format!("{}: {}", from.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn clone_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn clone_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::copy(from, to).map(|_| ())
+}
+
+/// Write `contents` to `path` through a temp file and a rename, so a
+/// hardlinked sandbox file is replaced instead of edited in place. Symlinks
+/// are written through, leaving the link itself alone.
+pub fn replace_file(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let meta = std::fs::symlink_metadata(path).ok();
+    if meta.as_ref().is_some_and(|m| m.file_type().is_symlink()) {
+        return std::fs::write(path, contents);
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.lsp-bench-tmp", name));
+    std::fs::write(&tmp, contents)?;
+    if let Some(meta) = meta {
+        let _ = std::fs::set_permissions(&tmp, meta.permissions());
+    }
+    std::fs::rename(&tmp, path)
+}
@@ -0,0 +1,267 @@
+//! Summary statistics over benchmark latencies.
+//!
+//! Percentiles interpolate linearly between the two closest ranks, so with
+//! few iterations p95 and p99 fall between the largest samples instead of
+//! being the maximum. Confidence intervals come from a bootstrap with a
+//! fixed seed: the same latencies always give the same interval.
+//!
+//! Outliers are iterations outside Tukey's fences, [`TUKEY_K`] interquartile
+//! ranges beyond the first and third quartiles: a GC pause or disk flush
+//! that hit one iteration. The `trimmed_*` statistics leave them out.
+//!
+//! [`mann_whitney_u`] tests whether two runs' latencies differ, for
+//! `lsp-bench compare`.
+
+/// Resamples drawn for each bootstrap confidence interval.
+const BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// How many interquartile ranges past the quartiles an outlier lies.
+pub const TUKEY_K: f64 = 1.5;
+
+/// Summary of one benchmark's latencies, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub n: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    /// Sample standard deviation (n - 1 denominator).
+    pub stddev: f64,
+    /// Median absolute deviation from the median, unscaled.
+    pub mad: f64,
+    /// Bootstrap 95% confidence interval of the mean.
+    pub mean_ci: (f64, f64),
+    /// Bootstrap 95% confidence interval of the median.
+    pub p50_ci: (f64, f64),
+    /// Coefficient of variation: `stddev / mean`.
+    pub cv: f64,
+    /// Tukey's fences `(low, high)`; latencies outside them are outliers.
+    pub fences: (f64, f64),
+    pub outliers: usize,
+    /// Mean, median and p95 without the outliers.
+    pub trimmed_mean: f64,
+    pub trimmed_p50: f64,
+    pub trimmed_p95: f64,
+}
+
+impl Stats {
+    /// Statistics of `samples`, or all zeros if there are none.
+    pub fn of(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = sorted.len();
+        let mean = mean_of(&sorted);
+        let p50 = percentile(&sorted, 50.0);
+        let stddev = if n > 1 {
+            (sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let mut deviations: Vec<f64> = sorted.iter().map(|x| (x - p50).abs()).collect();
+        deviations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let iqr = percentile(&sorted, 75.0) - percentile(&sorted, 25.0);
+        let fences = (
+            percentile(&sorted, 25.0) - TUKEY_K * iqr,
+            percentile(&sorted, 75.0) + TUKEY_K * iqr,
+        );
+        let kept: Vec<f64> = sorted
+            .iter()
+            .copied()
+            .filter(|&x| x >= fences.0 && x <= fences.1)
+            .collect();
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        Stats {
+            n,
+            min: sorted[0],
+            max: sorted[n - 1],
+            mean,
+            p50,
+            p90: percentile(&sorted, 90.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            stddev,
+            mad: percentile(&deviations, 50.0),
+            mean_ci: bootstrap_ci(&sorted, mean_of, &mut rng),
+            p50_ci: bootstrap_ci(&sorted, |s| percentile(s, 50.0), &mut rng),
+            cv: if mean > 0.0 { stddev / mean } else { 0.0 },
+            fences,
+            outliers: n - kept.len(),
+            trimmed_mean: mean_of(&kept),
+            trimmed_p50: percentile(&kept, 50.0),
+            trimmed_p95: percentile(&kept, 95.0),
+        }
+    }
+
+    /// Whether `ms` lies outside Tukey's fences.
+    pub fn is_outlier(&self, ms: f64) -> bool {
+        ms < self.fences.0 || ms > self.fences.1
+    }
+}
+
+/// `(p50, p95, mean)` of `samples`, which are sorted in place.
+pub fn stats(samples: &mut [f64]) -> (f64, f64, f64) {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (
+        percentile(samples, 50.0),
+        percentile(samples, 95.0),
+        mean_of(samples),
+    )
+}
+
+/// The `q`th percentile (0-100) of ascending `sorted`, interpolating
+/// linearly between the closest ranks.
+pub fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let n = sorted.len();
+    if n == 0 {
+        return 0.0;
+    }
+    let rank = q / 100.0 * (n - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+fn mean_of(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// 95% percentile-bootstrap interval of `stat` over `sorted`.
+fn bootstrap_ci(sorted: &[f64], stat: impl Fn(&[f64]) -> f64, rng: &mut Rng) -> (f64, f64) {
+    let n = sorted.len();
+    if n < 2 {
+        return (sorted[0], sorted[0]);
+    }
+    let mut resample = vec![0.0; n];
+    let mut estimates: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            for x in resample.iter_mut() {
+                *x = sorted[rng.below(n as u64) as usize];
+            }
+            resample.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            stat(&resample)
+        })
+        .collect();
+    estimates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (percentile(&estimates, 2.5), percentile(&estimates, 97.5))
+}
+
+// ── Mann-Whitney U ──────────────────────────────────────────────────────────
+
+/// Largest sample size for which [`mann_whitney_u`] computes the exact
+/// p-value; larger samples use the normal approximation.
+const EXACT_MAX: usize = 30;
+
+/// Two-sided Mann-Whitney U test of whether `a` and `b` come from the same
+/// distribution. Returns `U` for `a` and the p-value.
+///
+/// Small samples without ties get the exact p-value; otherwise the normal
+/// approximation with tie and continuity correction is used.
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let (m, n) = (a.len(), b.len());
+    if m == 0 || n == 0 {
+        return (0.0, 1.0);
+    }
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+    // Average ranks over ties, and the tie correction term.
+    let mut rank_sum_a = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        rank_sum_a += rank * all[i..=j].iter().filter(|x| x.1).count() as f64;
+        let t = (j - i + 1) as f64;
+        tie_term += t * t * t - t;
+        i = j + 1;
+    }
+    let u = rank_sum_a - (m * (m + 1)) as f64 / 2.0;
+    let mn = (m * n) as f64;
+
+    if tie_term == 0.0 && m <= EXACT_MAX && n <= EXACT_MAX {
+        let counts = u_distribution(m, n);
+        let total: f64 = counts.iter().sum();
+        let k = u.round() as usize;
+        let below: f64 = counts[..=k].iter().sum::<f64>() / total;
+        let above: f64 = counts[k..].iter().sum::<f64>() / total;
+        return (u, (2.0 * below.min(above)).min(1.0));
+    }
+
+    let len = (m + n) as f64;
+    let variance = mn / 12.0 * ((len + 1.0) - tie_term / (len * (len - 1.0)));
+    if variance <= 0.0 {
+        return (u, 1.0);
+    }
+    let z = ((u - mn / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+    (u, erfc(z / std::f64::consts::SQRT_2).min(1.0))
+}
+
+/// Smallest p-value [`mann_whitney_u`] can return for samples of `m` and
+/// `n` distinct latencies: every `a` below every `b` (or above), 2 of the
+/// C(m + n, m) orderings. With 3 iterations a side that is 0.1, so no
+/// difference is significant at 0.05.
+pub fn min_p_value(m: usize, n: usize) -> f64 {
+    if m == 0 || n == 0 {
+        return 1.0;
+    }
+    // C(m + n, m) as a running product, exact in f64 at these sizes.
+    let orderings = (1..=m).fold(1.0, |c, i| c * (n + i) as f64 / i as f64);
+    (2.0 / orderings).min(1.0)
+}
+
+/// Number of orderings of `m` + `n` distinct values giving each U from 0 to
+/// `m * n`, by the recurrence f(i, j, u) = f(i-1, j, u-j) + f(i, j-1, u).
+fn u_distribution(m: usize, n: usize) -> Vec<f64> {
+    let mut prev: Vec<Vec<f64>> = vec![vec![1.0]; n + 1];
+    for i in 1..=m {
+        let mut cur: Vec<Vec<f64>> = vec![vec![1.0]];
+        for j in 1..=n {
+            let mut f = vec![0.0; i * j + 1];
+            for (u, count) in f.iter_mut().enumerate() {
+                if u >= j {
+                    *count += prev[j].get(u - j).copied().unwrap_or(0.0);
+                }
+                *count += cur[j - 1].get(u).copied().unwrap_or(0.0);
+            }
+            cur.push(f);
+        }
+        prev = cur;
+    }
+    prev.swap_remove(n)
+}
+
+/// Complementary error function (Abramowitz & Stegun 7.1.26, error below
+/// 1.5e-7), for `x >= 0`.
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
+
+/// xorshift64: deterministic, good enough for resampling.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
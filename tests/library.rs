@@ -0,0 +1,309 @@
+//! Embedding `lsp_bench` as a library: drive the bundled `mock-lsp` server
+//! through the public client, runner and results APIs.
+
+use lsp_bench::benchmark::{BenchContext, Readiness};
+use lsp_bench::helpers::uri;
+use lsp_bench::report::{generate_competition, Metric};
+use lsp_bench::results::Status;
+use lsp_bench::runners::bench_lsp_method;
+use lsp_bench::stats::{mann_whitney_u, min_p_value, Stats};
+use lsp_bench::{
+    load_results, run_with, BenchResult, Benchmark, LspClient, Registry, RunOptions, ServerConfig,
+};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+const LSP_BENCH: &str = env!("CARGO_BIN_EXE_lsp-bench");
+const MOCK_LSP: &str = env!("CARGO_BIN_EXE_mock-lsp");
+
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lsp-bench-lib-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("A.sol"), "contract A {}\n").unwrap();
+    dir
+}
+
+#[test]
+fn client_round_trips_a_request() {
+    let dir = fixture("client");
+    let file = dir.join("A.sol");
+    let mut c = LspClient::spawn(MOCK_LSP, &[], &dir, false, None).unwrap();
+    let init = c.initialize(&uri(&dir), None).unwrap();
+    assert!(init["capabilities"]["hoverProvider"]
+        .as_bool()
+        .unwrap_or(false));
+
+    c.open_file(&file).unwrap();
+    let id = c
+        .send(
+            "textDocument/hover",
+            json!({
+                "textDocument": { "uri": uri(&file) },
+                "position": { "line": 0, "character": 9 },
+            }),
+        )
+        .unwrap();
+    let resp = c.read_response(id, Duration::from_secs(5)).unwrap();
+    assert_eq!(resp["result"]["contents"]["value"], "mock hover");
+    c.kill();
+}
+
+#[test]
+fn server_edits_report_whether_they_landed() {
+    let dir = fixture("apply-edit");
+    let file = dir.join("A.sol");
+    let mut c = LspClient::spawn(MOCK_LSP, &[], &dir, false, None).unwrap();
+    c.initialize(&uri(&dir), None).unwrap();
+    c.open_file(&file).unwrap();
+    let insert = |file_uri: &str, version: Value| {
+        json!({ "documentChanges": [{
+            "textDocument": { "uri": file_uri, "version": version },
+            "edits": [{
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+                "newText": "// x\n",
+            }],
+        }]})
+    };
+
+    let missing = format!("file://{}", dir.join("Missing.sol").display());
+    let result = c.apply_server_edit(&insert(&missing, Value::Null));
+    assert_eq!(result["applied"], false, "{}", result);
+    assert!(result["failureReason"]
+        .as_str()
+        .unwrap()
+        .contains("Missing.sol"));
+    assert!(!dir.join("Missing.sol").exists());
+
+    let result = c.apply_server_edit(&insert(&uri(&file), json!(7)));
+    assert_eq!(result["applied"], false, "{}", result);
+    assert!(result["failureReason"]
+        .as_str()
+        .unwrap()
+        .contains("version"));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "contract A {}\n");
+
+    let result = c.apply_server_edit(&insert(&uri(&file), json!(1)));
+    assert_eq!(result["applied"], true, "{}", result);
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "// x\ncontract A {}\n"
+    );
+    c.kill();
+}
+
+#[test]
+fn runner_returns_iterations() {
+    let dir = fixture("runner");
+    let srv = ServerConfig {
+        label: "mock".into(),
+        description: String::new(),
+        link: String::new(),
+        cmd: MOCK_LSP.into(),
+        args: Vec::new(),
+        commit: None,
+        repo: None,
+        workspace_configuration: None,
+        trace: None,
+    };
+    let params = |_: &str, file_uri: &str| {
+        json!({
+            "textDocument": { "uri": file_uri },
+            "position": { "line": 0, "character": 9 },
+        })
+    };
+    let result = bench_lsp_method(
+        &srv,
+        &uri(&dir),
+        &dir,
+        &dir.join("A.sol"),
+        "textDocument/hover",
+        &params,
+        Readiness::Diagnostics,
+        Duration::from_secs(5),
+        Duration::from_secs(5),
+        1,
+        4,
+        0,
+        &|_| {},
+        None,
+        false,
+        false,
+    );
+    match result {
+        BenchResult::Ok { iterations, .. } => assert_eq!(iterations.len(), 4),
+        _ => panic!("hover benchmark did not succeed"),
+    }
+}
+
+#[test]
+fn results_file_parses_into_schema() {
+    let dir = fixture("schema");
+    std::fs::write(
+        dir.join("bench.yaml"),
+        format!(
+            "project: .\nfile: A.sol\nline: 0\ncol: 9\niterations: 2\nwarmup: 0\n\
+             output: out\nbenchmarks: [initialize, textDocument/hover]\n\
+             servers:\n  - label: mock\n    cmd: {}\n",
+            MOCK_LSP
+        ),
+    )
+    .unwrap();
+    let out = Command::new(LSP_BENCH)
+        .args(["-c", "bench.yaml"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let results = load_results(dir.join("out/results.json").to_str().unwrap()).unwrap();
+    assert_eq!(results.settings.iterations, 2);
+    assert_eq!(results.servers[0].name, "mock");
+    assert_eq!(results.benchmarks.len(), 2);
+    for b in &results.benchmarks {
+        let entry = &b.servers[0];
+        assert_eq!(entry.status, Status::Ok, "{}", b.name);
+        assert_eq!(entry.iterations.len(), 2, "{}", b.name);
+        assert!(entry.p50_ms.is_some());
+        let [lo, hi] = entry.mean_ci_ms.unwrap();
+        assert!(entry.min_ms.unwrap() <= lo && hi <= entry.max_ms.unwrap());
+    }
+}
+
+#[test]
+fn stats_interpolate_percentiles() {
+    let samples: Vec<f64> = (1..=10).map(f64::from).collect();
+    let s = Stats::of(&samples);
+    assert_eq!((s.min, s.max, s.mean, s.p50), (1.0, 10.0, 5.5, 5.5));
+    assert!((s.p95 - 9.55).abs() < 1e-9, "p95 {}", s.p95);
+    assert!((s.p99 - 9.91).abs() < 1e-9, "p99 {}", s.p99);
+    assert!((s.stddev - 3.0277).abs() < 1e-4, "stddev {}", s.stddev);
+    assert_eq!(s.mad, 2.5);
+    assert!(s.mean_ci.0 < 5.5 && 5.5 < s.mean_ci.1, "{:?}", s.mean_ci);
+    assert_eq!(Stats::of(&samples), s);
+}
+
+#[test]
+fn stats_trim_tukey_outliers() {
+    let samples = [5.0, 5.2, 4.9, 5.1, 5.0, 48.0, 5.3, 4.8];
+    let s = Stats::of(&samples);
+    assert_eq!(s.outliers, 1);
+    assert!(s.is_outlier(48.0) && !s.is_outlier(5.3));
+    assert!((s.trimmed_mean - 5.0428).abs() < 1e-3, "{}", s.trimmed_mean);
+    assert!(s.trimmed_p95 < 5.3 && s.p95 > 20.0);
+    assert!(s.cv > 1.0, "cv {}", s.cv);
+}
+
+#[test]
+fn mann_whitney_separates_shifted_samples() {
+    let a = [1.0, 2.0, 3.0, 4.0, 5.0];
+    let b = [6.0, 7.0, 8.0, 9.0, 10.0];
+    // Exact: 2 of the C(10, 5) = 252 orderings are this extreme.
+    let (u, p) = mann_whitney_u(&a, &b);
+    assert_eq!(u, 0.0);
+    assert!((p - 2.0 / 252.0).abs() < 1e-12, "p {}", p);
+    assert!((min_p_value(5, 5) - p).abs() < 1e-12);
+    assert!((min_p_value(3, 3) - 0.1).abs() < 1e-12);
+    assert_eq!(min_p_value(0, 5), 1.0);
+    // Ties fall back to the normal approximation.
+    let (_, p) = mann_whitney_u(&[1.0, 2.0, 3.0, 3.0], &[1.0, 2.0, 3.0, 3.0]);
+    assert!(p > 0.9, "p {}", p);
+    let interleaved = [1.5, 3.5, 5.5, 7.5, 9.5];
+    let (_, p) = mann_whitney_u(&a.map(|x| x * 2.0), &interleaved);
+    assert!(p > 0.5, "p {}", p);
+}
+
+#[test]
+fn report_ranks_by_chosen_metric() {
+    // `steady` has the lower p95, `spiky` the lower median.
+    let entry = |name: &str, ms: &[f64]| {
+        let iterations: Vec<Value> = ms
+            .iter()
+            .map(|ms| json!({ "ms": ms, "response": "ok" }))
+            .collect();
+        json!({ "server": name, "status": "ok", "iterations": iterations, "response": "ok" })
+    };
+    let data = json!({
+        "servers": [{ "name": "steady", "version": "1" }, { "name": "spiky", "version": "1" }],
+        "benchmarks": [{
+            "name": "initialize",
+            "servers": [
+                entry("steady", &[5.0, 5.0, 5.0, 5.0]),
+                entry("spiky", &[2.0, 2.0, 2.0, 50.0]),
+            ],
+        }],
+    });
+    let p95 = generate_competition(&data, "results.json", Metric::P95);
+    assert!(p95.contains("| Server | p95 |"));
+    let p50 = generate_competition(&data, "results.json", Metric::P50);
+    assert!(p50.contains("| Server | p50 |"));
+    assert!(p50.contains("| **spiky** | 2.0ms \u{26a1} |"), "{}", p50);
+    assert!(p95.contains("| **steady** | 5.0ms \u{26a1} |"), "{}", p95);
+}
+
+/// Hover under another name, recording only the hover text.
+struct HoverText;
+
+impl Benchmark for HoverText {
+    fn name(&self) -> &str {
+        "hover-text"
+    }
+
+    fn method(&self) -> &str {
+        "textDocument/hover"
+    }
+
+    fn params(&self, _ctx: &BenchContext, file_uri: &str) -> Value {
+        json!({
+            "textDocument": { "uri": file_uri },
+            "position": { "line": 0, "character": 9 },
+        })
+    }
+
+    fn summarize(&self, response: Value) -> Value {
+        response["contents"]["value"].clone()
+    }
+}
+
+#[test]
+fn registered_benchmark_runs_by_name() {
+    let dir = fixture("registry");
+    std::fs::write(
+        dir.join("bench.yaml"),
+        format!(
+            "project: {0}\nfile: A.sol\niterations: 2\nwarmup: 0\n\
+             output: {0}/out\nbenchmarks: [hover-text]\n\
+             servers:\n  - label: mock\n    cmd: {1}\n",
+            dir.display(),
+            MOCK_LSP
+        ),
+    )
+    .unwrap();
+    let mut registry = Registry::builtin();
+    registry.register(HoverText);
+    run_with(
+        &RunOptions {
+            config: dir.join("bench.yaml").to_string_lossy().to_string(),
+            servers: None,
+            verify: false,
+            verbose: false,
+            trace: false,
+            resume: false,
+        },
+        &registry,
+    );
+
+    let results = load_results(dir.join("out/results.json").to_str().unwrap()).unwrap();
+    let entry = &results.benchmarks[0].servers[0];
+    assert_eq!(results.benchmarks[0].name, "hover-text");
+    assert_eq!(entry.status, Status::Ok);
+    assert_eq!(entry.iterations[0].response, "mock hover");
+    let input = results.benchmarks[0].input.as_ref().unwrap().to_string();
+    assert!(input.contains("textDocument/hover"), "{}", input);
+}
@@ -0,0 +1,914 @@
+//! End-to-end tests: run real configs through `lsp-bench` against the
+//! bundled `mock-lsp` server.
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const LSP_BENCH: &str = env!("CARGO_BIN_EXE_lsp-bench");
+const MOCK_LSP: &str = env!("CARGO_BIN_EXE_mock-lsp");
+const GEN_REPORT: &str = env!("CARGO_BIN_EXE_gen-report");
+
+const SOURCE: &str = "contract A {\n    function f() public {}\n}\n";
+
+/// A fresh working directory with a one-file project in `proj/`.
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lsp-bench-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("proj")).unwrap();
+    std::fs::write(dir.join("proj/A.sol"), SOURCE).unwrap();
+    dir
+}
+
+/// Write `script` as the mock's script and a config running `benchmarks`
+/// against it, with `extra` appended to the config.
+fn write_config(dir: &Path, benchmarks: &str, script: &str, extra: &str) {
+    std::fs::write(dir.join("mock.yaml"), script).unwrap();
+    let config = format!(
+        "project: proj\n\
+         file: A.sol\n\
+         line: 1\n\
+         col: 13\n\
+         iterations: 3\n\
+         warmup: 1\n\
+         timeout: 5\n\
+         index_timeout: 5\n\
+         output: out\n\
+         benchmarks: {}\n\
+         servers:\n  \
+           - label: mock\n    \
+             cmd: {}\n    \
+             args: [--script, {}]\n\
+         {}",
+        benchmarks,
+        MOCK_LSP,
+        dir.join("mock.yaml").display(),
+        extra
+    );
+    std::fs::write(dir.join("bench.yaml"), config).unwrap();
+}
+
+fn lsp_bench(dir: &Path, args: &[&str]) -> Output {
+    Command::new(LSP_BENCH)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// Poll `ready` until it holds, failing the test after 20s.
+fn wait_for(what: &str, ready: impl Fn() -> bool) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while !ready() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "timed out waiting for {}",
+            what
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
+fn results(dir: &Path) -> Value {
+    let text = std::fs::read_to_string(dir.join("out/results.json")).unwrap();
+    serde_json::from_str(&text).unwrap()
+}
+
+/// The mock server's row for benchmark `name`.
+fn row<'a>(results: &'a Value, name: &str) -> &'a Value {
+    results["benchmarks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|b| b["name"] == name)
+        .unwrap_or_else(|| panic!("no {} benchmark in results", name))
+        .get("servers")
+        .and_then(|s| s.get(0))
+        .unwrap()
+}
+
+#[test]
+fn every_benchmark_succeeds_against_default_script() {
+    let dir = fixture("all");
+    std::fs::write(dir.join("proj/A2.sol"), SOURCE.replace("f()", "g()")).unwrap();
+    // References answer slowly enough for $/cancelRequest to cancel them.
+    write_config(
+        &dir,
+        "[all]",
+        "methods:\n  textDocument/references:\n    latency_ms: 100\n",
+        "methods:\n  \
+           workspace/executeCommand:\n    \
+             command: mock.command\n  \
+           textDocument/didChange:\n    \
+             didChange:\n      \
+               - file: A2.sol\n",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let results = results(&dir);
+    let benchmarks = results["benchmarks"].as_array().unwrap();
+    assert!(benchmarks.len() > 30);
+    for b in benchmarks {
+        let srv = &b["servers"][0];
+        assert_eq!(srv["status"], "ok", "{}: {}", b["name"], srv);
+    }
+}
+
+#[test]
+fn unchanged_unversioned_diagnostics_answer_the_edit() {
+    let dir = fixture("edit-same");
+    std::fs::write(dir.join("proj/A2.sol"), SOURCE.replace("{}", "{ }")).unwrap();
+    write_config(
+        &dir,
+        "[textDocument/didChange]",
+        "unversioned_diagnostics: true\n",
+        "methods:\n  \
+           textDocument/didChange:\n    \
+             didChange:\n      \
+               - file: A2.sol\n      \
+               - file: A.sol\n",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    let edit = row(&results, "textDocument/didChange");
+    assert_eq!(edit["status"], "ok", "{}", edit);
+    assert_eq!(edit["iterations"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn scripted_latency_shows_in_timings() {
+    let dir = fixture("latency");
+    write_config(
+        &dir,
+        "[textDocument/hover, textDocument/definition]",
+        "seed: 7\nmethods:\n  textDocument/hover:\n    latency_ms: 40\n    jitter_ms: 10\n",
+        "",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let results = results(&dir);
+    let hover = row(&results, "textDocument/hover");
+    let definition = row(&results, "textDocument/definition");
+    assert!(hover["p50_ms"].as_f64().unwrap() >= 40.0, "{}", hover);
+    assert!(
+        definition["p50_ms"].as_f64().unwrap() < 40.0,
+        "{}",
+        definition
+    );
+}
+
+#[test]
+fn canned_response_is_recorded_and_verified() {
+    let dir = fixture("verify");
+    write_config(
+        &dir,
+        "[textDocument/references]",
+        "methods:\n  \
+           textDocument/references:\n    \
+             result:\n      \
+               - { uri: $uri, range: { start: { line: 1, character: 13 }, end: { line: 1, character: 14 } } }\n      \
+               - { uri: $uri, range: { start: { line: 2, character: 0 }, end: { line: 2, character: 1 } } }\n",
+        "methods:\n  textDocument/references:\n    expect:\n      count: 2\n",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml", "--verify"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let results = results(&dir);
+    let refs = row(&results, "textDocument/references");
+    let uri = refs["response"][0]["uri"].as_str().unwrap();
+    assert!(uri.ends_with("/proj/A.sol"), "{}", uri);
+
+    // The same script fails a stricter expectation.
+    let config = std::fs::read_to_string(dir.join("bench.yaml")).unwrap();
+    std::fs::write(
+        dir.join("bench.yaml"),
+        config.replace("count: 2", "count: 3"),
+    )
+    .unwrap();
+    let out = lsp_bench(&dir, &["-c", "bench.yaml", "--verify"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn error_responses_are_invalid() {
+    let dir = fixture("error");
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "methods:\n  textDocument/hover:\n    error: { code: -32603, message: boom }\n",
+        "",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    let hover = row(&results, "textDocument/hover");
+    assert_eq!(hover["status"], "invalid", "{}", hover);
+}
+
+#[test]
+fn missing_capability_is_unsupported() {
+    let dir = fixture("caps");
+    write_config(
+        &dir,
+        "[textDocument/hover, textDocument/definition]",
+        "capabilities:\n  textDocumentSync: 1\n  definitionProvider: true\n",
+        "",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    assert_eq!(row(&results, "textDocument/hover")["status"], "unsupported");
+    assert_eq!(row(&results, "textDocument/definition")["status"], "ok");
+}
+
+#[test]
+fn typing_without_any_provider_fails() {
+    let dir = fixture("typing-caps");
+    write_config(
+        &dir,
+        "[typing]",
+        "capabilities:\n  textDocumentSync: 2\n",
+        "methods:\n  typing:\n    text: \"ab\"\n",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    let typing = row(&results, "typing");
+    assert_eq!(typing["status"], "fail", "{}", typing);
+    assert_eq!(
+        typing["error"],
+        "server advertises none of textDocument/completion"
+    );
+}
+
+#[test]
+fn registered_providers_count_for_typing_and_load() {
+    let dir = fixture("registered");
+    write_config(
+        &dir,
+        "[typing, load]",
+        "capabilities:\n  textDocumentSync: 2\n\
+         registrations:\n  - { id: c1, method: textDocument/completion }\n",
+        "methods:\n  \
+           typing:\n    text: \"ab\"\n  \
+           load:\n    requests: [textDocument/completion]\n",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let results = results(&dir);
+    for name in ["typing", "load"] {
+        let r = row(&results, name);
+        assert_eq!(r["status"], "ok", "{}", r);
+    }
+    let typing = row(&results, "typing");
+    assert_eq!(
+        typing["details"]["skipped_requests"],
+        serde_json::json!([]),
+        "{}",
+        typing
+    );
+}
+
+#[test]
+fn crash_on_nth_request_fails_benchmark() {
+    let dir = fixture("crash");
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "methods:\n  textDocument/hover:\n    crash_on: 2\n",
+        "",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    let hover = row(&results, "textDocument/hover");
+    assert_eq!(hover["status"], "fail", "{}", hover);
+}
+
+#[test]
+fn cancel_fails_when_nothing_was_left_to_cancel() {
+    let dir = fixture("cancel-early");
+    write_config(&dir, "[$/cancelRequest]", "{}", "");
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let results = results(&dir);
+    let cancel = row(&results, "$/cancelRequest");
+    assert_eq!(cancel["status"], "fail", "{}", cancel);
+    assert!(
+        cancel["error"]
+            .as_str()
+            .unwrap()
+            .contains("lower cancelAfterMs"),
+        "{}",
+        cancel
+    );
+}
+
+#[test]
+fn progress_and_diagnostics_are_published() {
+    let dir = fixture("progress");
+    write_config(
+        &dir,
+        "[textDocument/diagnostic]",
+        "progress:\n  duration_ms: 50\n\
+         diagnostics:\n  \
+           - { range: { start: { line: 0, character: 0 }, end: { line: 0, character: 8 } }, severity: 2, message: one }\n  \
+           - { range: { start: { line: 1, character: 4 }, end: { line: 1, character: 12 } }, severity: 1, message: two }\n",
+        "",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let results = results(&dir);
+    let diag = row(&results, "textDocument/diagnostic");
+    assert_eq!(diag["status"], "ok", "{}", diag);
+    assert!(diag.to_string().contains("\"two\""), "{}", diag);
+}
+
+#[test]
+fn trace_replays_against_mock() {
+    let dir = fixture("replay");
+    write_config(&dir, "[textDocument/hover]", "{}", "");
+    let out = lsp_bench(&dir, &["-c", "bench.yaml", "--trace"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let trace = dir.join("out/traces/mock__textDocument_hover.jsonl");
+    assert!(trace.exists());
+
+    let out = lsp_bench(
+        &dir,
+        &[
+            "replay",
+            "--session",
+            trace.to_str().unwrap(),
+            "--timing",
+            "compressed",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    assert!(stderr.contains("0 differ, 0 missing"), "{}", stderr);
+}
+
+#[test]
+fn minimize_shrinks_crashing_session() {
+    let dir = fixture("minimize");
+    // Crashes on the third hover: the warmup and first iteration survive.
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "methods:\n  textDocument/hover:\n    crash_on: 3\n",
+        "",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml", "--trace"]);
+    let trace = dir.join("out/traces/mock__textDocument_hover.jsonl");
+    let min = dir.join("min.jsonl");
+
+    let out = lsp_bench(
+        &dir,
+        &[
+            "minimize",
+            "--session",
+            trace.to_str().unwrap(),
+            "--output",
+            min.to_str().unwrap(),
+            "--timeout",
+            "2",
+        ],
+    );
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let sent: Vec<String> = std::fs::read_to_string(&min)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap())
+        .filter(|e| e["dir"] == "send")
+        .map(|e| e["message"]["method"].as_str().unwrap_or("").to_string())
+        .collect();
+    assert_eq!(
+        sent,
+        [
+            "initialize",
+            "initialized",
+            "textDocument/hover",
+            "textDocument/hover",
+            "textDocument/hover"
+        ]
+    );
+
+    // The reproducer still crashes the server on replay.
+    let out = lsp_bench(&dir, &["replay", "--session", min.to_str().unwrap()]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn sandbox_keeps_project_intact_when_killed() {
+    let dir = fixture("sandbox");
+    write_config(
+        &dir,
+        "[workspace/willRenameFiles]",
+        "methods:\n  workspace/willRenameFiles:\n    latency_ms: 1500\n",
+        "methods:\n  \
+           workspace/willRenameFiles:\n    \
+             renameSteps:\n      \
+               - { file: A.sol, newName: B.sol }\n      \
+               - { file: B.sol, newName: C.sol }\n",
+    );
+    // Kill lsp-bench once the first rename has been applied in the sandbox,
+    // which the trace shows as its didRenameFiles.
+    let mut child = Command::new(LSP_BENCH)
+        .args(["-c", "bench.yaml", "--trace"])
+        .current_dir(&dir)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let trace = dir.join("out/traces/mock__workspace_willRenameFiles.jsonl");
+    wait_for("the first rename", || {
+        std::fs::read_to_string(&trace).is_ok_and(|t| t.contains("workspace/didRenameFiles"))
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(dir.join("proj/A.sol").exists());
+    assert!(!dir.join("proj/B.sol").exists());
+}
+
+#[test]
+fn requests_that_can_push_edits_are_sandboxed() {
+    let dir = fixture("sandbox-edits");
+    write_config(
+        &dir,
+        "[workspace/executeCommand, textDocument/codeAction, textDocument/hover]",
+        "{}\n",
+        "",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    assert_eq!(
+        stderr.matches("fresh copy of the project").count(),
+        2,
+        "{}",
+        stderr
+    );
+}
+
+/// A rename-sequence config editing the project in place, slow enough to be
+/// interrupted between its two renames.
+fn write_unsandboxed_rename_config(dir: &Path) {
+    write_config(
+        dir,
+        "[workspace/willRenameFiles]",
+        "methods:\n  workspace/willRenameFiles:\n    latency_ms: 1500\n",
+        "sandbox: never\n\
+         methods:\n  \
+           workspace/willRenameFiles:\n    \
+             renameSteps:\n      \
+               - { file: A.sol, newName: B.sol }\n      \
+               - { file: B.sol, newName: C.sol }\n",
+    );
+}
+
+/// Wait until the first rename of the config above is on disk and journaled.
+fn wait_for_first_rename(dir: &Path) {
+    wait_for("the first rename", || {
+        dir.join("proj/B.sol").exists()
+            && std::fs::metadata(dir.join("out/journal.jsonl")).is_ok_and(|m| m.len() > 0)
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupt_restores_project() {
+    signal_restores_project("interrupt", "-INT");
+}
+
+#[cfg(unix)]
+#[test]
+fn terminate_restores_project() {
+    signal_restores_project("terminate", "-TERM");
+}
+
+/// Send `signal` to lsp-bench mid-rename and check the project is restored.
+#[cfg(unix)]
+fn signal_restores_project(name: &str, signal: &str) {
+    let dir = fixture(name);
+    write_unsandboxed_rename_config(&dir);
+    let mut child = Command::new(LSP_BENCH)
+        .args(["-c", "bench.yaml"])
+        .current_dir(&dir)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_first_rename(&dir);
+    Command::new("kill")
+        .args([signal, &child.id().to_string()])
+        .status()
+        .unwrap();
+    let status = child.wait().unwrap();
+
+    assert_eq!(status.code(), Some(130));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("proj/A.sol")).unwrap(),
+        SOURCE
+    );
+    assert!(!dir.join("proj/B.sol").exists());
+    assert!(!dir.join("out/journal.jsonl").exists());
+}
+
+#[test]
+fn restore_replays_journal_after_hard_kill() {
+    let dir = fixture("restore");
+    write_unsandboxed_rename_config(&dir);
+    let mut child = Command::new(LSP_BENCH)
+        .args(["-c", "bench.yaml"])
+        .current_dir(&dir)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_first_rename(&dir);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(dir.join("proj/B.sol").exists());
+    assert!(
+        std::fs::metadata(dir.join("out/journal.jsonl"))
+            .unwrap()
+            .len()
+            > 0
+    );
+
+    // A new run refuses to start over the unrestored project.
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("lsp-bench restore"));
+
+    let out = lsp_bench(&dir, &["-c", "bench.yaml", "restore"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("proj/A.sol")).unwrap(),
+        SOURCE
+    );
+    assert!(!dir.join("proj/B.sol").exists());
+    assert!(!dir.join("out/journal.jsonl").exists());
+}
+
+#[test]
+fn resume_skips_completed_benchmarks() {
+    let dir = fixture("resume");
+    write_config(
+        &dir,
+        "[initialize, textDocument/hover, textDocument/references]",
+        "methods:\n  textDocument/references:\n    latency_ms: 3000\n",
+        "",
+    );
+    // Kill lsp-bench once the first two benchmarks are in the partial file.
+    let mut child = Command::new(LSP_BENCH)
+        .args(["-c", "bench.yaml"])
+        .current_dir(&dir)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let partial_path = dir.join("out/partial/results.json");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    let partial = loop {
+        assert!(std::time::Instant::now() < deadline, "no partial results");
+        let saved: Option<Value> = std::fs::read_to_string(&partial_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        if let Some(saved) = saved.filter(|s| s["benchmarks"].as_array().unwrap().len() == 2) {
+            break saved;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    write_config(
+        &dir,
+        "[initialize, textDocument/hover, textDocument/references]",
+        "{}\n",
+        "",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml", "--resume"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("2/2 benchmark × server results"),
+        "{}",
+        stderr
+    );
+
+    let r = results(&dir);
+    assert_eq!(r["benchmarks"].as_array().unwrap().len(), 3);
+    // Saved statistics carry over as is, not recomputed from rounded
+    // latencies.
+    assert_eq!(
+        row(&r, "textDocument/hover"),
+        &partial["benchmarks"][1]["servers"][0]
+    );
+    assert_eq!(row(&r, "textDocument/references")["status"], "ok");
+    assert!(r["servers"][0]["settings_hash"].is_string());
+    assert!(!partial_path.exists());
+}
+
+#[test]
+fn memory_covers_worker_processes() {
+    let dir = fixture("workers");
+    write_config(&dir, "[textDocument/hover]", "workers: 2\n", "");
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let r = results(&dir);
+    let hover = row(&r, "textDocument/hover");
+    assert_eq!(hover["tree_processes"], 3);
+    let rss = hover["rss_kb"].as_u64().unwrap();
+    assert!(hover["tree_rss_kb"].as_u64().unwrap() > rss);
+    if cfg!(target_os = "linux") {
+        assert!(hover["tree_pss_kb"].as_u64().unwrap() >= hover["pss_kb"].as_u64().unwrap());
+        assert!(hover["tree_cpu_ms"].is_u64());
+    }
+}
+
+#[test]
+fn timeline_samples_resources_over_the_run() {
+    let dir = fixture("timeline");
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "latency_ms: 100\nworkers: 1\n",
+        "sample_interval_ms: 50\n",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let r = results(&dir);
+    let timeline = &row(&r, "textDocument/hover")["timeline"];
+    assert_eq!(timeline["interval_ms"], 50);
+    let samples = timeline["samples"].as_array().unwrap();
+    assert!(samples.len() > 3, "{} samples", samples.len());
+    assert!(samples.iter().any(|s| s["processes"] == 2));
+    let peak = timeline["peak"]["rss_kb"].as_u64().unwrap();
+    assert!(peak >= timeline["mean"]["rss_kb"].as_u64().unwrap());
+    assert!(peak >= timeline["final"]["rss_kb"].as_u64().unwrap());
+    if cfg!(target_os = "linux") {
+        assert!(timeline["peak"]["threads"].as_u64().unwrap() >= 1);
+        assert!(timeline["peak"]["fds"].as_u64().unwrap() >= 3);
+    }
+
+    // The sampler is on by default.
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "latency_ms: 100\nworkers: 1\n",
+        "",
+    );
+    lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let r = results(&dir);
+    let hover = row(&r, "textDocument/hover");
+    assert_eq!(hover["timeline"]["interval_ms"], 250, "{}", hover);
+}
+
+#[test]
+fn initialize_breaks_startup_into_phases() {
+    let dir = fixture("phases");
+    write_config(&dir, "[initialize]", "progress:\n  duration_ms: 300\n", "");
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let r = results(&dir);
+    let init = row(&r, "initialize");
+    let phases = &init["details"]["phases"];
+    for name in ["first_byte", "initialize", "diagnostics", "indexing"] {
+        assert_eq!(phases[name]["n"], 3, "{}: {}", name, phases);
+    }
+    assert!(phases["indexing"]["p50_ms"].as_f64().unwrap() >= 300.0);
+    assert!(phases["initialize"]["p50_ms"].as_f64().unwrap() < 300.0);
+    assert_eq!(init["details"]["runs"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn noisy_latencies_warn() {
+    let dir = fixture("noisy");
+    write_config(
+        &dir,
+        "[textDocument/hover]",
+        "seed: 3\nmethods:\n  textDocument/hover:\n    latency_ms: 2\n    jitter_ms: 60\n",
+        "cv_threshold: 0.05\n",
+    );
+    let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("mock is noisy on textDocument/hover"),
+        "{}",
+        stderr
+    );
+
+    let r = results(&dir);
+    let hover = row(&r, "textDocument/hover");
+    assert!(hover["cv"].as_f64().unwrap() > 0.05, "{}", hover);
+    assert!(hover["trimmed_mean_ms"].is_f64());
+    assert_eq!(hover["fences_ms"].as_array().unwrap().len(), 2);
+}
+
+/// A results.json with one server's hover and definition latencies; an empty
+/// list records a failure.
+fn write_results(path: &Path, hover: &[f64], definition: &[f64]) {
+    let entry = |ms: &[f64]| {
+        if ms.is_empty() {
+            return serde_json::json!({ "server": "mock", "status": "fail", "error": "timeout" });
+        }
+        let iterations: Vec<Value> = ms
+            .iter()
+            .map(|ms| serde_json::json!({ "ms": ms, "response": null }))
+            .collect();
+        serde_json::json!({ "server": "mock", "status": "ok", "iterations": iterations })
+    };
+    let results = serde_json::json!({
+        "timestamp": "2026-01-01T00:00:00Z",
+        "date": "2026-01-01",
+        "settings": {
+            "iterations": hover.len(), "warmup": 0, "timeout_secs": 5, "index_timeout_secs": 5,
+            "project": "proj", "file": "A.sol", "line": 1, "col": 13,
+        },
+        "servers": [{ "name": "mock", "version": "1" }],
+        "benchmarks": [
+            { "name": "textDocument/hover", "servers": [entry(hover)] },
+            { "name": "textDocument/definition", "servers": [entry(definition)] },
+        ],
+    });
+    std::fs::write(path, results.to_string()).unwrap();
+}
+
+#[test]
+fn compare_exits_non_zero_on_regression() {
+    let dir = fixture("compare");
+    let fast = [5.0, 5.1, 4.9, 5.2, 5.0, 5.1];
+    let slow = [7.0, 7.2, 6.9, 7.1, 7.3, 7.0];
+    write_results(&dir.join("base.json"), &fast, &fast);
+    write_results(&dir.join("slow.json"), &slow, &fast);
+    write_results(&dir.join("broken.json"), &fast, &[]);
+
+    let out = lsp_bench(&dir, &["compare", "base.json", "slow.json"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(1), "{}", stdout);
+    assert!(
+        stdout.contains("1 regressed, 0 improved, 1 unchanged"),
+        "{}",
+        stdout
+    );
+
+    // The other way round is an improvement and passes.
+    let out = lsp_bench(&dir, &["compare", "slow.json", "base.json"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("0 regressed, 1 improved, 1 unchanged"),
+        "{}",
+        stdout
+    );
+
+    // A slowdown below the threshold isn't a regression.
+    let out = lsp_bench(
+        &dir,
+        &["compare", "base.json", "slow.json", "--regression", "50"],
+    );
+    assert!(out.status.success());
+
+    // A benchmark that stopped passing is.
+    let out = lsp_bench(&dir, &["compare", "base.json", "broken.json"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("broke"), "{}", stdout);
+
+    // So is one the head run dropped.
+    let mut dropped: Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("base.json")).unwrap()).unwrap();
+    dropped["benchmarks"].as_array_mut().unwrap().pop();
+    std::fs::write(dir.join("dropped.json"), dropped.to_string()).unwrap();
+    let out = lsp_bench(&dir, &["compare", "base.json", "dropped.json"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("(1 missing from head)"), "{}", stdout);
+    assert!(
+        !String::from_utf8_lossy(&out.stderr).contains("too few iterations"),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    // Three iterations a side can't reach p < 0.05, however far apart.
+    write_results(&dir.join("few.json"), &fast[..3], &fast[..3]);
+    write_results(&dir.join("few-slow.json"), &slow[..3], &fast[..3]);
+    let out = lsp_bench(&dir, &["compare", "few.json", "few-slow.json"]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("2 pair(s) have too few iterations"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("p >= 0.100"), "{}", stderr);
+}
+
+#[test]
+fn history_keeps_every_run_for_trend() {
+    let dir = fixture("history");
+    write_config(&dir, "[textDocument/hover]", "seed: 7\n", "");
+    for _ in 0..2 {
+        let out = lsp_bench(&dir, &["-c", "bench.yaml"]);
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+
+    let history = dir.join("out/history");
+    let index = std::fs::read_to_string(history.join("index.jsonl")).unwrap();
+    let entries: Vec<Value> = index
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2, "{}", index);
+    assert_ne!(entries[0]["file"], entries[1]["file"]);
+    for entry in &entries {
+        assert!(entry["servers"]["mock"]["version"].is_string(), "{}", entry);
+        assert!(history.join(entry["file"].as_str().unwrap()).exists());
+    }
+
+    let out = Command::new(GEN_REPORT)
+        .args(["--trend", "--quiet", "out", "-o", "TREND.md"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let trend = std::fs::read_to_string(dir.join("TREND.md")).unwrap();
+    assert!(trend.contains("2 run(s) from"), "{}", trend);
+    assert!(trend.contains("## textDocument/hover"), "{}", trend);
+    assert!(trend.contains("### mock"), "{}", trend);
+    let rows = trend.lines().filter(|l| l.starts_with("| 20")).count();
+    assert_eq!(rows, 2, "{}", trend);
+
+    // Runs are picked from the index by server and version.
+    let trend_at = |at: &str| {
+        Command::new(GEN_REPORT)
+            .args(["--trend", "--quiet", "out", "-o", "AT.md"])
+            .args(["--server", "mock", "--at", at])
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    let out = trend_at(env!("CARGO_PKG_VERSION"));
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let trend = std::fs::read_to_string(dir.join("AT.md")).unwrap();
+    assert!(trend.contains("2 run(s) from"), "{}", trend);
+    let out = trend_at("0.0.0-none");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("No stored runs of mock"));
+}